@@ -0,0 +1,192 @@
+//! Structural comparison of two versions of the specification.
+
+use std::fmt::Display;
+
+use crate::spec::{CStruct, Field, Spec};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    PacketAdded(String),
+    PacketRemoved(String),
+    PacketIdChanged(String, Option<u8>, Option<u8>),
+    PacketSizeChanged(String, Option<usize>, Option<usize>),
+    PacketVersionChanged(String, Option<String>, Option<String>),
+    StructAdded(String),
+    StructRemoved(String),
+    FieldAdded(String, Field),
+    FieldRemoved(String, Field),
+    FieldTypeChanged(String, String, String, String),
+    FieldMoved(String, String, usize, usize),
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Change::PacketAdded(name) => write!(f, "+ packet {name}"),
+            Change::PacketRemoved(name) => write!(f, "- packet {name}"),
+            Change::PacketIdChanged(name, old, new) => {
+                write!(
+                    f,
+                    "~ packet {name}: id {} -> {}",
+                    or_none(old),
+                    or_none(new)
+                )
+            }
+            Change::PacketSizeChanged(name, old, new) => {
+                write!(
+                    f,
+                    "~ packet {name}: size {} -> {}",
+                    or_none(old),
+                    or_none(new)
+                )
+            }
+            Change::PacketVersionChanged(name, old, new) => {
+                write!(
+                    f,
+                    "~ packet {name}: version {} -> {}",
+                    or_none(old),
+                    or_none(new)
+                )
+            }
+            Change::StructAdded(name) => write!(f, "+ struct {name}"),
+            Change::StructRemoved(name) => write!(f, "- struct {name}"),
+            Change::FieldAdded(owner, field) => {
+                write!(f, "+ {owner}.{}: {}", field.name, type_name(field))
+            }
+            Change::FieldRemoved(owner, field) => {
+                write!(f, "- {owner}.{}: {}", field.name, type_name(field))
+            }
+            Change::FieldTypeChanged(owner, name, old, new) => {
+                write!(f, "~ {owner}.{name}: {old} -> {new}")
+            }
+            Change::FieldMoved(owner, name, old, new) => {
+                write!(f, "~ {owner}.{name}: moved from position {old} to {new}")
+            }
+        }
+    }
+}
+
+fn or_none<T: Display>(value: &Option<T>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => "none".to_string(),
+    }
+}
+
+fn type_name(field: &Field) -> String {
+    match field.len {
+        Some(len) => format!("{}[{}]", field.ty, len),
+        None => field.ty.clone(),
+    }
+}
+
+/// Every struct in the document, with union members named `Union::Member`
+fn structs(spec: &Spec) -> Vec<(String, &CStruct)> {
+    let mut out = Vec::new();
+    let all = spec
+        .header
+        .iter()
+        .chain(spec.packets.iter().flat_map(|p| p.structs.iter()));
+    for s in all {
+        out.push((s.name.clone(), s));
+        for member in &s.members {
+            out.push((format!("{}::{}", s.name, member.name), member));
+        }
+    }
+    out
+}
+
+pub fn diff(old: &Spec, new: &Spec) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    for packet in &old.packets {
+        let Some(other) = new.packets.iter().find(|p| p.title == packet.title) else {
+            changes.push(Change::PacketRemoved(packet.title.clone()));
+            continue;
+        };
+        if packet.id != other.id {
+            changes.push(Change::PacketIdChanged(
+                packet.title.clone(),
+                packet.id,
+                other.id,
+            ));
+        }
+        if packet.size != other.size {
+            changes.push(Change::PacketSizeChanged(
+                packet.title.clone(),
+                packet.size,
+                other.size,
+            ));
+        }
+        if packet.version != other.version {
+            changes.push(Change::PacketVersionChanged(
+                packet.title.clone(),
+                packet.version.clone(),
+                other.version.clone(),
+            ));
+        }
+    }
+    for packet in &new.packets {
+        if !old.packets.iter().any(|p| p.title == packet.title) {
+            changes.push(Change::PacketAdded(packet.title.clone()));
+        }
+    }
+
+    let old_structs = structs(old);
+    let new_structs = structs(new);
+    for (name, s) in &old_structs {
+        match new_structs.iter().find(|(n, _)| n == name) {
+            Some((_, other)) => diff_fields(name, s, other, &mut changes),
+            None => changes.push(Change::StructRemoved(name.clone())),
+        }
+    }
+    for (name, _) in &new_structs {
+        if !old_structs.iter().any(|(n, _)| n == name) {
+            changes.push(Change::StructAdded(name.clone()));
+        }
+    }
+
+    changes
+}
+
+fn diff_fields(owner: &str, old: &CStruct, new: &CStruct, changes: &mut Vec<Change>) {
+    for (old_pos, field) in old.fields.iter().enumerate() {
+        let Some(new_pos) = new.fields.iter().position(|f| f.name == field.name) else {
+            changes.push(Change::FieldRemoved(owner.to_string(), field.clone()));
+            continue;
+        };
+        let other = &new.fields[new_pos];
+        if field.ty != other.ty || field.len != other.len {
+            changes.push(Change::FieldTypeChanged(
+                owner.to_string(),
+                field.name.clone(),
+                type_name(field),
+                type_name(other),
+            ));
+        }
+        // compare positions among the fields both versions share, so one insertion
+        // doesn't report every following field as moved
+        let shared_old = old.fields[..old_pos]
+            .iter()
+            .filter(|f| new.fields.iter().any(|n| n.name == f.name))
+            .count();
+        let shared_new = new.fields[..new_pos]
+            .iter()
+            .filter(|f| old.fields.iter().any(|o| o.name == f.name))
+            .count();
+        // a field only overtaken by one that moved past it keeps its position, so isn't news
+        if shared_old != shared_new && old_pos != new_pos {
+            changes.push(Change::FieldMoved(
+                owner.to_string(),
+                field.name.clone(),
+                old_pos,
+                new_pos,
+            ));
+        }
+    }
+    for field in &new.fields {
+        if !old.fields.iter().any(|f| f.name == field.name) {
+            changes.push(Change::FieldAdded(owner.to_string(), field.clone()));
+        }
+    }
+}
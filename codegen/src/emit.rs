@@ -0,0 +1,229 @@
+//! Emits Rust packet modules in the same shape as `telemetry/src/packet/*.rs`.
+
+use std::fmt::Write;
+
+use crate::spec::{primitive_size, CStruct, Field, Kind, PacketSpec, Spec};
+
+/// Serde only implements arrays up to this length, anything longer needs `BigArray`
+const SERDE_MAX_ARRAY: usize = 32;
+
+const DERIVES: &str =
+    "#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]";
+
+/// A generated source file, relative to the output directory
+pub struct Module {
+    pub file_name: String,
+    pub source: String,
+}
+
+pub fn modules(spec: &Spec) -> Vec<Module> {
+    let mut modules = Vec::new();
+    if let Some(header) = &spec.header {
+        modules.push(Module {
+            file_name: "header.rs".to_string(),
+            source: header_module(header),
+        });
+    }
+    for packet in &spec.packets {
+        modules.push(Module {
+            file_name: format!("{}.rs", module_name(packet)),
+            source: packet_module(spec, packet),
+        });
+    }
+    modules
+}
+
+/// Name of the packet's module in `telemetry/src/packet`, e.g. "Car Telemetry" ->
+/// "car_telemetry". A trailing "Data" is dropped as it is from the packet's short name, so
+/// "Lap Data" -> "lap"
+pub fn module_name(packet: &PacketSpec) -> String {
+    let title = packet.title.strip_suffix(" Data").unwrap_or(&packet.title);
+    title.to_lowercase().replace(' ', "_")
+}
+
+fn header_module(header: &CStruct) -> String {
+    let mut out = String::new();
+    writeln!(out, "{DERIVES}").unwrap();
+    writeln!(out, "#[repr(C, packed)]").unwrap();
+    write_struct(&mut out, header);
+    out
+}
+
+fn packet_module(spec: &Spec, packet: &PacketSpec) -> String {
+    // only called for packets that have a packet struct
+    let packet_struct = packet
+        .packet_struct()
+        .expect("packet without a packet struct");
+    let has_union = packet.structs.iter().any(|s| s.kind == Kind::Union);
+    let needs_big_array = packet
+        .structs
+        .iter()
+        .flat_map(|s| {
+            s.fields
+                .iter()
+                .chain(s.members.iter().flat_map(|m| m.fields.iter()))
+        })
+        .any(|f| f.len.is_some_and(|len| len > SERDE_MAX_ARRAY));
+
+    let mut out = String::new();
+    if needs_big_array {
+        writeln!(out, "use serde_big_array::BigArray;").unwrap();
+    }
+    writeln!(out, "use telemetry_derive::Packet;").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "use super::PacketHeader;").unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "/// # {} Packet", packet.title).unwrap();
+    writeln!(out, "///").unwrap();
+    for line in &packet.description {
+        writeln!(out, "{}", doc_line(line)).unwrap();
+    }
+    if !packet.description.is_empty() {
+        writeln!(out, "///").unwrap();
+    }
+    if let Some(frequency) = &packet.frequency {
+        writeln!(out, "/// Frequency: {frequency}  ").unwrap();
+    }
+    if let Some(size) = packet.size {
+        writeln!(out, "/// Size: {size} bytes  ").unwrap();
+    }
+    if let Some(version) = &packet.version {
+        writeln!(out, "/// Version: {version}").unwrap();
+    }
+    if has_union {
+        writeln!(out, "///").unwrap();
+        writeln!(
+            out,
+            "/// The payload is a union, so `FromBytes` and `ToBytes` have to be written by hand."
+        )
+        .unwrap();
+    }
+
+    let derive = DERIVES.replace(")]", ", Packet)]");
+    writeln!(out, "{derive}").unwrap();
+    let id = packet
+        .id
+        .map(|id| id.to_string())
+        .unwrap_or("/* unknown */".to_string());
+    let size = packet
+        .size
+        .or_else(|| spec.size_of(&packet_struct.name))
+        .map(|size| size.to_string())
+        .unwrap_or("/* unknown */".to_string());
+    match has_union {
+        true => writeln!(out, "#[packet(id = {id}, size = {size}, manual)]").unwrap(),
+        false => writeln!(out, "#[packet(id = {id}, size = {size})]").unwrap(),
+    }
+    writeln!(out, "#[repr(C, packed)]").unwrap();
+    write_struct(&mut out, packet_struct);
+
+    for s in packet
+        .structs
+        .iter()
+        .filter(|s| s.name != packet_struct.name)
+    {
+        writeln!(out).unwrap();
+        match s.kind {
+            Kind::Struct => {
+                writeln!(out, "{DERIVES}").unwrap();
+                writeln!(out, "#[repr(C, packed)]").unwrap();
+                write_struct(&mut out, s);
+            }
+            Kind::Union => write_union(&mut out, s),
+        }
+    }
+    out
+}
+
+fn write_struct(out: &mut String, s: &CStruct) {
+    writeln!(out, "pub struct {} {{", s.name).unwrap();
+    for field in &s.fields {
+        write_field(out, field);
+    }
+    writeln!(out, "}}").unwrap();
+}
+
+/// Unions become an enum of their members, plus one struct per member
+fn write_union(out: &mut String, union: &CStruct) {
+    writeln!(out, "{DERIVES}").unwrap();
+    writeln!(out, "pub enum {} {{", union.name).unwrap();
+    for member in &union.members {
+        writeln!(out, "    {0}({0}),", member.name).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+
+    for member in &union.members {
+        writeln!(out).unwrap();
+        writeln!(out, "{DERIVES}").unwrap();
+        writeln!(out, "#[repr(C, packed)]").unwrap();
+        write_struct(out, member);
+    }
+}
+
+fn write_field(out: &mut String, field: &Field) {
+    for line in &field.comment {
+        writeln!(out, "    {}", doc_line(line)).unwrap();
+    }
+    if field.len.is_some_and(|len| len > SERDE_MAX_ARRAY) {
+        writeln!(out, "    #[serde(with = \"BigArray\")]").unwrap();
+    }
+    let ty = rust_type(&field.ty);
+    let ty = match field.len {
+        Some(len) => format!("[{ty}; {len}]"),
+        None => ty,
+    };
+    writeln!(out, "    pub {}: {},", field_name(&field.name), ty).unwrap();
+}
+
+fn doc_line(line: &str) -> String {
+    match line.is_empty() {
+        true => "///".to_string(),
+        false => format!("/// {line}"),
+    }
+}
+
+fn rust_type(ty: &str) -> String {
+    let primitive = match ty {
+        "uint8" | "char" => "u8",
+        "int8" => "i8",
+        "uint16" => "u16",
+        "int16" => "i16",
+        "uint32" => "u32",
+        "int32" => "i32",
+        "uint64" => "u64",
+        "int64" => "i64",
+        "float" => "f32",
+        "double" => "f64",
+        _ => return ty.to_string(),
+    };
+    debug_assert!(primitive_size(ty).is_some());
+    primitive.to_string()
+}
+
+/// `m_sessionUID` -> `session_uid`, `m_ERSAssist` -> `ers_assist`
+pub fn field_name(name: &str) -> String {
+    let name = name.strip_prefix("m_").unwrap_or(name);
+    let chars: Vec<char> = name.chars().collect();
+    let mut out = String::new();
+
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|n| n.is_ascii_lowercase());
+            // start of a new word, or the last capital of an acronym followed by a word
+            if prev.is_ascii_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_ascii_uppercase() && next_is_lower)
+            {
+                out.push('_');
+            }
+        }
+        out.push(c.to_ascii_lowercase());
+    }
+
+    match out.as_str() {
+        "type" | "ref" | "move" | "match" | "loop" | "mod" | "use" => format!("r#{out}"),
+        _ => out,
+    }
+}
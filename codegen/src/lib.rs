@@ -0,0 +1,6 @@
+//! Reads the markdown UDP specification, generates packet modules from it and compares two
+//! versions of it. The `telemetry-codegen` binary is a thin command line over these.
+
+pub mod diff;
+pub mod emit;
+pub mod spec;
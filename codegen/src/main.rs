@@ -0,0 +1,94 @@
+//! Generates the packet modules of the `telemetry` crate from the markdown UDP specification.
+//!
+//! ```text
+//! telemetry-codegen generate <spec.md> <out-dir>
+//! telemetry-codegen diff <old-spec.md> <new-spec.md>
+//! ```
+//!
+//! Generated modules are a starting point for a new game year: hand-written additions such as
+//! `decode_car` accessors, typed enums and the event payload decoding are not part of the spec
+//! and have to be carried over when merging the output into `telemetry/src/packet`.
+
+use std::path::Path;
+use std::process::ExitCode;
+
+use codegen::{diff, emit, spec};
+
+const USAGE: &str = "usage:
+    telemetry-codegen generate <spec.md> <out-dir>
+    telemetry-codegen diff <old-spec.md> <new-spec.md>";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["generate", spec, out_dir] => generate(Path::new(spec), Path::new(out_dir)),
+        ["diff", old, new] => compare(Path::new(old), Path::new(new)),
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn load(path: &Path) -> Result<spec::Spec, String> {
+    let src = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    spec::parse(&src).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+fn generate(spec_path: &Path, out_dir: &Path) -> Result<(), String> {
+    let spec = load(spec_path)?;
+    std::fs::create_dir_all(out_dir).map_err(|e| format!("{}: {e}", out_dir.display()))?;
+
+    // the documented sizes are the contract with the game, so flag any struct that doesn't add up
+    for packet in &spec.packets {
+        let name = &packet
+            .packet_struct()
+            .expect("parser keeps packets with a struct")
+            .name;
+        match (packet.size, spec.size_of(name)) {
+            (Some(documented), Some(computed)) if documented != computed => eprintln!(
+                "warning: {name} is documented as {documented} bytes but its fields add up to {computed}"
+            ),
+            (_, None) => eprintln!("warning: {name} refers to a type that isn't defined"),
+            _ => {}
+        }
+        if packet.id.is_none() {
+            eprintln!("warning: no packet ID listed for {} Packet", packet.title);
+        }
+    }
+
+    for module in emit::modules(&spec) {
+        let path = out_dir.join(&module.file_name);
+        std::fs::write(&path, module.source).map_err(|e| format!("{}: {e}", path.display()))?;
+        println!("wrote {}", path.display());
+    }
+
+    println!();
+    println!("// module declarations for packet.rs");
+    for packet in &spec.packets {
+        println!("mod {};", emit::module_name(packet));
+    }
+    Ok(())
+}
+
+fn compare(old_path: &Path, new_path: &Path) -> Result<(), String> {
+    let old = load(old_path)?;
+    let new = load(new_path)?;
+
+    let changes = diff::diff(&old, &new);
+    if changes.is_empty() {
+        println!("no structural changes");
+    }
+    for change in changes {
+        println!("{change}");
+    }
+    Ok(())
+}
@@ -0,0 +1,340 @@
+//! Parser for the markdown UDP specification (`udp_spec.md`).
+//!
+//! Only the parts needed to generate code are understood: `###` section headings, the
+//! Frequency/Size/Version bullets, the packet ID table and the C structs inside ```c blocks.
+
+use std::fmt::Display;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spec {
+    /// Title of the document, e.g. "Data Output from F1® 23 Game"
+    pub title: String,
+    pub header: Option<CStruct>,
+    pub packets: Vec<PacketSpec>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PacketSpec {
+    /// Section title without the trailing "Packet", e.g. "Lap Data"
+    pub title: String,
+    pub description: Vec<String>,
+    pub id: Option<u8>,
+    pub frequency: Option<String>,
+    pub size: Option<usize>,
+    pub version: Option<String>,
+    /// Every struct and union in the section, in the order they appear
+    pub structs: Vec<CStruct>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Struct,
+    Union,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CStruct {
+    pub name: String,
+    pub kind: Kind,
+    pub fields: Vec<Field>,
+    /// Members of a union, each an anonymous struct named after its declarator
+    pub members: Vec<CStruct>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub ty: String,
+    pub name: String,
+    pub len: Option<usize>,
+    pub comment: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl PacketSpec {
+    /// The struct that starts with the packet header, i.e. the packet itself
+    pub fn packet_struct(&self) -> Option<&CStruct> {
+        self.structs
+            .iter()
+            .find(|s| s.fields.first().is_some_and(|f| f.ty == "PacketHeader"))
+    }
+}
+
+impl Spec {
+    /// Looks up a struct or union by name, anywhere in the document
+    pub fn find(&self, name: &str) -> Option<&CStruct> {
+        self.header
+            .iter()
+            .chain(self.packets.iter().flat_map(|p| p.structs.iter()))
+            .find(|s| s.name == name)
+    }
+
+    /// Size in bytes of a type as laid out on the wire (structs are packed)
+    pub fn size_of(&self, ty: &str) -> Option<usize> {
+        if let Some(size) = primitive_size(ty) {
+            return Some(size);
+        }
+        let s = self.find(ty)?;
+        match s.kind {
+            Kind::Struct => self.fields_size(&s.fields),
+            Kind::Union => s
+                .members
+                .iter()
+                .map(|m| self.fields_size(&m.fields))
+                .try_fold(0, |max, size| size.map(|size| max.max(size))),
+        }
+    }
+
+    fn fields_size(&self, fields: &[Field]) -> Option<usize> {
+        fields
+            .iter()
+            .map(|f| Some(self.size_of(&f.ty)? * f.len.unwrap_or(1)))
+            .sum()
+    }
+}
+
+pub fn primitive_size(ty: &str) -> Option<usize> {
+    match ty {
+        "uint8" | "int8" | "char" => Some(1),
+        "uint16" | "int16" => Some(2),
+        "uint32" | "int32" | "float" => Some(4),
+        "uint64" | "int64" | "double" => Some(8),
+        _ => None,
+    }
+}
+
+pub fn parse(src: &str) -> Result<Spec, ParseError> {
+    let mut spec = Spec {
+        title: String::new(),
+        header: None,
+        packets: Vec::new(),
+    };
+    let mut ids: Vec<(String, u8)> = Vec::new();
+    let mut section = String::new();
+    let mut current: Option<PacketSpec> = None;
+    let mut in_description = false;
+
+    let lines: Vec<&str> = src.lines().collect();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim();
+
+        if let Some(title) = trimmed.strip_prefix("# ") {
+            spec.title = title.to_string();
+        } else if trimmed.starts_with("## ") {
+            // leaving the packet information chapter
+            spec.packets.extend(current.take());
+            section.clear();
+        } else if let Some(title) = trimmed.strip_prefix("### ") {
+            spec.packets.extend(current.take());
+            section = title.to_string();
+            if let Some(title) = section.strip_suffix(" Packet") {
+                current = Some(PacketSpec {
+                    title: title.to_string(),
+                    description: Vec::new(),
+                    id: None,
+                    frequency: None,
+                    size: None,
+                    version: None,
+                    structs: Vec::new(),
+                });
+                in_description = true;
+            }
+        } else if trimmed.starts_with("```c") {
+            let start = i + 1;
+            let mut end = start;
+            while end < lines.len() && !lines[end].trim().starts_with("```") {
+                end += 1;
+            }
+            let structs = parse_c(&lines[start..end], start + 1)?;
+            i = end;
+
+            if section == "Packet Header" {
+                spec.header = structs.into_iter().find(|s| s.name == "PacketHeader");
+            } else if let Some(packet) = current.as_mut() {
+                packet.structs.extend(structs);
+            }
+            in_description = false;
+        } else if section == "Packet IDs" && trimmed.starts_with('|') {
+            let cells: Vec<&str> = trimmed
+                .trim_matches('|')
+                .split('|')
+                .map(str::trim)
+                .collect();
+            if let [name, value, ..] = cells[..] {
+                if let Ok(value) = value.parse() {
+                    ids.push((name.to_string(), value));
+                }
+            }
+        } else if let Some(packet) = current.as_mut() {
+            if let Some(value) = bullet(trimmed, "Frequency") {
+                packet.frequency = Some(value.to_string());
+                in_description = false;
+            } else if let Some(value) = bullet(trimmed, "Size") {
+                packet.size = value.trim_end_matches("bytes").trim().parse().ok();
+                in_description = false;
+            } else if let Some(value) = bullet(trimmed, "Version") {
+                packet.version = Some(value.to_string());
+                in_description = false;
+            } else if in_description
+                && !trimmed.starts_with('|')
+                && !trimmed.starts_with("---")
+                && (!trimmed.is_empty() || !packet.description.is_empty())
+            {
+                packet.description.push(trimmed.to_string());
+            }
+        }
+        i += 1;
+    }
+    spec.packets.extend(current.take());
+
+    for packet in spec.packets.iter_mut() {
+        while packet.description.last().is_some_and(|l| l.is_empty()) {
+            packet.description.pop();
+        }
+        packet.id = ids
+            .iter()
+            .find(|(name, _)| *name == packet.title)
+            .map(|(_, id)| *id);
+    }
+    // a heading ending in "Packet" without any struct underneath isn't a packet definition
+    spec.packets.retain(|p| p.packet_struct().is_some());
+
+    Ok(spec)
+}
+
+fn bullet<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    line.strip_prefix("- **")?
+        .strip_prefix(name)?
+        .strip_prefix("**:")
+        .map(str::trim)
+}
+
+/// Parses the structs and unions in the body of a ```c block. `first_line` is the line number
+/// of `lines[0]` in the document, for error messages.
+fn parse_c(lines: &[&str], first_line: usize) -> Result<Vec<CStruct>, ParseError> {
+    let mut done = Vec::new();
+    let mut stack: Vec<CStruct> = Vec::new();
+    // a declaration that hasn't reached its `;` yet, and the column it started at
+    let mut pending: Option<(String, usize)> = None;
+    // standalone comments waiting for the next field
+    let mut leading: Vec<String> = Vec::new();
+    let mut field_column = 0;
+
+    for (offset, raw) in lines.iter().enumerate() {
+        let line_no = first_line + offset;
+        let error = |message: &str| ParseError {
+            line: line_no,
+            message: message.to_string(),
+        };
+
+        let (code, comment) = match raw.find("//") {
+            Some(idx) => (&raw[..idx], Some(raw[idx + 2..].trim().to_string())),
+            None => (*raw, None),
+        };
+        let code_trimmed = code.trim();
+        let column = raw.len() - raw.trim_start().len();
+
+        if code_trimmed.is_empty() && pending.is_none() {
+            let Some(comment) = comment else { continue };
+            let last = stack.last_mut().and_then(|s| s.fields.last_mut());
+            match last {
+                // indented past the fields, so it carries on the previous field's comment
+                Some(field) if column > field_column => field.comment.push(comment),
+                _ => leading.push(comment),
+            }
+            continue;
+        }
+
+        if let Some(rest) = code_trimmed
+            .strip_prefix("struct")
+            .or_else(|| code_trimmed.strip_prefix("union"))
+            .filter(|rest| rest.trim_end().ends_with('{'))
+        {
+            let kind = match code_trimmed.starts_with("union") {
+                true => Kind::Union,
+                false => Kind::Struct,
+            };
+            stack.push(CStruct {
+                name: rest.trim_end_matches('{').trim().to_string(),
+                kind,
+                fields: Vec::new(),
+                members: Vec::new(),
+            });
+            leading.clear();
+            continue;
+        }
+
+        if let Some(rest) = code_trimmed.strip_prefix('}') {
+            let mut closed = stack.pop().ok_or_else(|| error("unmatched `}`"))?;
+            let declarator = rest.trim().trim_end_matches(';').trim();
+            match stack.last_mut() {
+                Some(parent) if parent.kind == Kind::Union => {
+                    closed.name = declarator.to_string();
+                    parent.members.push(closed);
+                }
+                Some(_) => return Err(error("nested structs are only supported inside unions")),
+                None => done.push(closed),
+            }
+            continue;
+        }
+
+        let (mut decl, start_column) = pending.take().unwrap_or((String::new(), column));
+        // declarations are never split across lines on purpose, so a break inside one is a
+        // formatting glitch in the document and the halves belong together
+        decl.push_str(code_trimmed);
+        let Some(decl) = decl.strip_suffix(';') else {
+            pending = Some((decl, start_column));
+            continue;
+        };
+
+        let owner = stack
+            .last_mut()
+            .ok_or_else(|| error("field declared outside of a struct"))?;
+        let mut field = parse_field(decl).ok_or_else(|| error("unrecognised field declaration"))?;
+        field.comment = std::mem::take(&mut leading);
+        field.comment.extend(comment);
+        field_column = start_column;
+        owner.fields.push(field);
+    }
+
+    if !stack.is_empty() || pending.is_some() {
+        return Err(ParseError {
+            line: first_line + lines.len(),
+            message: "unterminated struct".to_string(),
+        });
+    }
+    Ok(done)
+}
+
+fn parse_field(decl: &str) -> Option<Field> {
+    let mut parts = decl.split_whitespace();
+    let ty = parts.next()?.to_string();
+    let declarator: String = parts.collect();
+
+    let (name, len) = match declarator.split_once('[') {
+        Some((name, len)) => (name, Some(len.strip_suffix(']')?.parse().ok()?)),
+        None => (declarator.as_str(), None),
+    };
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+
+    Some(Field {
+        ty,
+        name: name.to_string(),
+        len,
+        comment: Vec::new(),
+    })
+}
@@ -0,0 +1,92 @@
+use codegen::diff::{diff, Change};
+use codegen::spec::parse;
+
+const OLD: &str = r#"
+### Packet IDs
+
+| Packet Name | Value | Description |
+|-------------|-------|-------------|
+| Lap Data    | 2     | Lap times   |
+| Tyres       | 12    | Tyre sets   |
+
+### Lap Data Packet
+
+- **Size**: 9 bytes
+- **Version**: 1
+
+```c
+struct PacketLapData {
+    PacketHeader    m_header;
+    uint32          m_lastLapTimeInMS;
+    uint8           m_sector;
+    uint8           m_pitStatus;
+    uint8           m_resultStatus;
+};
+```
+
+### Tyres Packet
+
+```c
+struct PacketTyresData {
+    PacketHeader    m_header;
+};
+```
+"#;
+
+const NEW: &str = r#"
+### Packet IDs
+
+| Packet Name | Value | Description |
+|-------------|-------|-------------|
+| Lap Data    | 2     | Lap times   |
+| Motion Ex   | 13    | Motion      |
+
+### Lap Data Packet
+
+- **Size**: 12 bytes
+- **Version**: 2
+
+```c
+struct PacketLapData {
+    PacketHeader    m_header;
+    uint32          m_lastLapTimeInMS;
+    float           m_lapDistance;
+    uint8           m_pitStatus;
+    uint16          m_sector;
+    uint8           m_resultStatus;
+};
+```
+
+### Motion Ex Packet
+
+```c
+struct PacketMotionExData {
+    PacketHeader    m_header;
+};
+```
+"#;
+
+#[test]
+fn reports_structural_changes() {
+    let changes = diff(&parse(OLD).unwrap(), &parse(NEW).unwrap());
+    let lines: Vec<String> = changes.iter().map(Change::to_string).collect();
+    assert_eq!(
+        lines,
+        [
+            "~ packet Lap Data: size 9 -> 12",
+            "~ packet Lap Data: version 1 -> 2",
+            "- packet Tyres",
+            "+ packet Motion Ex",
+            "~ PacketLapData.m_sector: uint8 -> uint16",
+            "~ PacketLapData.m_sector: moved from position 2 to 4",
+            "+ PacketLapData.m_lapDistance: float",
+            "- struct PacketTyresData",
+            "+ struct PacketMotionExData",
+        ]
+    );
+}
+
+#[test]
+fn identical_specs_have_no_changes() {
+    assert!(diff(&parse(OLD).unwrap(), &parse(OLD).unwrap()).is_empty());
+}
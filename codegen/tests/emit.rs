@@ -0,0 +1,70 @@
+use std::path::Path;
+
+use codegen::emit::{field_name, module_name, modules};
+use codegen::spec::parse;
+
+fn repo_spec() -> codegen::spec::Spec {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../udp_spec.md");
+    parse(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+#[test]
+fn modules_are_named_like_the_telemetry_crate() {
+    let spec = repo_spec();
+    let names: Vec<String> = spec.packets.iter().map(module_name).collect();
+    assert_eq!(
+        names,
+        [
+            "motion",
+            "session",
+            "lap",
+            "event",
+            "participants",
+            "car_setups",
+            "car_telemetry",
+            "car_status",
+            "final_classification",
+            "lobby_info",
+            "car_damage",
+            "session_history",
+            "tyre_sets",
+            "motion_ex",
+        ]
+    );
+
+    // so the output can be diffed file by file against the hand-maintained modules
+    let packet_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../telemetry/src/packet");
+    for module in modules(&spec) {
+        assert!(
+            packet_dir.join(&module.file_name).exists(),
+            "telemetry has no {}",
+            module.file_name
+        );
+    }
+}
+
+#[test]
+fn packets_derive_with_their_id_and_size() {
+    let spec = repo_spec();
+    let modules = modules(&spec);
+    let lap = modules.iter().find(|m| m.file_name == "lap.rs").unwrap();
+    assert!(lap.source.contains("#[packet(id = 2, size = 1131)]"));
+    assert!(lap.source.contains("pub struct PacketLapData {"));
+    assert!(lap.source.contains("    pub lap_data: [LapData; 22],"));
+
+    // the event details are a union, which can't be decoded by the derive
+    let event = modules.iter().find(|m| m.file_name == "event.rs").unwrap();
+    assert!(event
+        .source
+        .contains("#[packet(id = 3, size = 45, manual)]"));
+    assert!(event.source.contains("pub enum EventDataDetails {"));
+}
+
+#[test]
+fn field_names_are_snake_case() {
+    assert_eq!(field_name("m_sessionUID"), "session_uid");
+    assert_eq!(field_name("m_ERSAssist"), "ers_assist");
+    assert_eq!(field_name("m_sector1TimeInMS"), "sector1_time_in_ms");
+    assert_eq!(field_name("vehicleIdx"), "vehicle_idx");
+    assert_eq!(field_name("m_type"), "r#type");
+}
@@ -0,0 +1,166 @@
+use codegen::spec::{parse, Kind, Spec};
+
+const SPEC: &str = r#"# Data Output from F1® 23 Game
+
+## Packet Information
+
+### Packet Header
+
+```c
+struct PacketHeader {
+    uint16    m_packetFormat;            // 2023
+    uint8     m_packetId;                // Identifier for the packet type
+    uint64    m_sessionUID;              // Unique identifier for the session
+};
+```
+
+### Packet IDs
+
+| Packet Name | Value | Description                      |
+|-------------|-------|----------------------------------|
+| Lap Data    | 2     | Lap times of every car           |
+| Event       | 3     | Notable events during a session  |
+
+### Lap Data Packet
+
+The lap data packet gives details of all the cars in the session.
+
+- **Frequency**: Rate as specified in menus
+- **Size**: 27 bytes
+- **Version**: 1
+
+```c
+struct LapData {
+    uint32   m_lastLapTimeInMS;          // Last lap time in milliseconds
+    uint8    m_driverStatus;             // 0 = in garage, 1 = flying lap
+                                         // 2 = in lap
+};
+```
+
+```c
+struct PacketLapData {
+    PacketHeader    m_header;              // Header
+    LapData         m_lapData[2];          // Lap data for all cars on track
+    // Index of Personal Best car in time trial
+    uint8           m_timeTrialPBCarIdx;
+};
+```
+
+### Event Packet
+
+- **Size**: 16 bytes
+
+```c
+union EventDataDetails {
+    struct {
+        uint8   vehicleIdx;   // Vehicle index of car achieving fastest lap
+        float   lapTime;      // Lap time is in seconds
+    } FastestLap;
+
+    struct {
+        uint8   vehicleIdx;   // Vehicle index of car retiring
+    } Retirement;
+};
+
+struct PacketEventData {
+    PacketHeader        m_header;
+    uint8               m_eventStringCode[4];
+    EventDataDetails    m_eventDetails;
+};
+```
+
+### Restrictions Packet
+
+Headings ending in "Packet" without a struct aren't packets.
+
+## FAQS
+"#;
+
+fn spec() -> Spec {
+    parse(SPEC).unwrap()
+}
+
+#[test]
+fn parses_the_header_and_packets() {
+    let spec = spec();
+    assert_eq!(spec.title, "Data Output from F1® 23 Game");
+
+    let header = spec.header.as_ref().unwrap();
+    let fields: Vec<&str> = header.fields.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(fields, ["m_packetFormat", "m_packetId", "m_sessionUID"]);
+
+    let titles: Vec<&str> = spec.packets.iter().map(|p| p.title.as_str()).collect();
+    assert_eq!(titles, ["Lap Data", "Event"]);
+
+    let lap = &spec.packets[0];
+    assert_eq!(lap.id, Some(2));
+    assert_eq!(lap.size, Some(27));
+    assert_eq!(lap.version.as_deref(), Some("1"));
+    assert_eq!(lap.frequency.as_deref(), Some("Rate as specified in menus"));
+    assert_eq!(
+        lap.description,
+        ["The lap data packet gives details of all the cars in the session."]
+    );
+    assert_eq!(spec.packets[1].id, Some(3));
+    assert_eq!(spec.packets[1].version, None);
+}
+
+#[test]
+fn parses_fields_arrays_and_comments() {
+    let spec = spec();
+    let lap = &spec.packets[0];
+    assert_eq!(lap.packet_struct().unwrap().name, "PacketLapData");
+
+    let lap_data = spec.find("LapData").unwrap();
+    assert_eq!(lap_data.kind, Kind::Struct);
+    let status = &lap_data.fields[1];
+    assert_eq!(status.ty, "uint8");
+    // a comment on the next line, indented past the fields, carries on the field's
+    assert_eq!(
+        status.comment,
+        ["0 = in garage, 1 = flying lap", "2 = in lap"]
+    );
+
+    let packet = spec.find("PacketLapData").unwrap();
+    let cars = &packet.fields[1];
+    assert_eq!((cars.ty.as_str(), cars.len), ("LapData", Some(2)));
+    // a comment on a line of its own belongs to the field below it
+    let pb = &packet.fields[2];
+    assert_eq!(pb.name, "m_timeTrialPBCarIdx");
+    assert_eq!(pb.comment, ["Index of Personal Best car in time trial"]);
+}
+
+#[test]
+fn parses_unions_into_their_members() {
+    let spec = spec();
+    let details = spec.find("EventDataDetails").unwrap();
+    assert_eq!(details.kind, Kind::Union);
+    let members: Vec<&str> = details.members.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(members, ["FastestLap", "Retirement"]);
+    assert_eq!(details.members[0].fields[1].name, "lapTime");
+}
+
+#[test]
+fn sizes_add_up_packed() {
+    let spec = spec();
+    assert_eq!(spec.size_of("PacketHeader"), Some(11));
+    assert_eq!(spec.size_of("LapData"), Some(5));
+    assert_eq!(spec.size_of("PacketLapData"), Some(11 + 2 * 5 + 1));
+    // a union is as big as its biggest member
+    assert_eq!(spec.size_of("EventDataDetails"), Some(5));
+    assert_eq!(spec.size_of("PacketEventData"), Some(11 + 4 + 5));
+    assert_eq!(spec.size_of("Unknown"), None);
+}
+
+#[test]
+fn errors_point_at_the_line() {
+    let src = "### Lap Data Packet\n\n```c\nstruct LapData {\n    uint32 m_lapTime;\n```\n";
+    let error = parse(src).unwrap_err();
+    assert_eq!(error.line, 6);
+    assert_eq!(error.to_string(), "line 6: unterminated struct");
+
+    let src = "### Lap Data Packet\n\n```c\nstruct LapData {\n    uint32 m_lap-time;\n};\n```\n";
+    let error = parse(src).unwrap_err();
+    assert_eq!(error.line, 5);
+    assert_eq!(error.message, "unrecognised field declaration");
+}
@@ -0,0 +1,158 @@
+//! HTTP API exposing the current session state as JSON.
+//!
+//! Routes are described by the OpenAPI document served at `/api/openapi.json`. Session routes
+//! answer for the feed that sent the latest packet, or for the one given as `?feed=<src>/<session
+//! uid>`, as listed by `/api/feeds`.
+
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use telemetry::MAX_CARS;
+
+use crate::feed::{FeedId, FeedStats};
+use crate::health::{FeedHealth, HealthMonitor};
+use crate::state::{LoggedEvent, SessionSnapshot, SessionState};
+
+const OPENAPI: &str = include_str!("openapi.json");
+
+enum ApiError {
+    /// The packet the route needs hasn't been received yet this session
+    NoData(&'static str),
+    CarIndexOutOfBounds(usize),
+    UnknownFeed(FeedId),
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::NoData(packet) => (
+                StatusCode::NOT_FOUND,
+                format!("no {packet} packet received yet"),
+            ),
+            ApiError::CarIndexOutOfBounds(idx) => (
+                StatusCode::BAD_REQUEST,
+                format!("car index {idx} is out of bounds"),
+            ),
+            ApiError::UnknownFeed(feed) => (StatusCode::NOT_FOUND, format!("no feed {feed}")),
+        };
+        (status, Json(json!({ "error": message }))).into_response()
+    }
+}
+
+type ApiResult = Result<Response, ApiError>;
+
+fn found<T: Serialize>(value: Option<T>, packet: &'static str) -> ApiResult {
+    value
+        .map(|value| Json(value).into_response())
+        .ok_or(ApiError::NoData(packet))
+}
+
+fn car_index(idx: usize) -> Result<usize, ApiError> {
+    (idx < MAX_CARS)
+        .then_some(idx)
+        .ok_or(ApiError::CarIndexOutOfBounds(idx))
+}
+
+#[derive(Debug, Deserialize)]
+struct FeedQuery {
+    feed: Option<FeedId>,
+}
+
+impl FeedQuery {
+    /// Runs `f` against the chosen feed's snapshot, or the latest feed's if none was chosen
+    fn read<R>(
+        &self,
+        state: &SessionState,
+        f: impl FnOnce(&SessionSnapshot) -> R,
+    ) -> Result<R, ApiError> {
+        match &self.feed {
+            Some(feed) => state.read_feed(feed, f).ok_or(ApiError::UnknownFeed(*feed)),
+            None => Ok(state.read(f)),
+        }
+    }
+}
+
+pub fn router(state: Arc<SessionState>, health: Arc<HealthMonitor>) -> Router {
+    let health = Router::new()
+        .route("/api/health", get(feed_health))
+        .with_state(health);
+    Router::new()
+        .route("/api/feeds", get(feeds))
+        .route("/api/session", get(session))
+        .route("/api/standings", get(standings))
+        .route("/api/cars/:idx/status", get(car_status))
+        .route("/api/cars/:idx/damage", get(car_damage))
+        .route("/api/events", get(events))
+        .route("/api/final-classification", get(final_classification))
+        .route("/api/openapi.json", get(openapi))
+        .with_state(state)
+        .merge(health)
+}
+
+async fn feeds(State(state): State<Arc<SessionState>>) -> Json<Vec<FeedStats>> {
+    Json(state.feeds())
+}
+
+async fn feed_health(State(health): State<Arc<HealthMonitor>>) -> Json<Vec<FeedHealth>> {
+    Json(health.report())
+}
+
+async fn session(State(state): State<Arc<SessionState>>, Query(q): Query<FeedQuery>) -> ApiResult {
+    found(q.read(&state, |s| s.session)?, "session")
+}
+
+async fn standings(
+    State(state): State<Arc<SessionState>>,
+    Query(q): Query<FeedQuery>,
+) -> ApiResult {
+    let standings = q.read(&state, |s| s.lap.is_some().then(|| s.standings()))?;
+    found(standings, "lap")
+}
+
+async fn car_status(
+    State(state): State<Arc<SessionState>>,
+    Query(q): Query<FeedQuery>,
+    Path(idx): Path<usize>,
+) -> ApiResult {
+    let idx = car_index(idx)?;
+    let status = q.read(&state, |s| s.car_status.map(|p| p.car_status_data[idx]))?;
+    found(status, "car status")
+}
+
+async fn car_damage(
+    State(state): State<Arc<SessionState>>,
+    Query(q): Query<FeedQuery>,
+    Path(idx): Path<usize>,
+) -> ApiResult {
+    let idx = car_index(idx)?;
+    let damage = q.read(&state, |s| s.car_damage.map(|p| p.car_damage_data[idx]))?;
+    found(damage, "car damage")
+}
+
+async fn events(State(state): State<Arc<SessionState>>, Query(q): Query<FeedQuery>) -> ApiResult {
+    let events: Vec<LoggedEvent> = q.read(&state, |s| s.events.iter().cloned().collect())?;
+    Ok(Json(events).into_response())
+}
+
+async fn final_classification(
+    State(state): State<Arc<SessionState>>,
+    Query(q): Query<FeedQuery>,
+) -> ApiResult {
+    let classification = q.read(&state, |s| {
+        s.final_classification.map(|p| {
+            let num_cars = (p.num_cars as usize).min(MAX_CARS);
+            p.classification_data[..num_cars].to_vec()
+        })
+    })?;
+    found(classification, "final classification")
+}
+
+async fn openapi() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "application/json")], OPENAPI)
+}
@@ -0,0 +1,108 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::UdpSocket;
+
+/// An address to receive telemetry on, and how to receive it.
+///
+/// The game sends to one address, or to the whole LAN with `broadcast="true"` in its UDP config.
+/// Broadcasts only reach sockets bound to an unspecified address such as `0.0.0.0:20777`, and
+/// several programs on the same machine can receive them at once. Multicast works the same way
+/// for the groups a socket has joined, e.g. when a relay sends one game's output to a group.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BindOptions {
+    pub addr: SocketAddr,
+    /// Receive LAN broadcasts, sharing the port with other programs doing the same
+    pub broadcast: bool,
+    /// Multicast groups to join, of the same IP version as `addr`
+    pub multicast: Vec<IpAddr>,
+    /// Interface to join IPv4 groups on, the system's default if `None`
+    pub interface: Option<Ipv4Addr>,
+}
+
+impl BindOptions {
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            broadcast: false,
+            multicast: Vec::new(),
+            interface: None,
+        }
+    }
+
+    pub fn broadcast(mut self) -> Self {
+        self.broadcast = true;
+        self
+    }
+
+    pub fn join(mut self, group: IpAddr) -> Self {
+        self.multicast.push(group);
+        self
+    }
+
+    pub fn interface(mut self, interface: Ipv4Addr) -> Self {
+        self.interface = Some(interface);
+        self
+    }
+
+    /// Why these options can't work, if they can't
+    pub fn check(&self) -> Result<(), String> {
+        if self.broadcast && self.addr.is_ipv6() {
+            return Err(format!(
+                "{} can't receive broadcasts, IPv6 has no broadcast",
+                self.addr
+            ));
+        }
+        for group in &self.multicast {
+            if !group.is_multicast() {
+                return Err(format!("{group} isn't a multicast group"));
+            }
+            if group.is_ipv4() != self.addr.is_ipv4() {
+                return Err(format!(
+                    "{} can't join {group}, they're different IP versions",
+                    self.addr
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn open(&self) -> io::Result<UdpSocket> {
+        self.check()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        let socket = Socket::new(
+            Domain::for_address(self.addr),
+            Type::DGRAM,
+            Some(Protocol::UDP),
+        )?;
+        if self.addr.is_ipv6() {
+            // so `[::]` and `0.0.0.0` can be bound side by side on every platform
+            socket.set_only_v6(true)?;
+        }
+        if self.broadcast || !self.multicast.is_empty() {
+            socket.set_reuse_address(true)?;
+        }
+        if self.broadcast {
+            socket.set_broadcast(true)?;
+        }
+        socket.set_nonblocking(true)?;
+        socket.bind(&self.addr.into())?;
+
+        for group in &self.multicast {
+            match group {
+                IpAddr::V4(group) => socket
+                    .join_multicast_v4(group, &self.interface.unwrap_or(Ipv4Addr::UNSPECIFIED))?,
+                IpAddr::V6(group) => socket.join_multicast_v6(group, 0)?,
+            }
+        }
+        UdpSocket::from_std(socket.into())
+    }
+}
+
+impl From<SocketAddr> for BindOptions {
+    fn from(addr: SocketAddr) -> Self {
+        Self::new(addr)
+    }
+}
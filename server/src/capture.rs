@@ -0,0 +1,368 @@
+//! Capture file format for recorded sessions.
+//!
+//! All integers are little-endian. A file is a header followed by any number of records, and a
+//! footer once the file has been finished:
+//!
+//! ```text
+//! header:  magic "F1CAPTUR" | u16 capture version | u16 packet format | u64 session uid
+//!          | u64 created (unix micros) | u8 tool version length | tool version (utf-8)
+//! record:  u32 payload length | u64 received (unix micros) | address | payload
+//! address: u8 family (4 or 6) | 4 or 16 byte ip | u16 port
+//! footer:  u32 0xffffffff | u64 record count | u64 finished (unix micros)
+//! ```
+//!
+//! A file without a footer was cut short, e.g. by a crash, and holds every record up to the
+//! last complete one. Version 1 files never have a footer.
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use telemetry::{Attributes, FromBytes, Packet, PacketDescriptor};
+
+use crate::feed::FeedId;
+
+pub const MAGIC: &[u8; 8] = b"F1CAPTUR";
+pub const CAPTURE_VERSION: u16 = 2;
+/// File extension used for capture files
+pub const EXTENSION: &str = "f1cap";
+
+/// Largest datagram we'll accept when reading, anything bigger means the file is corrupt
+const MAX_RECORD_LEN: usize = 64 * 1024;
+
+/// Stands in for a record length to start the footer
+const FOOTER_MARKER: u32 = u32::MAX;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureHeader {
+    pub capture_version: u16,
+    /// `packet_format` of the packets in the file, e.g. 2023
+    pub packet_format: u16,
+    pub session_uid: u64,
+    pub created: SystemTime,
+    /// Version of the tool that wrote the file
+    pub tool_version: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureRecord {
+    pub received_at: SystemTime,
+    pub src: SocketAddr,
+    /// The datagram exactly as it was received
+    pub data: Vec<u8>,
+}
+
+/// Written by [`CaptureWriter::finish`], so a file with one was closed properly
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureFooter {
+    pub records: u64,
+    pub finished: SystemTime,
+}
+
+impl CaptureHeader {
+    pub fn new(packet_format: u16, session_uid: u64) -> Self {
+        Self {
+            capture_version: CAPTURE_VERSION,
+            packet_format,
+            session_uid,
+            created: SystemTime::now(),
+            tool_version: concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"))
+                .to_string(),
+        }
+    }
+}
+
+fn to_micros(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_micros() as u64)
+        .unwrap_or(0)
+}
+
+fn from_micros(micros: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_micros(micros)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+pub struct CaptureWriter<W: Write> {
+    inner: W,
+    records: u64,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    pub fn new(mut inner: W, header: &CaptureHeader) -> io::Result<Self> {
+        let version = header.tool_version.as_bytes();
+        let version = &version[..version.len().min(u8::MAX as usize)];
+
+        inner.write_all(MAGIC)?;
+        inner.write_all(&header.capture_version.to_le_bytes())?;
+        inner.write_all(&header.packet_format.to_le_bytes())?;
+        inner.write_all(&header.session_uid.to_le_bytes())?;
+        inner.write_all(&to_micros(header.created).to_le_bytes())?;
+        inner.write_all(&[version.len() as u8])?;
+        inner.write_all(version)?;
+        Ok(Self { inner, records: 0 })
+    }
+
+    pub fn write_record(&mut self, record: &CaptureRecord) -> io::Result<()> {
+        let len = u32::try_from(record.data.len()).map_err(|_| invalid("record too large"))?;
+        self.inner.write_all(&len.to_le_bytes())?;
+        self.inner
+            .write_all(&to_micros(record.received_at).to_le_bytes())?;
+        match record.src.ip() {
+            IpAddr::V4(ip) => {
+                self.inner.write_all(&[4])?;
+                self.inner.write_all(&ip.octets())?;
+            }
+            IpAddr::V6(ip) => {
+                self.inner.write_all(&[6])?;
+                self.inner.write_all(&ip.octets())?;
+            }
+        }
+        self.inner.write_all(&record.src.port().to_le_bytes())?;
+        self.inner.write_all(&record.data)?;
+        self.records += 1;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    /// Writes the footer and flushes, after which nothing more can be written
+    pub fn finish(mut self) -> io::Result<W> {
+        self.inner.write_all(&FOOTER_MARKER.to_le_bytes())?;
+        self.inner.write_all(&self.records.to_le_bytes())?;
+        self.inner
+            .write_all(&to_micros(SystemTime::now()).to_le_bytes())?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+pub struct CaptureReader<R: Read> {
+    inner: R,
+    header: CaptureHeader,
+    /// Byte offset of the next record
+    position: u64,
+    footer: Option<CaptureFooter>,
+}
+
+fn read_array<const N: usize, R: Read>(reader: &mut R) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+impl<R: Read> CaptureReader<R> {
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        if &read_array::<8, _>(&mut inner)? != MAGIC {
+            return Err(invalid("not a capture file"));
+        }
+        let capture_version = u16::from_le_bytes(read_array(&mut inner)?);
+        if capture_version > CAPTURE_VERSION {
+            return Err(invalid("capture file is from a newer version"));
+        }
+        let packet_format = u16::from_le_bytes(read_array(&mut inner)?);
+        let session_uid = u64::from_le_bytes(read_array(&mut inner)?);
+        let created = from_micros(u64::from_le_bytes(read_array(&mut inner)?));
+        let [version_len] = read_array(&mut inner)?;
+        let mut version = vec![0; version_len as usize];
+        inner.read_exact(&mut version)?;
+
+        Ok(Self {
+            inner,
+            position: (8 + 2 + 2 + 8 + 8 + 1 + version.len()) as u64,
+            footer: None,
+            header: CaptureHeader {
+                capture_version,
+                packet_format,
+                session_uid,
+                created,
+                tool_version: String::from_utf8_lossy(&version).into_owned(),
+            },
+        })
+    }
+
+    pub fn header(&self) -> &CaptureHeader {
+        &self.header
+    }
+
+    /// Byte offset of the next record, usable with [`CaptureReader::seek`]
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Set once the footer has been read, i.e. after the last record of a finished file
+    pub fn footer(&self) -> Option<&CaptureFooter> {
+        self.footer.as_ref()
+    }
+
+    /// Reads the next record, or `None` at the end of the file or its footer
+    pub fn read_record(&mut self) -> io::Result<Option<CaptureRecord>> {
+        if self.footer.is_some() {
+            return Ok(None);
+        }
+        let mut len = [0; 4];
+        // a clean end of file can only happen between records
+        match self.inner.read(&mut len[..1])? {
+            0 => return Ok(None),
+            _ => self.inner.read_exact(&mut len[1..])?,
+        }
+        let len = u32::from_le_bytes(len);
+        if len == FOOTER_MARKER {
+            self.footer = Some(CaptureFooter {
+                records: u64::from_le_bytes(read_array(&mut self.inner)?),
+                finished: from_micros(u64::from_le_bytes(read_array(&mut self.inner)?)),
+            });
+            return Ok(None);
+        }
+        let len = len as usize;
+        if len > MAX_RECORD_LEN {
+            return Err(invalid("record length out of range"));
+        }
+
+        let received_at = from_micros(u64::from_le_bytes(read_array(&mut self.inner)?));
+        let ip = match read_array::<1, _>(&mut self.inner)? {
+            [4] => IpAddr::V4(Ipv4Addr::from(read_array::<4, _>(&mut self.inner)?)),
+            [6] => IpAddr::V6(Ipv6Addr::from(read_array::<16, _>(&mut self.inner)?)),
+            _ => return Err(invalid("unknown address family")),
+        };
+        let port = u16::from_le_bytes(read_array(&mut self.inner)?);
+        let mut data = vec![0; len];
+        self.inner.read_exact(&mut data)?;
+
+        let address_len = if ip.is_ipv4() { 4 } else { 16 };
+        self.position += (4 + 8 + 1 + address_len + 2 + len) as u64;
+
+        Ok(Some(CaptureRecord {
+            received_at,
+            src: SocketAddr::new(ip, port),
+            data,
+        }))
+    }
+}
+
+impl<R: Read + Seek> CaptureReader<R> {
+    /// Moves to a record offset previously returned by [`CaptureReader::position`]
+    pub fn seek(&mut self, position: u64) -> io::Result<()> {
+        self.inner.seek(SeekFrom::Start(position))?;
+        self.position = position;
+        self.footer = None;
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for CaptureReader<R> {
+    type Item = io::Result<CaptureRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+/// One feed seen in a capture file: a session as sent by one sender
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionSummary {
+    pub feed: FeedId,
+    pub session_uid: u64,
+    pub packet_format: u16,
+    pub packets: u64,
+    pub first_session_time: f32,
+    pub last_session_time: f32,
+}
+
+/// What a capture file holds, read without stopping at datagrams that don't decode
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CaptureSummary {
+    pub records: u64,
+    /// Decoded packets per packet short name, e.g. `car_telemetry`
+    pub packets: BTreeMap<String, u64>,
+    /// In the order they first appear
+    pub sessions: Vec<SessionSummary>,
+    pub first_received: Option<SystemTime>,
+    pub last_received: Option<SystemTime>,
+    /// Datagrams that failed to decode, per error message
+    pub decode_errors: BTreeMap<String, u64>,
+    /// Why reading stopped before the end of the file, e.g. a record cut short by a crash
+    pub read_error: Option<String>,
+    /// `None` if the file was never finished
+    pub footer: Option<CaptureFooter>,
+}
+
+impl CaptureSummary {
+    /// Reads every remaining record, stopping early at one that can't be read
+    pub fn read<R: Read>(mut reader: CaptureReader<R>) -> Self {
+        let mut summary = Self::default();
+        loop {
+            let record = match reader.read_record() {
+                Ok(Some(record)) => record,
+                Ok(None) => {
+                    summary.footer = reader.footer().cloned();
+                    break;
+                }
+                Err(e) => {
+                    summary.read_error = Some(e.to_string());
+                    break;
+                }
+            };
+            summary.add(&record);
+        }
+        summary
+    }
+
+    fn add(&mut self, record: &CaptureRecord) {
+        self.records += 1;
+        self.first_received.get_or_insert(record.received_at);
+        self.last_received = Some(record.received_at);
+
+        let packet = match Packet::from_bytes(&record.data) {
+            Ok(packet) => packet,
+            Err(e) => {
+                *self.decode_errors.entry(e.to_string()).or_default() += 1;
+                return;
+            }
+        };
+        if let Some(descriptor) = PacketDescriptor::for_id(packet.packet_id().into()) {
+            *self.packets.entry(descriptor.short_name()).or_default() += 1;
+        }
+
+        let header = packet.header();
+        let (session_uid, session_time) = (header.session_uid, header.session_time);
+        let feed = FeedId::new(record.src, &packet);
+        match self
+            .sessions
+            .iter_mut()
+            .find(|session| session.feed == feed)
+        {
+            Some(session) => {
+                session.packets += 1;
+                session.first_session_time = session.first_session_time.min(session_time);
+                session.last_session_time = session.last_session_time.max(session_time);
+            }
+            None => self.sessions.push(SessionSummary {
+                feed,
+                session_uid,
+                packet_format: header.packet_format,
+                packets: 1,
+                first_session_time: session_time,
+                last_session_time: session_time,
+            }),
+        }
+    }
+
+    /// Time between the first and last record being received
+    pub fn duration(&self) -> Duration {
+        match (self.first_received, self.last_received) {
+            (Some(first), Some(last)) => last.duration_since(first).unwrap_or_default(),
+            _ => Duration::ZERO,
+        }
+    }
+}
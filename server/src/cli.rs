@@ -0,0 +1,467 @@
+//! The `server` command line: its subcommands and their options, and how those turn into the
+//! same [`Config`] a config file would give.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use rumqttc::QoS;
+use telemetry::{PacketDescriptor, PacketID, MAX_CARS};
+use tracing::level_filters::LevelFilter;
+
+use crate::bind::BindOptions;
+use crate::config::{
+    Config, ConfigError, ListenerConfig, MqttConfig, OutputConfig, RecorderConfig, RelayConfig,
+    DEFAULT_BIND, DEFAULT_MQTT_PORT,
+};
+use crate::logging::{LogFormat, Logger};
+use crate::output::{Format, PacketFilter};
+use crate::replay::{ReplayCommand, SeekTarget, Speed};
+
+const EXIT_STATUS: &str = "\
+Exit status is 0 on success, 1 if something failed while running and 2 for invalid usage.";
+
+const REPLAY_COMMANDS: &str = "\
+While running, enter commands on stdin:
+  p            pause
+  r            resume
+  t <seconds>  seek to session time
+  l <lap>      seek to lap
+  s <speed>    change speed, or `s max`
+  q            quit";
+
+/// Receives F1 23 UDP telemetry, and records, replays, inspects and exports captures of it
+#[derive(Debug, Parser)]
+#[command(version, after_help = EXIT_STATUS)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+    /// Only log messages at this level or above: off, error, warn, info, debug or trace.
+    /// Overrides the config file
+    #[arg(long, global = true, value_name = "LEVEL", value_parser = parse_log_level)]
+    pub log_level: Option<LevelFilter>,
+    /// Log as text, or as json with one object per line for log shippers. Overrides the
+    /// config file
+    #[arg(long, global = true, value_name = "FORMAT")]
+    pub log_format: Option<LogFormat>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Receive packets, print them and serve them to other programs
+    #[command(after_help = EXIT_STATUS)]
+    Listen(Box<ListenArgs>),
+    /// Receive packets and write them to capture files, starting a new file every session
+    #[command(after_help = EXIT_STATUS)]
+    Record(RecordArgs),
+    /// Send the datagrams in capture files to an address with their original timing
+    #[command(after_help = format!("{REPLAY_COMMANDS}\n\n{EXIT_STATUS}"))]
+    Replay(ReplayArgs),
+    /// Summarise capture files: packet counts, sessions, duration and decode errors
+    #[command(after_help = EXIT_STATUS)]
+    Inspect(InspectArgs),
+    /// Write the packets in capture files as text, one packet after another
+    #[command(after_help = EXIT_STATUS)]
+    Export(ExportArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct BindArgs {
+    /// Address to receive telemetry on, IPv4 or IPv6. Can be given more than once
+    #[arg(short, long, value_name = "ADDR", default_value = DEFAULT_BIND)]
+    pub bind: Vec<SocketAddr>,
+    /// Receive telemetry the game broadcasts to the LAN, sharing the port with other programs.
+    /// Needs an unspecified IPv4 address such as 0.0.0.0:20777
+    #[arg(long)]
+    pub broadcast: bool,
+    /// Join these comma separated multicast groups on every address of the same IP version
+    #[arg(long, value_name = "GROUPS", value_delimiter = ',')]
+    pub multicast: Vec<IpAddr>,
+    /// Interface to join IPv4 multicast groups on, by its address
+    #[arg(long, value_name = "ADDR", requires = "multicast")]
+    pub interface: Option<Ipv4Addr>,
+}
+
+impl BindArgs {
+    pub fn options(&self) -> Result<Vec<BindOptions>, String> {
+        if let Some(group) = self.multicast.iter().find(|group| {
+            !self
+                .bind
+                .iter()
+                .any(|addr| addr.is_ipv4() == group.is_ipv4())
+        }) {
+            return Err(format!(
+                "no address of the same IP version to join {group} on"
+            ));
+        }
+
+        let options = self
+            .bind
+            .iter()
+            .map(|addr| {
+                let mut options = BindOptions::new(*addr);
+                options.broadcast = self.broadcast;
+                options.multicast = self
+                    .multicast
+                    .iter()
+                    .filter(|group| group.is_ipv4() == addr.is_ipv4())
+                    .copied()
+                    .collect();
+                options.interface = self.interface;
+                options
+            })
+            .collect::<Vec<_>>();
+        for bind in &options {
+            bind.check()?;
+        }
+        Ok(options)
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct PacketArgs {
+    /// Only these comma separated packet types, e.g. car_telemetry,lap
+    #[arg(short, long, value_name = "TYPES", value_delimiter = ',', value_parser = parse_packet_type)]
+    pub packets: Vec<PacketID>,
+}
+
+impl PacketArgs {
+    /// Raw packet IDs to keep, every type if `None`
+    pub fn packet_ids(&self) -> Option<Vec<u8>> {
+        (!self.packets.is_empty()).then(|| self.packets.iter().cloned().map(u8::from).collect())
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct FilterArgs {
+    #[command(flatten)]
+    pub packets: PacketArgs,
+    /// Only these comma separated car indexes. Per-car arrays only hold these cars, in this
+    /// order, and packets about other cars are skipped
+    #[arg(short, long, value_name = "INDEXES", value_delimiter = ',', value_parser = parse_car)]
+    pub cars: Vec<usize>,
+    /// Only packets sent from these comma separated addresses, to follow one rig when several
+    /// send telemetry
+    #[arg(long, value_name = "ADDRS", value_delimiter = ',')]
+    pub sources: Vec<SocketAddr>,
+}
+
+impl FilterArgs {
+    pub fn filter(&self) -> PacketFilter {
+        PacketFilter::new(
+            self.packets.packets.iter().cloned(),
+            self.cars.iter().copied(),
+        )
+        .sources(self.sources.iter().copied())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// Indented JSON under a line saying what the packet is and which feed it came from
+    #[default]
+    Pretty,
+    /// One JSON object per line
+    Json,
+    /// One short line per packet
+    Compact,
+}
+
+impl From<OutputFormat> for Format {
+    fn from(format: OutputFormat) -> Self {
+        match format {
+            OutputFormat::Pretty => Format::Pretty,
+            OutputFormat::Json => Format::Json,
+            OutputFormat::Compact => Format::Compact,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ListenArgs {
+    /// Take every setting from this TOML file instead, reloading it on SIGHUP
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = [
+            "bind", "broadcast", "multicast", "interface", "packets", "cars", "sources", "format", "quiet", "record", "relay", "ws", "http",
+            "grpc", "mqtt",
+        ]
+    )]
+    pub config: Option<PathBuf>,
+    #[command(flatten)]
+    pub bind: BindArgs,
+    // only applies to printed packets, the other outputs have filters of their own
+    #[command(flatten)]
+    pub filter: FilterArgs,
+    /// How to print packets
+    #[arg(short, long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+    /// Don't print packets
+    #[arg(short, long)]
+    pub quiet: bool,
+    /// Also write every datagram to capture files in this directory
+    #[arg(long, value_name = "DIR")]
+    pub record: Option<PathBuf>,
+    /// Forward datagrams to ADDR, optionally only the comma separated packet TYPES and at most
+    /// HZ packets per type per second. Can be given more than once
+    #[arg(long, value_name = "ADDR[/TYPES][@HZ]", value_parser = parse_relay)]
+    pub relay: Vec<RelayConfig>,
+    /// Serve a WebSocket stream of decoded packets at ws://ADDR/ws
+    #[arg(long, value_name = "ADDR")]
+    pub ws: Option<SocketAddr>,
+    /// Serve the current session state at http://ADDR/api, and Prometheus metrics at
+    /// http://ADDR/metrics
+    #[arg(long, value_name = "ADDR")]
+    pub http: Option<SocketAddr>,
+    /// Serve the gRPC telemetry service described by proto/telemetry.proto
+    #[arg(long, value_name = "ADDR")]
+    pub grpc: Option<SocketAddr>,
+    /// Publish packets to topics under f1/<session_uid>/ on an MQTT broker, port 1883 by
+    /// default, optionally only the TYPES and at most HZ packets per type per second (events are
+    /// never rate limited)
+    #[arg(long, value_name = "HOST[:PORT][/TYPES][@HZ]", value_parser = parse_mqtt)]
+    pub mqtt: Option<MqttConfig>,
+    /// QoS level of MQTT publishes
+    #[arg(long, value_name = "0|1|2", default_value = "0", value_parser = parse_qos, requires = "mqtt")]
+    pub mqtt_qos: QoS,
+    /// Ask the MQTT broker to retain the last message of every topic
+    #[arg(long, requires = "mqtt")]
+    pub mqtt_retain: bool,
+    /// Publish under f1/<src>/<session_uid>/ instead, keeping every sender's topics apart
+    #[arg(long, requires = "mqtt")]
+    pub mqtt_sender_topics: bool,
+}
+
+impl ListenArgs {
+    /// The config these options describe, the same as a config file would
+    pub fn into_config(self) -> Result<Config, ConfigError> {
+        let mqtt = self.mqtt.map(|mqtt| MqttConfig {
+            qos: self.mqtt_qos,
+            retain: self.mqtt_retain,
+            sender_topics: self.mqtt_sender_topics,
+            ..mqtt
+        });
+        let listener = |bind| ListenerConfig { bind };
+        Ok(Config {
+            bind: self.bind.options().map_err(ConfigError::Invalid)?,
+            output: OutputConfig {
+                enabled: !self.quiet,
+                format: self.format.into(),
+                packets: self.filter.packets.packets,
+                cars: self.filter.cars,
+                sources: self.filter.sources,
+            },
+            recorder: self.record.map(|dir| RecorderConfig {
+                dir,
+                packets: Vec::new(),
+            }),
+            relay: self.relay,
+            websocket: self.ws.map(listener),
+            http: self.http.map(listener),
+            grpc: self.grpc.map(listener),
+            mqtt,
+            ..Config::default()
+        })
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct RecordArgs {
+    /// Directory to write capture files to, created if it doesn't exist
+    pub dir: PathBuf,
+    #[command(flatten)]
+    pub bind: BindArgs,
+    #[command(flatten)]
+    pub packets: PacketArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct ReplayArgs {
+    /// Address to send the datagrams to
+    pub target: SocketAddr,
+    /// Capture files, played in order
+    #[arg(required = true)]
+    pub captures: Vec<PathBuf>,
+    #[command(flatten)]
+    pub packets: PacketArgs,
+    /// Playback speed, from 0.5 to 20 times the original
+    #[arg(long, default_value = "1", value_parser = parse_speed, conflicts_with = "max")]
+    pub speed: Speed,
+    /// Send every datagram as fast as possible
+    #[arg(long)]
+    pub max: bool,
+    /// Start again from the first file once the last one finishes
+    #[arg(long = "loop")]
+    pub looping: bool,
+    /// Start at this session time in the first file
+    #[arg(long, value_name = "SECONDS", conflicts_with = "from_lap")]
+    pub from_time: Option<f32>,
+    /// Start once the player's car begins this lap in the first file
+    #[arg(long, value_name = "LAP")]
+    pub from_lap: Option<u8>,
+}
+
+#[derive(Debug, Args)]
+pub struct InspectArgs {
+    #[arg(required = true)]
+    pub captures: Vec<PathBuf>,
+    /// Print a JSON object per file instead
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// Capture files, exported in order
+    #[arg(required = true)]
+    pub captures: Vec<PathBuf>,
+    #[command(flatten)]
+    pub filter: FilterArgs,
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Json)]
+    pub format: OutputFormat,
+    /// File to write to instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+fn parse_packet_type(name: &str) -> Result<PacketID, String> {
+    PacketDescriptor::for_name(name)
+        .map(|descriptor| PacketID::from(descriptor.id))
+        .ok_or(format!("unknown packet type {name}"))
+}
+
+fn parse_log_level(level: &str) -> Result<LevelFilter, String> {
+    level
+        .parse()
+        .map_err(|_| "expected off, error, warn, info, debug or trace".to_string())
+}
+
+fn parse_car(idx: &str) -> Result<usize, String> {
+    idx.parse()
+        .ok()
+        .filter(|idx| *idx < MAX_CARS)
+        .ok_or(format!("expected a car index from 0 to {}", MAX_CARS - 1))
+}
+
+fn parse_speed(value: &str) -> Result<Speed, String> {
+    value
+        .parse()
+        .ok()
+        .and_then(Speed::multiplier)
+        .ok_or("expected a speed from 0.5 to 20".to_string())
+}
+
+/// A `<addr>[/<types>][@<hz>]` option value
+struct Spec<'a> {
+    addr: &'a str,
+    packet_ids: Option<Vec<PacketID>>,
+    hz: Option<f64>,
+}
+
+fn parse_spec(spec: &str) -> Result<Spec<'_>, String> {
+    let (spec, hz) = match spec.rsplit_once('@') {
+        Some((spec, hz)) => (spec, Some(hz)),
+        None => (spec, None),
+    };
+    let (addr, types) = match spec.split_once('/') {
+        Some((addr, types)) => (addr, Some(types)),
+        None => (spec, None),
+    };
+
+    let packet_ids = types
+        .map(|types| types.split(',').map(parse_packet_type).collect())
+        .transpose()?;
+    let hz = hz
+        .map(|hz| hz.parse().map_err(|_| format!("invalid rate {hz}")))
+        .transpose()?;
+    Ok(Spec {
+        addr,
+        packet_ids,
+        hz,
+    })
+}
+
+fn parse_relay(spec: &str) -> Result<RelayConfig, String> {
+    let spec = parse_spec(spec)?;
+    Ok(RelayConfig {
+        addr: spec
+            .addr
+            .parse()
+            .map_err(|_| format!("invalid relay address {}", spec.addr))?,
+        packets: spec.packet_ids.unwrap_or_default(),
+        max_rate: spec.hz,
+    })
+}
+
+/// Parses `<host>[:<port>][/<types>][@<hz>]`
+fn parse_mqtt(spec: &str) -> Result<MqttConfig, String> {
+    let spec = parse_spec(spec)?;
+    let (host, port) = match spec.addr.rsplit_once(':') {
+        Some((host, port)) => {
+            let port = port
+                .parse()
+                .map_err(|_| format!("invalid MQTT port {port}"))?;
+            (host, port)
+        }
+        None => (spec.addr, DEFAULT_MQTT_PORT),
+    };
+    Ok(MqttConfig {
+        host: host.to_string(),
+        port,
+        client_id: None,
+        prefix: "f1".to_string(),
+        sender_topics: false,
+        qos: QoS::AtMostOnce,
+        retain: false,
+        packets: spec.packet_ids.unwrap_or_default(),
+        cars: Vec::new(),
+        max_rate: spec.hz,
+        fields: Vec::new(),
+    })
+}
+
+fn parse_qos(qos: &str) -> Result<QoS, String> {
+    match qos {
+        "0" => Ok(QoS::AtMostOnce),
+        "1" => Ok(QoS::AtLeastOnce),
+        "2" => Ok(QoS::ExactlyOnce),
+        _ => Err("expected 0, 1 or 2".to_string()),
+    }
+}
+
+/// Parses a line of the commands listed in `server replay --help`
+pub fn parse_command(line: &str) -> Option<ReplayCommand> {
+    let mut parts = line.split_whitespace();
+    let command = match (parts.next()?, parts.next()) {
+        ("p", None) => ReplayCommand::Pause,
+        ("r", None) => ReplayCommand::Resume,
+        ("q", None) => ReplayCommand::Stop,
+        ("t", Some(time)) => ReplayCommand::Seek(SeekTarget::SessionTime(time.parse().ok()?)),
+        ("l", Some(lap)) => ReplayCommand::Seek(SeekTarget::Lap(lap.parse().ok()?)),
+        ("s", Some("max")) => ReplayCommand::SetSpeed(Speed::Max),
+        ("s", Some(speed)) => ReplayCommand::SetSpeed(parse_speed(speed).ok()?),
+        _ => return None,
+    };
+    Some(command)
+}
+
+/// The installed logger, and what the command line says about it
+pub struct Logging {
+    pub logger: Arc<Logger>,
+    pub level: Option<LevelFilter>,
+    pub format: Option<LogFormat>,
+}
+
+impl Logging {
+    /// Takes the level and format from `config` unless the command line set them
+    pub fn apply(&self, config: &Config) {
+        if self.level.is_none() {
+            self.logger.set_level(config.log_level);
+        }
+        if self.format.is_none() {
+            self.logger.set_format(config.log_format);
+        }
+    }
+}
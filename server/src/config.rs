@@ -0,0 +1,456 @@
+//! TOML configuration for `server listen --config <file>`.
+//!
+//! Every section is optional. A config using all of them looks like:
+//!
+//! ```toml
+//! # one address or a list of them, where each can also be a table to receive broadcasts or
+//! # join multicast groups, e.g. { addr = "0.0.0.0:20777", multicast = ["239.255.0.1"] }
+//! bind = ["127.0.0.1:20777", "[::1]:20777", { addr = "0.0.0.0:20778", broadcast = true }]
+//! # off, error, warn, info, debug or trace
+//! log_level = "info"
+//! # text, or json for log shippers
+//! log_format = "text"
+//!
+//! # printing packets to stdout
+//! [output]
+//! enabled = true
+//! format = "compact"
+//! packets = ["lap", "event"]
+//! cars = [0, 1]
+//! sources = ["192.168.1.30:52344"]
+//!
+//! [recorder]
+//! dir = "captures"
+//! packets = ["lap", "session"]
+//!
+//! [[relay]]
+//! addr = "192.168.1.20:20777"
+//! packets = ["car_telemetry"]
+//! max_rate = 10
+//!
+//! [websocket]
+//! bind = "0.0.0.0:8080"
+//!
+//! [http]
+//! bind = "0.0.0.0:8081"
+//!
+//! [grpc]
+//! bind = "0.0.0.0:50051"
+//!
+//! [mqtt]
+//! host = "localhost"
+//! port = 1883
+//! prefix = "f1"
+//! sender_topics = true
+//! qos = 1
+//! retain = true
+//! packets = ["car_status", "event"]
+//! cars = [0]
+//! max_rate = 5
+//! fields = { car_status = ["vehicle_fia_flags"] }
+//! ```
+//!
+//! Packet types use the short names of the packet registry, e.g. `car_telemetry`. Empty packet,
+//! car and source lists mean every type, every car and every sender.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Display;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use rumqttc::QoS;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+use telemetry::{PacketDescriptor, PacketID, MAX_CARS};
+use tracing::level_filters::LevelFilter;
+
+use crate::bind::BindOptions;
+use crate::logging::LogFormat;
+use crate::output::{Format, PacketFilter};
+
+pub const DEFAULT_BIND: &str = "127.0.0.1:20777";
+pub const DEFAULT_MQTT_PORT: u16 = 1883;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Addresses to receive telemetry on
+    #[serde(default = "default_bind", deserialize_with = "one_or_many")]
+    pub bind: Vec<BindOptions>,
+    #[serde(default = "default_log_level", deserialize_with = "log_level")]
+    pub log_level: LevelFilter,
+    #[serde(default)]
+    pub log_format: LogFormat,
+    #[serde(default)]
+    pub output: OutputConfig,
+    pub recorder: Option<RecorderConfig>,
+    #[serde(default)]
+    pub relay: Vec<RelayConfig>,
+    pub websocket: Option<ListenerConfig>,
+    pub http: Option<ListenerConfig>,
+    pub grpc: Option<ListenerConfig>,
+    pub mqtt: Option<MqttConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputConfig {
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub format: Format,
+    #[serde(default, deserialize_with = "packet_types")]
+    pub packets: Vec<PacketID>,
+    #[serde(default, deserialize_with = "car_indexes")]
+    pub cars: Vec<usize>,
+    /// Senders to print packets from, every sender if empty
+    #[serde(default)]
+    pub sources: Vec<SocketAddr>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RecorderConfig {
+    /// Created if it doesn't exist
+    pub dir: PathBuf,
+    #[serde(default, deserialize_with = "packet_types")]
+    pub packets: Vec<PacketID>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RelayConfig {
+    pub addr: SocketAddr,
+    #[serde(default, deserialize_with = "packet_types")]
+    pub packets: Vec<PacketID>,
+    /// Packets per second of each type
+    pub max_rate: Option<f64>,
+}
+
+/// A TCP service such as the WebSocket endpoint
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ListenerConfig {
+    pub bind: SocketAddr,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MqttConfig {
+    pub host: String,
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+    /// Defaults to one based on the process ID
+    pub client_id: Option<String>,
+    #[serde(default = "default_prefix")]
+    pub prefix: String,
+    /// Whether topics have a level for the sender's address, under the prefix
+    #[serde(default)]
+    pub sender_topics: bool,
+    #[serde(default = "default_qos", deserialize_with = "qos")]
+    pub qos: QoS,
+    #[serde(default)]
+    pub retain: bool,
+    #[serde(default, deserialize_with = "packet_types")]
+    pub packets: Vec<PacketID>,
+    #[serde(default, deserialize_with = "car_indexes")]
+    pub cars: Vec<usize>,
+    /// Packets per second of each type, events are never limited
+    pub max_rate: Option<f64>,
+    /// Fields to publish per packet type
+    #[serde(default, deserialize_with = "packet_fields")]
+    pub fields: Vec<(PacketID, Vec<String>)>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: default_bind(),
+            log_level: default_log_level(),
+            log_format: LogFormat::default(),
+            output: OutputConfig::default(),
+            recorder: None,
+            relay: Vec::new(),
+            websocket: None,
+            http: None,
+            grpc: None,
+            mqtt: None,
+        }
+    }
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            format: Format::default(),
+            packets: Vec::new(),
+            cars: Vec::new(),
+            sources: Vec::new(),
+        }
+    }
+}
+
+impl OutputConfig {
+    pub fn filter(&self) -> PacketFilter {
+        PacketFilter::new(self.packets.iter().cloned(), self.cars.iter().copied())
+            .sources(self.sources.iter().copied())
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    /// Not valid TOML, or doesn't match the expected layout
+    Parse(toml::de::Error),
+    /// Parsed, but the values don't make sense together
+    Invalid(String),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "couldn't read config: {e}"),
+            ConfigError::Parse(e) => write!(f, "{e}"),
+            ConfigError::Invalid(e) => write!(f, "invalid config: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl FromStr for Config {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let config: Config = toml::from_str(s).map_err(ConfigError::Parse)?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl Config {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        std::fs::read_to_string(path)
+            .map_err(ConfigError::Io)?
+            .parse()
+    }
+
+    /// Sections that differ from `other` and can't change without restarting
+    pub fn needs_restart(&self, other: &Config) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self.bind != other.bind {
+            changed.push("bind");
+        }
+        if self.websocket != other.websocket {
+            changed.push("websocket");
+        }
+        if self.http != other.http {
+            changed.push("http");
+        }
+        if self.grpc != other.grpc {
+            changed.push("grpc");
+        }
+        changed
+    }
+
+    /// Checks the values make sense together, which parsing a file already does
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.check().map_err(ConfigError::Invalid)
+    }
+
+    fn check(&self) -> Result<(), String> {
+        if self.bind.is_empty() {
+            return Err("bind needs at least one address".to_string());
+        }
+        for bind in &self.bind {
+            bind.check()?;
+        }
+        let bind: Vec<SocketAddr> = self.bind.iter().map(|bind| bind.addr).collect();
+        if let Some(addr) = duplicate(&bind) {
+            return Err(format!("{addr} is bound more than once"));
+        }
+
+        for relay in &self.relay {
+            if bind.contains(&relay.addr) {
+                return Err(format!(
+                    "relay to {} would send packets back to this server",
+                    relay.addr
+                ));
+            }
+            check_rate(relay.max_rate, "relay max_rate")?;
+        }
+
+        let listeners: Vec<SocketAddr> = [&self.websocket, &self.http, &self.grpc]
+            .into_iter()
+            .flatten()
+            .map(|listener| listener.bind)
+            .collect();
+        if let Some(addr) = duplicate(&listeners) {
+            return Err(format!(
+                "{addr} is used by more than one of websocket, http and grpc"
+            ));
+        }
+
+        if let Some(mqtt) = &self.mqtt {
+            if mqtt.host.is_empty() {
+                return Err("mqtt host is empty".to_string());
+            }
+            if mqtt.prefix.is_empty() || mqtt.prefix.contains(['+', '#']) {
+                return Err(format!(
+                    "mqtt prefix {:?} must be non-empty and free of the wildcards + and #",
+                    mqtt.prefix
+                ));
+            }
+            check_rate(mqtt.max_rate, "mqtt max_rate")?;
+            if let Some((packet_id, _)) = mqtt.fields.iter().find(|(_, fields)| fields.is_empty()) {
+                return Err(format!("mqtt fields for {packet_id:?} are empty"));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn duplicate(addrs: &[SocketAddr]) -> Option<SocketAddr> {
+    let mut seen = HashSet::new();
+    addrs.iter().find(|addr| !seen.insert(**addr)).copied()
+}
+
+fn check_rate(rate: Option<f64>, name: &str) -> Result<(), String> {
+    match rate {
+        Some(rate) if !(rate.is_finite() && rate > 0.0) => {
+            Err(format!("{name} must be a positive number, got {rate}"))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn default_bind() -> Vec<BindOptions> {
+    let addr: SocketAddr = DEFAULT_BIND.parse().expect("default bind address is valid");
+    vec![addr.into()]
+}
+
+fn default_log_level() -> LevelFilter {
+    LevelFilter::INFO
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn default_mqtt_port() -> u16 {
+    DEFAULT_MQTT_PORT
+}
+
+fn default_prefix() -> String {
+    "f1".to_string()
+}
+
+fn default_qos() -> QoS {
+    QoS::AtMostOnce
+}
+
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<BindOptions>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Options {
+        addr: SocketAddr,
+        #[serde(default)]
+        broadcast: bool,
+        #[serde(default)]
+        multicast: Vec<IpAddr>,
+        interface: Option<Ipv4Addr>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Bind {
+        Addr(SocketAddr),
+        Options(Options),
+    }
+
+    impl From<Bind> for BindOptions {
+        fn from(bind: Bind) -> Self {
+            match bind {
+                Bind::Addr(addr) => addr.into(),
+                Bind::Options(options) => BindOptions {
+                    addr: options.addr,
+                    broadcast: options.broadcast,
+                    multicast: options.multicast,
+                    interface: options.interface,
+                },
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(Bind),
+        Many(Vec<Bind>),
+    }
+
+    match OneOrMany::deserialize(deserializer) {
+        Ok(OneOrMany::One(bind)) => Ok(vec![bind.into()]),
+        Ok(OneOrMany::Many(binds)) => Ok(binds.into_iter().map(Into::into).collect()),
+        Err(_) => Err(D::Error::custom(
+            "expected an address such as \"127.0.0.1:20777\", a table such as \
+             { addr = \"0.0.0.0:20777\", broadcast = true }, or a list of them",
+        )),
+    }
+}
+
+fn log_level<'de, D: Deserializer<'de>>(deserializer: D) -> Result<LevelFilter, D::Error> {
+    let level = String::deserialize(deserializer)?;
+    level.parse().map_err(|_| {
+        D::Error::custom(format!(
+            "unknown log level {level}, expected off, error, warn, info, debug or trace"
+        ))
+    })
+}
+
+fn packet_type<E: serde::de::Error>(name: &str) -> Result<PacketID, E> {
+    PacketDescriptor::for_name(name)
+        .map(|descriptor| PacketID::from(descriptor.id))
+        .ok_or_else(|| E::custom(format!("unknown packet type {name}")))
+}
+
+fn packet_types<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<PacketID>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|name| packet_type(name))
+        .collect()
+}
+
+fn car_indexes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<usize>, D::Error> {
+    let cars = Vec::<usize>::deserialize(deserializer)?;
+    match cars.iter().find(|car| **car >= MAX_CARS) {
+        Some(car) => Err(D::Error::custom(format!(
+            "car index {car} is out of bounds, expected 0 to {}",
+            MAX_CARS - 1
+        ))),
+        None => Ok(cars),
+    }
+}
+
+fn qos<'de, D: Deserializer<'de>>(deserializer: D) -> Result<QoS, D::Error> {
+    match u8::deserialize(deserializer)? {
+        0 => Ok(QoS::AtMostOnce),
+        1 => Ok(QoS::AtLeastOnce),
+        2 => Ok(QoS::ExactlyOnce),
+        qos => Err(D::Error::custom(format!(
+            "unknown QoS {qos}, expected 0, 1 or 2"
+        ))),
+    }
+}
+
+fn packet_fields<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<(PacketID, Vec<String>)>, D::Error> {
+    // a BTreeMap keeps the order stable, so reloading an unchanged file compares equal
+    BTreeMap::<String, Vec<String>>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(name, fields)| Ok((packet_type(&name)?, fields)))
+        .collect()
+}
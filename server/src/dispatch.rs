@@ -0,0 +1,193 @@
+//! Hands packets to the sinks, each through its own queue and task.
+
+use std::net::SocketAddr;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use futures::FutureExt;
+use telemetry::Packet;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn, Instrument, Span};
+
+use crate::flashback::Rewind;
+use crate::metrics::Metrics;
+use crate::sink::Sink;
+
+/// Packets waiting for a sink before further ones are dropped for it
+pub const SINK_QUEUE_CAPACITY: usize = 1024;
+
+/// How long shutting down waits for the sinks to get through their queues and close, unless the
+/// server is given another with [`Server::sink_close_timeout`](crate::Server::sink_close_timeout)
+pub const SINK_CLOSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+enum Job {
+    Packet {
+        src: SocketAddr,
+        received_at: SystemTime,
+        raw: Arc<[u8]>,
+        packet: Arc<Packet>,
+        /// The packet's span, so what the sink logs says which packet it was about
+        span: Span,
+    },
+    Rewind(Rewind),
+}
+
+struct Queue {
+    name: String,
+    jobs: mpsc::Sender<Job>,
+    worker: JoinHandle<()>,
+    /// Whether the last packet was dropped, so a sink falling behind is reported once rather
+    /// than for every packet
+    overflowing: AtomicBool,
+}
+
+/// Runs every sink on its own task, so a slow sink only falls behind itself and a failing one
+/// only loses its own packets
+pub(crate) struct Dispatcher {
+    queues: Vec<Queue>,
+    metrics: Arc<Metrics>,
+}
+
+impl Dispatcher {
+    /// Starts a task for each of `sinks`. Must be called from within a Tokio runtime
+    pub(crate) fn start(sinks: &[Arc<dyn Sink>], metrics: Arc<Metrics>) -> Self {
+        let queues = sinks
+            .iter()
+            .map(|sink| {
+                let (jobs, queued) = mpsc::channel(SINK_QUEUE_CAPACITY);
+                Queue {
+                    name: sink.name().to_string(),
+                    jobs,
+                    worker: tokio::spawn(work(sink.clone(), queued, metrics.clone())),
+                    overflowing: AtomicBool::new(false),
+                }
+            })
+            .collect();
+        Self { queues, metrics }
+    }
+
+    /// Queues a packet for every sink, without waiting for any of them
+    pub(crate) fn packet(
+        &self,
+        src: SocketAddr,
+        received_at: SystemTime,
+        raw: &[u8],
+        packet: Arc<Packet>,
+    ) {
+        let raw: Arc<[u8]> = raw.into();
+        for queue in &self.queues {
+            let job = Job::Packet {
+                src,
+                received_at,
+                raw: raw.clone(),
+                packet: packet.clone(),
+                span: Span::current(),
+            };
+            self.queue(queue, job);
+        }
+    }
+
+    /// Queues a flashback for every sink, ahead of the packet that revealed it
+    pub(crate) fn rewind(&self, rewind: &Rewind) {
+        for queue in &self.queues {
+            self.queue(queue, Job::Rewind(*rewind));
+        }
+    }
+
+    fn queue(&self, queue: &Queue, job: Job) {
+        match queue.jobs.try_send(job) {
+            Ok(()) => {
+                if queue.overflowing.swap(false, Ordering::Relaxed) {
+                    info!(sink = queue.name, "sink caught up");
+                }
+            }
+            Err(TrySendError::Full(_)) => {
+                self.metrics.sink_dropped(&queue.name);
+                if !queue.overflowing.swap(true, Ordering::Relaxed) {
+                    warn!(
+                        sink = queue.name,
+                        capacity = SINK_QUEUE_CAPACITY,
+                        "sink fell behind, dropping packets for it"
+                    );
+                }
+            }
+            // the task is gone, which it has already been reported for
+            Err(TrySendError::Closed(_)) => self.metrics.sink_dropped(&queue.name),
+        }
+    }
+
+    /// Waits for every sink to get through what's queued for it, then closes them. Sinks still
+    /// going after `timeout` are abandoned and logged
+    pub(crate) async fn close(self, timeout: Duration) {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let workers: Vec<_> = self
+            .queues
+            .into_iter()
+            .map(|queue| (queue.name, queue.worker))
+            .collect();
+        let mut unfinished = Vec::new();
+        for (name, mut worker) in workers {
+            match tokio::time::timeout_at(deadline, &mut worker).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => error!(sink = name, error = %e, "sink task failed"),
+                Err(_) => {
+                    worker.abort();
+                    unfinished.push(name);
+                }
+            }
+        }
+        if !unfinished.is_empty() {
+            warn!(
+                sinks = ?unfinished,
+                timeout = ?timeout,
+                "gave up waiting for sinks to close"
+            );
+        }
+    }
+}
+
+/// Handles everything queued for `sink`, then closes it once the queue is closed
+async fn work(sink: Arc<dyn Sink>, mut jobs: mpsc::Receiver<Job>, metrics: Arc<Metrics>) {
+    while let Some(job) = jobs.recv().await {
+        match job {
+            Job::Packet {
+                src,
+                received_at,
+                raw,
+                packet,
+                span,
+            } => {
+                async {
+                    let started = Instant::now();
+                    let result = AssertUnwindSafe(sink.handle(src, received_at, &raw, &packet))
+                        .catch_unwind()
+                        .await;
+                    metrics.sink_handled(sink.name(), started.elapsed());
+                    match result {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) => error!(sink = sink.name(), error = %e, "sink failed"),
+                        Err(_) => error!(sink = sink.name(), "sink panicked"),
+                    }
+                }
+                .instrument(span)
+                .await
+            }
+            Job::Rewind(rewind) => {
+                match AssertUnwindSafe(sink.rewind(&rewind)).catch_unwind().await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => error!(sink = sink.name(), error = %e, "sink failed to rewind"),
+                    Err(_) => error!(sink = sink.name(), "sink panicked"),
+                }
+            }
+        }
+    }
+
+    match AssertUnwindSafe(sink.close()).catch_unwind().await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => error!(sink = sink.name(), error = %e, "sink failed to close"),
+        Err(_) => error!(sink = sink.name(), "sink panicked"),
+    }
+}
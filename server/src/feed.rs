@@ -0,0 +1,107 @@
+//! Telemetry from one game: everything a single sender sends during a single session.
+//!
+//! Several rigs can send to the same server, so packets are told apart by the address they
+//! came from as well as their session UID. A feed is written as `<src>/<session_uid>`, e.g.
+//! `192.168.1.20:52344/1234605616436508552`.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use telemetry::{Attributes, Packet, PacketDescriptor};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FeedId {
+    pub src: SocketAddr,
+    pub session_uid: u64,
+}
+
+impl FeedId {
+    pub fn new(src: SocketAddr, packet: &Packet) -> Self {
+        Self {
+            src,
+            session_uid: packet.header().session_uid,
+        }
+    }
+}
+
+impl Display for FeedId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}/{}", self.src, self.session_uid)
+    }
+}
+
+impl FromStr for FeedId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected a feed as <address>/<session uid>, got {s}");
+        let (src, session_uid) = s.rsplit_once('/').ok_or_else(invalid)?;
+        Ok(Self {
+            src: src.parse().map_err(|_| invalid())?,
+            session_uid: session_uid.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl Serialize for FeedId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for FeedId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Counts of what a feed has sent so far
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FeedStats {
+    pub feed: FeedId,
+    pub packets: u64,
+    /// Size of the datagrams
+    pub bytes: u64,
+    /// Unix time in milliseconds
+    pub first_received: u64,
+    pub last_received: u64,
+    /// Car of the driver sending the feed
+    pub player_car_index: u8,
+    /// Empty until a participants packet has arrived
+    pub player_name: String,
+    /// Packets by short name, e.g. `car_telemetry`
+    pub packets_by_type: BTreeMap<String, u64>,
+}
+
+impl FeedStats {
+    pub(crate) fn new(feed: FeedId, received_at: u64) -> Self {
+        Self {
+            feed,
+            packets: 0,
+            bytes: 0,
+            first_received: received_at,
+            last_received: received_at,
+            player_car_index: 0,
+            player_name: String::new(),
+            packets_by_type: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn count(&mut self, len: usize, received_at: u64, packet: &Packet) {
+        self.packets += 1;
+        self.bytes += len as u64;
+        self.last_received = received_at;
+        self.player_car_index = packet.header().player_car_index;
+        if let Some(descriptor) = PacketDescriptor::for_id(packet.packet_id().into()) {
+            *self
+                .packets_by_type
+                .entry(descriptor.short_name())
+                .or_default() += 1;
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Flashbacks: the game going back in time part way through a session.
+//!
+//! After a flashback `frame_identifier` and `session_time` carry on from an earlier point while
+//! `overall_frame_identifier` keeps counting up, and a `FLBK` event says where the game went
+//! back to. Whichever of the two arrives first is reported as a [`Rewind`], so anything built up
+//! from the packets since then can be thrown away before the game sends them again.
+//!
+//! Sinks get it through [`Sink::rewind`](crate::Sink::rewind) and subscribers on the packet that
+//! revealed it. Of what this crate keeps, only [`SessionState`](crate::SessionState)'s event log
+//! rolls back, dropping the events after the point the game went back to. The rest is left
+//! alone on purpose:
+//!
+//! - the latest packets `SessionState` keeps are replaced by the game's next ones anyway
+//! - [`SessionManager`](crate::SessionManager) times sessions by the clock, which a flashback
+//!   doesn't turn back
+//! - [`HealthMonitor`](crate::HealthMonitor) and the metrics count datagrams as they arrive, by
+//!   `overall_frame_identifier` where it matters
+//! - recordings and the relay pass the raw stream on, flashbacks included
+//!
+//! Nothing here adds up laps, stints or fuel across packets. A sink that does should drop what it
+//! counted after [`Rewind::session_time`] when it's told to rewind.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use telemetry::{Attributes, EventDataDetails, Packet};
+
+use crate::feed::FeedId;
+
+/// Overall frames after a rewind during which the `FLBK` event for it is expected
+const FLASHBACK_EVENT_FRAMES: u32 = 120;
+
+/// A feed going back to an earlier point of its session
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Rewind {
+    pub feed: FeedId,
+    /// The frame and session time the game went back to
+    pub frame_identifier: u32,
+    pub session_time: f32,
+    /// The latest frame and session time before the flashback
+    pub from_frame_identifier: u32,
+    pub from_session_time: f32,
+    /// Overall frame identifier of the packet that revealed the flashback
+    pub overall_frame_identifier: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Position {
+    overall_frame_identifier: u32,
+    frame_identifier: u32,
+    session_time: f32,
+    /// Overall frame identifier of the last rewind
+    rewound_at: Option<u32>,
+}
+
+/// Spots flashbacks in each feed. Like [`SessionState`](crate::SessionState), each sender only
+/// has one feed at a time
+#[derive(Debug, Default)]
+pub struct FlashbackDetector {
+    feeds: HashMap<FeedId, Position>,
+}
+
+impl FlashbackDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the rewind `packet` reveals, if it's the first sign of a flashback
+    pub fn observe(&mut self, feed: FeedId, packet: &Packet) -> Option<Rewind> {
+        let header = packet.header();
+        let (overall, frame, session_time) = (
+            header.overall_frame_identifier,
+            header.frame_identifier,
+            header.session_time,
+        );
+        if !self.feeds.contains_key(&feed) {
+            self.feeds.retain(|id, _| id.src != feed.src);
+        }
+        let position = self.feeds.entry(feed).or_insert(Position {
+            overall_frame_identifier: overall,
+            frame_identifier: frame,
+            session_time,
+            rewound_at: None,
+        });
+        // a datagram overtaken by later ones says nothing about where the game is now
+        if overall < position.overall_frame_identifier {
+            return None;
+        }
+
+        let mut rewind = None;
+        if let Packet::Event(event) = packet {
+            if let EventDataDetails::Flashback(flashback) = event.event_details {
+                let already_rewound = position
+                    .rewound_at
+                    .is_some_and(|at| overall.saturating_sub(at) <= FLASHBACK_EVENT_FRAMES);
+                if !already_rewound {
+                    rewind = Some((
+                        flashback.flashback_frame_identifier,
+                        flashback.flashback_session_time,
+                    ));
+                }
+            }
+        }
+        if rewind.is_none() && frame < position.frame_identifier {
+            rewind = Some((frame, session_time));
+        }
+
+        let rewind = rewind.map(|(frame_identifier, session_time)| Rewind {
+            feed,
+            frame_identifier,
+            session_time,
+            from_frame_identifier: position.frame_identifier,
+            from_session_time: position.session_time,
+            overall_frame_identifier: overall,
+        });
+        position.overall_frame_identifier = overall;
+        position.frame_identifier = frame;
+        position.session_time = session_time;
+        if let Some(rewind) = &rewind {
+            position.rewound_at = Some(overall);
+            // the event can come before the game has actually gone back
+            if rewind.frame_identifier < frame {
+                position.frame_identifier = rewind.frame_identifier;
+                position.session_time = rewind.session_time;
+            }
+        }
+        rewind
+    }
+}
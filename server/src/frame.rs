@@ -0,0 +1,283 @@
+//! Per-frame packets put back together.
+//!
+//! Motion, lap, car telemetry, car status and extended motion packets are all sent on every
+//! frame at the rate set in the game's menus, but as separate datagrams. A [`FrameAssembler`]
+//! collects them by `overall_frame_identifier`, which unlike `frame_identifier` is never reused
+//! after a flashback, into a [`Frame`] holding a single instant of the session.
+//!
+//! A frame is complete once it has every type the feed has sent in about the last second, since
+//! motion packets stop while the player isn't in control. Frames still missing packets after
+//! the timeout are handed out anyway, marked incomplete, as are the unfinished frames before a
+//! complete one, so frames of a feed always come out in order.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use telemetry::{
+    Attributes, Packet, PacketCarStatusData, PacketCarTelemetryData, PacketID, PacketLapData,
+    PacketMotionData, PacketMotionExData,
+};
+
+use crate::feed::FeedId;
+use crate::subscription::{RecvError, Subscription};
+
+/// How many frames back a type was last sent for it to be expected in a frame, about a second
+/// at 60 frames per second
+const EXPECTED_WITHIN: u32 = 60;
+
+/// Packet types the game sends together on every sent frame
+pub(crate) const PER_FRAME: [PacketID; 5] = [
+    PacketID::Motion,
+    PacketID::Lap,
+    PacketID::CarTelemetry,
+    PacketID::CarStatus,
+    PacketID::MotionEx,
+];
+
+pub(crate) fn per_frame(packet_id: u8) -> bool {
+    PER_FRAME.into_iter().any(|id| u8::from(id) == packet_id)
+}
+
+/// The per-frame packets of one frame of a feed
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub feed: FeedId,
+    pub frame_identifier: u32,
+    pub overall_frame_identifier: u32,
+    pub session_time: f32,
+    pub motion: Option<PacketMotionData>,
+    pub lap: Option<PacketLapData>,
+    pub car_telemetry: Option<PacketCarTelemetryData>,
+    pub car_status: Option<PacketCarStatusData>,
+    pub motion_ex: Option<PacketMotionExData>,
+    /// Every type the feed has been sending arrived. Never set for a feed's first frames, when
+    /// what it sends isn't known yet
+    pub complete: bool,
+}
+
+impl Frame {
+    fn new(feed: FeedId, packet: &Packet) -> Self {
+        let header = packet.header();
+        Self {
+            feed,
+            frame_identifier: header.frame_identifier,
+            overall_frame_identifier: header.overall_frame_identifier,
+            session_time: header.session_time,
+            motion: None,
+            lap: None,
+            car_telemetry: None,
+            car_status: None,
+            motion_ex: None,
+            complete: false,
+        }
+    }
+
+    /// Keeps `packet`, replacing any earlier one of the same type
+    fn set(&mut self, packet: &Packet) {
+        match packet {
+            Packet::Motion(motion) => self.motion = Some(*motion),
+            Packet::Lap(lap) => self.lap = Some(*lap),
+            Packet::CarTelemetry(telemetry) => self.car_telemetry = Some(*telemetry),
+            Packet::CarStatus(status) => self.car_status = Some(*status),
+            Packet::MotionEx(motion_ex) => self.motion_ex = Some(*motion_ex),
+            _ => {}
+        }
+    }
+
+    /// Whether the frame has a packet of type `id`
+    pub fn has(&self, id: PacketID) -> bool {
+        match id {
+            PacketID::Motion => self.motion.is_some(),
+            PacketID::Lap => self.lap.is_some(),
+            PacketID::CarTelemetry => self.car_telemetry.is_some(),
+            PacketID::CarStatus => self.car_status.is_some(),
+            PacketID::MotionEx => self.motion_ex.is_some(),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Pending {
+    frame: Frame,
+    /// When the frame's first packet arrived
+    started: Instant,
+}
+
+#[derive(Debug, Default)]
+struct FeedFrames {
+    /// By overall frame identifier
+    pending: BTreeMap<u32, Pending>,
+    /// The last overall frame handed out with each type
+    last_sent: HashMap<u8, u32>,
+    /// The last frame handed out, anything for it or earlier frames is too late
+    emitted: Option<u32>,
+}
+
+impl FeedFrames {
+    fn is_complete(&self, frame: &Frame) -> bool {
+        // only frames already handed out count, so a frame can't expect itself
+        let overall = frame.overall_frame_identifier;
+        let expected: Vec<PacketID> = PER_FRAME
+            .into_iter()
+            .filter(|id| {
+                self.last_sent
+                    .get(&u8::from(id.clone()))
+                    .is_some_and(|last| overall.saturating_sub(*last) <= EXPECTED_WITHIN)
+            })
+            .collect();
+        !expected.is_empty() && expected.into_iter().all(|id| frame.has(id))
+    }
+
+    /// Hands out every pending frame up to and including `overall`
+    fn emit_through(&mut self, overall: u32) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        while let Some(entry) = self.pending.first_entry() {
+            if *entry.key() > overall {
+                break;
+            }
+            let mut frame = entry.remove().frame;
+            frame.complete = self.is_complete(&frame);
+            let sent = frame.overall_frame_identifier;
+            for id in PER_FRAME.into_iter().filter(|id| frame.has(id.clone())) {
+                self.last_sent.insert(id.into(), sent);
+            }
+            self.emitted = Some(sent);
+            frames.push(frame);
+        }
+        frames
+    }
+}
+
+/// Groups per-frame packets into [`Frame`]s, see the [module docs](self)
+#[derive(Debug)]
+pub struct FrameAssembler {
+    timeout: Duration,
+    feeds: HashMap<FeedId, FeedFrames>,
+}
+
+impl FrameAssembler {
+    /// Gives up waiting on a frame's missing packets `timeout` after its first one arrived
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            feeds: HashMap::new(),
+        }
+    }
+
+    /// Adds a packet received at `now`, returning the frames it finished. Packets that aren't
+    /// sent per frame, or that arrive after their frame was handed out, are ignored
+    pub fn push(&mut self, feed: FeedId, packet: &Packet, now: Instant) -> Vec<Frame> {
+        let packet_id = u8::from(packet.packet_id());
+        if !per_frame(packet_id) {
+            return Vec::new();
+        }
+        if !self.feeds.contains_key(&feed) {
+            // a sender starting a new session is done with the previous one
+            self.feeds.retain(|id, _| id.src != feed.src);
+        }
+        let frames = self.feeds.entry(feed).or_default();
+        let overall = packet.header().overall_frame_identifier;
+        if frames.emitted.is_some_and(|emitted| overall <= emitted) {
+            return Vec::new();
+        }
+
+        let pending = frames.pending.entry(overall).or_insert_with(|| Pending {
+            frame: Frame::new(feed, packet),
+            started: now,
+        });
+        pending.frame.set(packet);
+
+        if frames.is_complete(&frames.pending[&overall].frame) {
+            frames.emit_through(overall)
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Hands out the frames that have waited longer than the timeout
+    pub fn expire(&mut self, now: Instant) -> Vec<Frame> {
+        let mut expired = Vec::new();
+        for frames in self.feeds.values_mut() {
+            let last_expired = frames
+                .pending
+                .iter()
+                .filter(|(_, pending)| now.duration_since(pending.started) >= self.timeout)
+                .map(|(overall, _)| *overall)
+                .next_back();
+            if let Some(overall) = last_expired {
+                expired.extend(frames.emit_through(overall));
+            }
+        }
+        expired
+    }
+
+    /// Hands out every frame still waiting, e.g. once there are no more packets
+    pub fn flush(&mut self) -> Vec<Frame> {
+        self.feeds
+            .values_mut()
+            .flat_map(|frames| frames.emit_through(u32::MAX))
+            .collect()
+    }
+
+    /// When the next frame times out, if any are waiting
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.feeds
+            .values()
+            .filter_map(|frames| frames.pending.values().map(|p| p.started).min())
+            .min()
+            .map(|started| started + self.timeout)
+    }
+}
+
+/// Live stream of assembled frames, see [`Server::subscribe_frames`](crate::Server::subscribe_frames)
+pub struct FrameSubscription {
+    inner: Subscription,
+    assembler: FrameAssembler,
+    ready: VecDeque<Frame>,
+}
+
+impl FrameSubscription {
+    pub(crate) fn new(inner: Subscription, timeout: Duration) -> Self {
+        Self {
+            inner,
+            assembler: FrameAssembler::new(timeout),
+            ready: VecDeque::new(),
+        }
+    }
+
+    /// Waits for the next frame, complete or timed out. Frames still waiting when the server
+    /// shuts down are handed out before [`RecvError::Closed`]
+    pub async fn recv(&mut self) -> Result<Frame, RecvError> {
+        loop {
+            if let Some(frame) = self.ready.pop_front() {
+                return Ok(frame);
+            }
+            let deadline = self.assembler.next_deadline();
+            tokio::select! {
+                received = self.inner.recv() => match received {
+                    Ok(received) => {
+                        let frames = self.assembler.push(received.feed, &received.packet, Instant::now());
+                        self.ready.extend(frames);
+                    }
+                    Err(RecvError::Closed) => {
+                        self.ready.extend(self.assembler.flush());
+                        if self.ready.is_empty() {
+                            return Err(RecvError::Closed);
+                        }
+                    }
+                    Err(e) => return Err(e),
+                },
+                _ = sleep_until(deadline), if deadline.is_some() => {
+                    self.ready.extend(self.assembler.expire(Instant::now()));
+                }
+            }
+        }
+    }
+}
+
+async fn sleep_until(deadline: Option<Instant>) {
+    if let Some(deadline) = deadline {
+        tokio::time::sleep_until(deadline.into()).await;
+    }
+}
@@ -0,0 +1,136 @@
+//! gRPC service streaming decoded packets, described by `proto/telemetry.proto`
+
+mod convert;
+
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Instant;
+
+use futures::Stream;
+use telemetry::Attributes;
+use tonic::{Request, Response, Status};
+
+use crate::feed::FeedId;
+use crate::rate_limit::RateLimit;
+use crate::state::SessionState;
+use crate::subscription::{RecvError, Subscriber};
+
+pub use convert::ConversionError;
+
+/// Types generated from `proto/telemetry.proto`
+pub mod proto {
+    tonic::include_proto!("f1.telemetry.v1");
+}
+
+pub struct TelemetryService {
+    subscriber: Subscriber,
+    state: Arc<SessionState>,
+}
+
+impl TelemetryService {
+    /// Each `Subscribe` call subscribes through `subscriber`, so a slow client only ever loses
+    /// its own packets
+    pub fn new(subscriber: Subscriber, state: Arc<SessionState>) -> Self {
+        Self { subscriber, state }
+    }
+
+    pub fn into_server(self) -> proto::telemetry_server::TelemetryServer<Self> {
+        proto::telemetry_server::TelemetryServer::new(self)
+    }
+}
+
+type PacketStream = Pin<Box<dyn Stream<Item = Result<proto::ReceivedPacket, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl proto::telemetry_server::Telemetry for TelemetryService {
+    type SubscribeStream = PacketStream;
+
+    async fn subscribe(
+        &self,
+        request: Request<proto::SubscribeRequest>,
+    ) -> Result<Response<PacketStream>, Status> {
+        let request = request.into_inner();
+        if let Some(unknown) = request
+            .packet_types
+            .iter()
+            .find(|packet_type| proto::PacketType::try_from(**packet_type).is_err())
+        {
+            return Err(Status::invalid_argument(format!(
+                "unknown packet type {unknown}"
+            )));
+        }
+        // PacketType values are the packet IDs, so all of these fit a u8
+        let packet_ids: Vec<u8> = request
+            .packet_types
+            .iter()
+            .map(|packet_type| *packet_type as u8)
+            .collect();
+        let sources: Vec<SocketAddr> = request
+            .sources
+            .iter()
+            .map(|src| src.parse())
+            .collect::<Result<_, _>>()
+            .map_err(|e| Status::invalid_argument(format!("invalid source: {e}")))?;
+        let rate_limit = RateLimit::new(Some(request.max_rate));
+
+        let state = (
+            self.subscriber.subscribe(),
+            packet_ids,
+            sources,
+            rate_limit,
+            false,
+        );
+        let stream = futures::stream::unfold(state, |mut state| async move {
+            let (subscription, packet_ids, sources, rate_limit, closed) = &mut state;
+            if *closed {
+                return None;
+            }
+            loop {
+                let received = match subscription.recv().await {
+                    Ok(received) => received,
+                    // the client only misses packets, it carries on from the oldest buffered
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => {
+                        *closed = true;
+                        let status = Status::unavailable("server shutting down");
+                        return Some((Err(status), state));
+                    }
+                };
+                let packet_id = u8::from(received.packet.packet_id());
+                if !packet_ids.is_empty() && !packet_ids.contains(&packet_id) {
+                    continue;
+                }
+                if !sources.is_empty() && !sources.contains(&received.src) {
+                    continue;
+                }
+                if rate_limit.allow(received.src, packet_id, Instant::now()) {
+                    return Some((Ok((&received).into()), state));
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn get_session_state(
+        &self,
+        request: Request<proto::GetSessionStateRequest>,
+    ) -> Result<Response<proto::SessionState>, Status> {
+        let feed = request.into_inner().feed;
+        if feed.is_empty() {
+            return Ok(Response::new(self.state.read(|snapshot| snapshot.into())));
+        }
+        let feed: FeedId = feed.parse().map_err(Status::invalid_argument)?;
+        self.state
+            .read_feed(&feed, |snapshot| Response::new(snapshot.into()))
+            .ok_or_else(|| Status::not_found(format!("no feed {feed}")))
+    }
+
+    async fn list_feeds(
+        &self,
+        _request: Request<proto::ListFeedsRequest>,
+    ) -> Result<Response<proto::ListFeedsResponse>, Status> {
+        let feeds = self.state.feeds().into_iter().map(Into::into).collect();
+        Ok(Response::new(proto::ListFeedsResponse { feeds }))
+    }
+}
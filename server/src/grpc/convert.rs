@@ -0,0 +1,895 @@
+//! Conversions between the `telemetry` packet structs and the generated protobuf messages.
+//!
+//! Packets convert into messages infallibly. Converting back checks what protobuf can't express:
+//! integers fitting the wire type, arrays having the right length and messages being set.
+
+use std::fmt::Display;
+use std::time::UNIX_EPOCH;
+
+use telemetry::{
+    Buttons, CarDamageData, CarMotionData, CarSetupData, CarStatusData, CarTelemetryData,
+    DriveThroughPenaltyServed, EventDataDetails, FastestLap, FinalClassificationData, Flashback,
+    InfringementType, LapData, LapHistoryData, LobbyInfoData, MarshalZone, Overtake, Packet,
+    PacketCarDamageData, PacketCarSetupData, PacketCarStatusData, PacketCarTelemetryData,
+    PacketEventData, PacketFinalClassificationData, PacketHeader, PacketLapData,
+    PacketLobbyInfoData, PacketMotionData, PacketMotionExData, PacketParticipantsData,
+    PacketSessionData, PacketSessionHistoryData, PacketTyreSetData, ParticipantData, Penalty,
+    PenaltyType, RaceWinner, Retirement, SpeedTrap, StartLights, StopGoPenaltyServed,
+    TeamMateInPits, TyreSetData, TyreStintHistoryData, WeatherForecastSample,
+};
+
+use super::proto;
+use crate::feed::FeedStats;
+use crate::flashback::Rewind;
+use crate::state::{LoggedEvent, SessionSnapshot, Standing};
+use crate::subscription::ReceivedPacket;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConversionError {
+    /// A message or oneof field that the packet requires wasn't set
+    MissingField(&'static str),
+    /// An integer doesn't fit the packet's narrower field
+    OutOfRange(&'static str),
+    /// A repeated field doesn't have the length of the packet's array
+    WrongLength {
+        field: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConversionError::MissingField(field) => write!(f, "Field {} is not set", field),
+            ConversionError::OutOfRange(field) => write!(f, "Field {} is out of range", field),
+            ConversionError::WrongLength {
+                field,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Field {} has {} entries, expected {}",
+                field, actual, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+/// A packet field stored as a protobuf scalar
+trait Scalar: Sized {
+    type Proto;
+
+    fn to_proto(self) -> Self::Proto;
+    fn from_proto(value: Self::Proto, field: &'static str) -> Result<Self, ConversionError>;
+}
+
+macro_rules! widened_scalar {
+    ($($rust:ty => $proto:ty),* $(,)?) => {$(
+        impl Scalar for $rust {
+            type Proto = $proto;
+
+            fn to_proto(self) -> $proto {
+                self.into()
+            }
+
+            fn from_proto(value: $proto, field: &'static str) -> Result<Self, ConversionError> {
+                value.try_into().map_err(|_| ConversionError::OutOfRange(field))
+            }
+        }
+    )*};
+}
+
+widened_scalar!(
+    u8 => u32, u16 => u32, u32 => u32, u64 => u64,
+    i8 => i32, i16 => i32,
+    f32 => f32, f64 => f64, bool => bool,
+);
+
+impl Scalar for PenaltyType {
+    type Proto = u32;
+
+    fn to_proto(self) -> u32 {
+        u8::from(self).into()
+    }
+
+    fn from_proto(value: u32, field: &'static str) -> Result<Self, ConversionError> {
+        u8::from_proto(value, field).map(Into::into)
+    }
+}
+
+impl Scalar for InfringementType {
+    type Proto = u32;
+
+    fn to_proto(self) -> u32 {
+        u8::from(self).into()
+    }
+
+    fn from_proto(value: u32, field: &'static str) -> Result<Self, ConversionError> {
+        u8::from_proto(value, field).map(Into::into)
+    }
+}
+
+fn array<T, const N: usize>(
+    values: Vec<T>,
+    field: &'static str,
+) -> Result<[T; N], ConversionError> {
+    let actual = values.len();
+    values.try_into().map_err(|_| ConversionError::WrongLength {
+        field,
+        expected: N,
+        actual,
+    })
+}
+
+/// Null terminated bytes, e.g. a participant's name
+fn bytes_to_string(bytes: &[u8]) -> String {
+    let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len]).into_owned()
+}
+
+fn string_to_bytes<const N: usize>(
+    value: String,
+    field: &'static str,
+) -> Result<[u8; N], ConversionError> {
+    if value.len() > N {
+        return Err(ConversionError::OutOfRange(field));
+    }
+    let mut bytes = [0; N];
+    bytes[..value.len()].copy_from_slice(value.as_bytes());
+    Ok(bytes)
+}
+
+/// Implements both directions for a struct, given the kind of each of its fields:
+/// `scalar`, `message`, `string` (null terminated bytes), `[scalar]` or `[message]` (arrays)
+macro_rules! convert {
+    ($rust:ident { $($field:ident: $kind:tt),* $(,)? }) => {
+        impl From<$rust> for proto::$rust {
+            fn from(value: $rust) -> Self {
+                Self {
+                    $($field: convert!(@to $kind, { value.$field })),*
+                }
+            }
+        }
+
+        impl TryFrom<proto::$rust> for $rust {
+            type Error = ConversionError;
+
+            fn try_from(value: proto::$rust) -> Result<Self, Self::Error> {
+                Ok(Self {
+                    $($field: convert!(@from $kind, value.$field, stringify!($field))),*
+                })
+            }
+        }
+    };
+    (@to scalar, $value:expr) => {
+        Scalar::to_proto($value)
+    };
+    (@to message, $value:expr) => {
+        Some($value.into())
+    };
+    (@to string, $value:expr) => {
+        bytes_to_string(&$value)
+    };
+    (@to [scalar], $value:expr) => {
+        $value.into_iter().map(Scalar::to_proto).collect()
+    };
+    (@to [message], $value:expr) => {
+        $value.into_iter().map(Into::into).collect()
+    };
+    (@from scalar, $value:expr, $name:expr) => {
+        Scalar::from_proto($value, $name)?
+    };
+    (@from message, $value:expr, $name:expr) => {
+        $value
+            .ok_or(ConversionError::MissingField($name))?
+            .try_into()?
+    };
+    (@from string, $value:expr, $name:expr) => {
+        string_to_bytes($value, $name)?
+    };
+    (@from [scalar], $value:expr, $name:expr) => {
+        array(
+            $value
+                .into_iter()
+                .map(|value| Scalar::from_proto(value, $name))
+                .collect::<Result<_, _>>()?,
+            $name,
+        )?
+    };
+    (@from [message], $value:expr, $name:expr) => {
+        array(
+            $value
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
+            $name,
+        )?
+    };
+}
+
+convert!(PacketHeader {
+    packet_format: scalar,
+    game_year: scalar,
+    game_major_version: scalar,
+    game_minor_version: scalar,
+    packet_version: scalar,
+    packet_id: scalar,
+    session_uid: scalar,
+    session_time: scalar,
+    frame_identifier: scalar,
+    overall_frame_identifier: scalar,
+    player_car_index: scalar,
+    secondary_player_car_index: scalar,
+});
+
+convert!(PacketMotionData {
+    header: message,
+    car_motion_data: [message],
+});
+
+convert!(CarMotionData {
+    world_position_x: scalar,
+    world_position_y: scalar,
+    world_position_z: scalar,
+    world_velocity_x: scalar,
+    world_velocity_y: scalar,
+    world_velocity_z: scalar,
+    world_forward_dir_x: scalar,
+    world_forward_dir_y: scalar,
+    world_forward_dir_z: scalar,
+    world_right_dir_x: scalar,
+    world_right_dir_y: scalar,
+    world_right_dir_z: scalar,
+    g_force_lateral: scalar,
+    g_force_longitudinal: scalar,
+    g_force_vertical: scalar,
+    m_yaw: scalar,
+    m_pitch: scalar,
+    m_roll: scalar,
+});
+
+convert!(PacketSessionData {
+    header: message,
+    weather: scalar,
+    track_temperature: scalar,
+    air_temperature: scalar,
+    total_laps: scalar,
+    track_length: scalar,
+    session_type: scalar,
+    track_id: scalar,
+    formula: scalar,
+    session_time_left: scalar,
+    session_duration: scalar,
+    pit_speed_limit: scalar,
+    game_paused: scalar,
+    is_spectating: scalar,
+    spectator_car_index: scalar,
+    sli_pro_native_support: scalar,
+    num_marshal_zones: scalar,
+    marshal_zones: [message],
+    safety_car_status: scalar,
+    network_game: scalar,
+    num_weather_forecast_samples: scalar,
+    weather_forecast_samples: [message],
+    forecast_accuracy: scalar,
+    ai_difficulty: scalar,
+    season_link_identifier: scalar,
+    weekend_link_identifier: scalar,
+    session_link_identifier: scalar,
+    pit_stop_window_ideal_lap: scalar,
+    pit_stop_window_latest_lap: scalar,
+    pit_stop_rejoin_position: scalar,
+    steering_assist: scalar,
+    braking_assist: scalar,
+    gearbox_assist: scalar,
+    pit_assist: scalar,
+    pit_release_assist: scalar,
+    ers_assist: scalar,
+    drs_assist: scalar,
+    dynamic_racing_line: scalar,
+    dynamic_racing_line_type: scalar,
+    game_mode: scalar,
+    rule_set: scalar,
+    time_of_day: scalar,
+    session_length: scalar,
+    speed_units_lead_player: scalar,
+    temperature_units_lead_player: scalar,
+    speed_units_secondary_player: scalar,
+    temperature_units_secondary_player: scalar,
+    num_safety_car_periods: scalar,
+    num_virtual_safety_car_periods: scalar,
+    num_red_flag_periods: scalar,
+});
+
+convert!(MarshalZone {
+    zone_start: scalar,
+    zone_flag: scalar,
+});
+
+convert!(WeatherForecastSample {
+    session_type: scalar,
+    time_offset: scalar,
+    weather: scalar,
+    track_temperature: scalar,
+    track_temperature_change: scalar,
+    air_temperature: scalar,
+    air_temperature_change: scalar,
+    rain_percentage: scalar,
+});
+
+convert!(PacketLapData {
+    header: message,
+    lap_data: [message],
+    time_trial_personal_best_car_idx: scalar,
+    time_trial_rival_car_idx: scalar,
+});
+
+convert!(LapData {
+    last_lap_time_in_ms: scalar,
+    current_lap_time_in_ms: scalar,
+    sector1_time_in_ms: scalar,
+    sector1_time_minutes: scalar,
+    sector2_time_in_ms: scalar,
+    sector2_time_minutes: scalar,
+    delta_to_car_in_front_in_ms: scalar,
+    delta_to_race_leader_in_ms: scalar,
+    lap_distance: scalar,
+    total_distance: scalar,
+    safety_car_delta: scalar,
+    car_position: scalar,
+    current_lap_num: scalar,
+    pit_status: scalar,
+    num_pit_stops: scalar,
+    sector: scalar,
+    current_lap_invalid: scalar,
+    penalties: scalar,
+    total_warnings: scalar,
+    corner_cutting_warnings: scalar,
+    num_unserved_drive_through_pens: scalar,
+    num_unserved_stop_go_pens: scalar,
+    grid_position: scalar,
+    driver_status: scalar,
+    result_status: scalar,
+    pit_lane_timer_active: scalar,
+    pit_lane_time_in_lane_in_ms: scalar,
+    pit_stop_timer_in_ms: scalar,
+    pit_stop_should_serve_pen: scalar,
+});
+
+convert!(PacketEventData {
+    header: message,
+    event_string_code: string,
+    event_details: message,
+});
+
+convert!(FastestLap {
+    vehicle_idx: scalar,
+    lap_time: scalar,
+});
+
+convert!(Retirement {
+    vehicle_idx: scalar,
+});
+
+convert!(TeamMateInPits {
+    vehicle_idx: scalar,
+});
+
+convert!(RaceWinner {
+    vehicle_idx: scalar,
+});
+
+convert!(Penalty {
+    penalty_type: scalar,
+    infringement_type: scalar,
+    vehicle_idx: scalar,
+    other_vehicle_idx: scalar,
+    time: scalar,
+    lap_num: scalar,
+    places_gained: scalar,
+});
+
+convert!(SpeedTrap {
+    vehicle_idx: scalar,
+    speed: scalar,
+    is_overall_fastest_in_session: scalar,
+    is_driver_fastest_in_session: scalar,
+    fastest_vehicle_idx_in_session: scalar,
+    fastest_speed_in_session: scalar,
+});
+
+convert!(StartLights { num_lights: scalar });
+
+convert!(DriveThroughPenaltyServed {
+    vehicle_idx: scalar,
+});
+
+convert!(StopGoPenaltyServed {
+    vehicle_idx: scalar,
+});
+
+convert!(Flashback {
+    flashback_frame_identifier: scalar,
+    flashback_session_time: scalar,
+});
+
+convert!(Buttons {
+    button_status: scalar,
+});
+
+convert!(Overtake {
+    overtaking_vehicle_idx: scalar,
+    being_overtaken_vehicle_idx: scalar,
+});
+
+convert!(PacketParticipantsData {
+    header: message,
+    num_active_cars_u8: scalar,
+    participants: [message],
+});
+
+convert!(ParticipantData {
+    ai_controlled: scalar,
+    driver_id: scalar,
+    network_id: scalar,
+    team_id: scalar,
+    my_team: scalar,
+    race_number: scalar,
+    nationality: scalar,
+    name: string,
+    your_telemetry: scalar,
+    show_online_names: scalar,
+    platform: scalar,
+});
+
+convert!(PacketCarSetupData {
+    header: message,
+    car_setups: [message],
+});
+
+convert!(CarSetupData {
+    front_wing: scalar,
+    rear_wing: scalar,
+    on_throttle: scalar,
+    off_throttle: scalar,
+    front_camber: scalar,
+    rear_camber: scalar,
+    front_toe: scalar,
+    rear_toe: scalar,
+    front_suspension: scalar,
+    rear_suspension: scalar,
+    front_anti_roll_bar: scalar,
+    rear_anti_roll_bar: scalar,
+    front_suspension_height: scalar,
+    rear_suspension_height: scalar,
+    brake_pressure: scalar,
+    brake_bias: scalar,
+    rear_left_tyre_pressure: scalar,
+    rear_right_tyre_pressure: scalar,
+    front_left_tyre_pressure: scalar,
+    front_right_tyre_pressure: scalar,
+    ballast: scalar,
+    fuel_load: scalar,
+});
+
+convert!(PacketCarTelemetryData {
+    header: message,
+    car_telemetry_data: [message],
+    mfd_panel_index: scalar,
+    mfd_panel_index_secondary_player: scalar,
+    suggested_gear: scalar,
+});
+
+convert!(CarTelemetryData {
+    speed: scalar,
+    throttle: scalar,
+    steer: scalar,
+    brake: scalar,
+    clutch: scalar,
+    gear: scalar,
+    engine_rpm: scalar,
+    drs: scalar,
+    rev_lights_percent: scalar,
+    rev_lights_bit_value: scalar,
+    brakes_temperature: [scalar],
+    tyres_surface_temperature: [scalar],
+    tyres_inner_temperature: [scalar],
+    engine_temperature: scalar,
+    tyres_pressure: [scalar],
+    surface_type: [scalar],
+});
+
+convert!(PacketCarStatusData {
+    header: message,
+    car_status_data: [message],
+});
+
+convert!(CarStatusData {
+    traction_control: scalar,
+    anti_lock_brakes: scalar,
+    fuel_mix: scalar,
+    front_brake_bias: scalar,
+    pit_limiter_status: scalar,
+    fuel_in_tank: scalar,
+    fuel_capacity: scalar,
+    fuel_remaining_laps: scalar,
+    max_rpm: scalar,
+    idle_rpm: scalar,
+    max_gears: scalar,
+    drs_allowed: scalar,
+    drs_activation_distance: scalar,
+    actual_tyre_compound: scalar,
+    visual_tyre_compound: scalar,
+    tyres_age_laps: scalar,
+    vehicle_fia_flags: scalar,
+    engine_power_ice: scalar,
+    engine_power_mgu_k: scalar,
+    ers_store_energy: scalar,
+    ers_deploy_mode: scalar,
+    ers_harvested_this_lap_mgu_k: scalar,
+    ers_harvested_this_lap_mgu_h: scalar,
+    ers_deployed_this_lap: scalar,
+    network_paused: scalar,
+});
+
+convert!(PacketFinalClassificationData {
+    header: message,
+    num_cars: scalar,
+    classification_data: [message],
+});
+
+convert!(FinalClassificationData {
+    position: scalar,
+    num_laps: scalar,
+    grid_position: scalar,
+    points: scalar,
+    num_pit_stops: scalar,
+    result_status: scalar,
+    best_lap_time_in_ms: scalar,
+    total_race_time: scalar,
+    penalties_time: scalar,
+    num_penalties: scalar,
+    num_tyre_stints: scalar,
+    tyre_stints_actual: [scalar],
+    tyre_stints_visual: [scalar],
+    tyre_stints_end_laps: [scalar],
+});
+
+convert!(PacketLobbyInfoData {
+    header: message,
+    num_players: scalar,
+    lobby_players: [message],
+});
+
+convert!(LobbyInfoData {
+    ai_controlled: scalar,
+    team_id: scalar,
+    nationality: scalar,
+    platform: scalar,
+    name: string,
+    car_number: scalar,
+    ready_status: scalar,
+});
+
+convert!(PacketCarDamageData {
+    header: message,
+    car_damage_data: [message],
+});
+
+convert!(CarDamageData {
+    tyres_wear: [scalar],
+    tyres_damage: [scalar],
+    brakes_damage: [scalar],
+    front_left_wing_damage: scalar,
+    front_right_wing_damage: scalar,
+    rear_wing_damage: scalar,
+    floor_damage: scalar,
+    diffuser_damage: scalar,
+    sidepod_damage: scalar,
+    drs_fault: scalar,
+    ers_fault: scalar,
+    gear_box_damage: scalar,
+    engine_damage: scalar,
+    engine_mgu_h_wear: scalar,
+    engine_es_wear: scalar,
+    engine_ce_wear: scalar,
+    engine_ice_wear: scalar,
+    engine_mgu_k_wear: scalar,
+    engine_tc_wear: scalar,
+    engine_blown: scalar,
+    engine_seized: scalar,
+});
+
+convert!(PacketSessionHistoryData {
+    header: message,
+    car_idx: scalar,
+    num_laps: scalar,
+    num_tyre_stints: scalar,
+    best_lap_time_lap_num: scalar,
+    best_sector_1_lap_num: scalar,
+    best_sector_2_lap_num: scalar,
+    best_sector_3_lap_num: scalar,
+    lap_history_data: [message],
+    tyre_stints_history_data: [message],
+});
+
+convert!(LapHistoryData {
+    lap_time_in_ms: scalar,
+    sector_1_time_in_ms: scalar,
+    sector_1_time_minutes: scalar,
+    sector_2_time_in_ms: scalar,
+    sector_2_time_minutes: scalar,
+    sector_3_time_in_ms: scalar,
+    sector_3_time_minutes: scalar,
+    lap_valid_bit_flags: scalar,
+});
+
+convert!(TyreStintHistoryData {
+    end_lap: scalar,
+    tyre_actual_compound: scalar,
+    tyre_visual_compound: scalar,
+});
+
+convert!(PacketTyreSetData {
+    header: message,
+    car_idx: scalar,
+    tyre_set_data: [message],
+    fitted_idx: scalar,
+});
+
+convert!(TyreSetData {
+    actual_tyre_compound: scalar,
+    visual_tyre_compound: scalar,
+    wear: scalar,
+    available: scalar,
+    recommended_session: scalar,
+    life_span: scalar,
+    usable_life: scalar,
+    lap_delta_time: scalar,
+    fitted: scalar,
+});
+
+convert!(PacketMotionExData {
+    header: message,
+    suspension_position: [scalar],
+    suspension_velocity: [scalar],
+    suspension_acceleration: [scalar],
+    wheel_speed: [scalar],
+    wheel_slip_ratio: [scalar],
+    wheel_slip_angle: [scalar],
+    wheel_lat_force: [scalar],
+    wheel_long_force: [scalar],
+    height_of_cog_above_ground: scalar,
+    local_velocity_x: scalar,
+    local_velocity_y: scalar,
+    local_velocity_z: scalar,
+    angular_velocity_x: scalar,
+    angular_velocity_y: scalar,
+    angular_velocity_z: scalar,
+    angular_acceleration_x: scalar,
+    angular_acceleration_y: scalar,
+    angular_acceleration_z: scalar,
+    front_wheels_angle: scalar,
+    wheel_vert_force: [scalar],
+});
+
+impl From<EventDataDetails> for proto::EventDetails {
+    fn from(details: EventDataDetails) -> Self {
+        use proto::event_details::Details;
+
+        let details = match details {
+            EventDataDetails::FastestLap(d) => Details::FastestLap(d.into()),
+            EventDataDetails::Retirement(d) => Details::Retirement(d.into()),
+            EventDataDetails::TeamMateInPits(d) => Details::TeamMateInPits(d.into()),
+            EventDataDetails::RaceWinner(d) => Details::RaceWinner(d.into()),
+            EventDataDetails::Penalty(d) => Details::Penalty(d.into()),
+            EventDataDetails::SpeedTrap(d) => Details::SpeedTrap(d.into()),
+            EventDataDetails::StartLights(d) => Details::StartLights(d.into()),
+            EventDataDetails::DriveThroughPenaltyServed(d) => {
+                Details::DriveThroughPenaltyServed(d.into())
+            }
+            EventDataDetails::StopGoPenaltyServed(d) => Details::StopGoPenaltyServed(d.into()),
+            EventDataDetails::Flashback(d) => Details::Flashback(d.into()),
+            EventDataDetails::Buttons(d) => Details::Buttons(d.into()),
+            EventDataDetails::Overtake(d) => Details::Overtake(d.into()),
+            EventDataDetails::SessionStarted => Details::SessionStarted(proto::NoDetails {}),
+            EventDataDetails::SessionEnded => Details::SessionEnded(proto::NoDetails {}),
+            EventDataDetails::DRSEnabled => Details::DrsEnabled(proto::NoDetails {}),
+            EventDataDetails::DRSDisabled => Details::DrsDisabled(proto::NoDetails {}),
+            EventDataDetails::ChequeredFlag => Details::ChequeredFlag(proto::NoDetails {}),
+            EventDataDetails::LightsOut => Details::LightsOut(proto::NoDetails {}),
+            EventDataDetails::RedFlag => Details::RedFlag(proto::NoDetails {}),
+        };
+        Self {
+            details: Some(details),
+        }
+    }
+}
+
+impl TryFrom<proto::EventDetails> for EventDataDetails {
+    type Error = ConversionError;
+
+    fn try_from(details: proto::EventDetails) -> Result<Self, Self::Error> {
+        use proto::event_details::Details;
+
+        let details = details
+            .details
+            .ok_or(ConversionError::MissingField("details"))?;
+        Ok(match details {
+            Details::FastestLap(d) => EventDataDetails::FastestLap(d.try_into()?),
+            Details::Retirement(d) => EventDataDetails::Retirement(d.try_into()?),
+            Details::TeamMateInPits(d) => EventDataDetails::TeamMateInPits(d.try_into()?),
+            Details::RaceWinner(d) => EventDataDetails::RaceWinner(d.try_into()?),
+            Details::Penalty(d) => EventDataDetails::Penalty(d.try_into()?),
+            Details::SpeedTrap(d) => EventDataDetails::SpeedTrap(d.try_into()?),
+            Details::StartLights(d) => EventDataDetails::StartLights(d.try_into()?),
+            Details::DriveThroughPenaltyServed(d) => {
+                EventDataDetails::DriveThroughPenaltyServed(d.try_into()?)
+            }
+            Details::StopGoPenaltyServed(d) => EventDataDetails::StopGoPenaltyServed(d.try_into()?),
+            Details::Flashback(d) => EventDataDetails::Flashback(d.try_into()?),
+            Details::Buttons(d) => EventDataDetails::Buttons(d.try_into()?),
+            Details::Overtake(d) => EventDataDetails::Overtake(d.try_into()?),
+            Details::SessionStarted(_) => EventDataDetails::SessionStarted,
+            Details::SessionEnded(_) => EventDataDetails::SessionEnded,
+            Details::DrsEnabled(_) => EventDataDetails::DRSEnabled,
+            Details::DrsDisabled(_) => EventDataDetails::DRSDisabled,
+            Details::ChequeredFlag(_) => EventDataDetails::ChequeredFlag,
+            Details::LightsOut(_) => EventDataDetails::LightsOut,
+            Details::RedFlag(_) => EventDataDetails::RedFlag,
+        })
+    }
+}
+
+impl From<Packet> for proto::Packet {
+    fn from(packet: Packet) -> Self {
+        use proto::packet::Packet as P;
+
+        let packet = match packet {
+            Packet::Header(p) => P::Header(p.into()),
+            Packet::Motion(p) => P::Motion(p.into()),
+            Packet::Session(p) => P::Session(p.into()),
+            Packet::Lap(p) => P::Lap(p.into()),
+            Packet::Event(p) => P::Event(p.into()),
+            Packet::Participants(p) => P::Participants(p.into()),
+            Packet::CarSetups(p) => P::CarSetups(p.into()),
+            Packet::CarTelemetry(p) => P::CarTelemetry(p.into()),
+            Packet::CarStatus(p) => P::CarStatus(p.into()),
+            Packet::FinalClassification(p) => P::FinalClassification(p.into()),
+            Packet::LobbyInfo(p) => P::LobbyInfo(p.into()),
+            Packet::CarDamage(p) => P::CarDamage(p.into()),
+            Packet::SessionHistory(p) => P::SessionHistory(p.into()),
+            Packet::TyreSets(p) => P::TyreSets(p.into()),
+            Packet::MotionEx(p) => P::MotionEx(p.into()),
+        };
+        Self {
+            packet: Some(packet),
+        }
+    }
+}
+
+impl TryFrom<proto::Packet> for Packet {
+    type Error = ConversionError;
+
+    fn try_from(packet: proto::Packet) -> Result<Self, Self::Error> {
+        use proto::packet::Packet as P;
+
+        let packet = packet
+            .packet
+            .ok_or(ConversionError::MissingField("packet"))?;
+        Ok(match packet {
+            P::Header(p) => Packet::Header(p.try_into()?),
+            P::Motion(p) => Packet::Motion(p.try_into()?),
+            P::Session(p) => Packet::Session(p.try_into()?),
+            P::Lap(p) => Packet::Lap(p.try_into()?),
+            P::Event(p) => Packet::Event(p.try_into()?),
+            P::Participants(p) => Packet::Participants(p.try_into()?),
+            P::CarSetups(p) => Packet::CarSetups(p.try_into()?),
+            P::CarTelemetry(p) => Packet::CarTelemetry(p.try_into()?),
+            P::CarStatus(p) => Packet::CarStatus(p.try_into()?),
+            P::FinalClassification(p) => Packet::FinalClassification(p.try_into()?),
+            P::LobbyInfo(p) => Packet::LobbyInfo(p.try_into()?),
+            P::CarDamage(p) => Packet::CarDamage(p.try_into()?),
+            P::SessionHistory(p) => Packet::SessionHistory(p.try_into()?),
+            P::TyreSets(p) => Packet::TyreSets(p.try_into()?),
+            P::MotionEx(p) => Packet::MotionEx(p.try_into()?),
+        })
+    }
+}
+
+impl From<&ReceivedPacket> for proto::ReceivedPacket {
+    fn from(received: &ReceivedPacket) -> Self {
+        Self {
+            src: received.src.to_string(),
+            feed: received.feed.to_string(),
+            received_at: received
+                .received_at
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            packet: Some((*received.packet).into()),
+            rewind: received.rewind.map(Into::into),
+        }
+    }
+}
+
+impl From<Rewind> for proto::Rewind {
+    fn from(rewind: Rewind) -> Self {
+        Self {
+            frame_identifier: rewind.frame_identifier,
+            session_time: rewind.session_time,
+            from_frame_identifier: rewind.from_frame_identifier,
+            from_session_time: rewind.from_session_time,
+            overall_frame_identifier: rewind.overall_frame_identifier,
+        }
+    }
+}
+
+impl From<Standing> for proto::Standing {
+    fn from(standing: Standing) -> Self {
+        Self {
+            position: standing.position.into(),
+            car_index: standing.car_index as u32,
+            name: standing.name,
+            team_id: standing.team_id.map(Into::into),
+            race_number: standing.race_number.map(Into::into),
+            current_lap: standing.current_lap.into(),
+            last_lap_time_in_ms: standing.last_lap_time_in_ms,
+            delta_to_car_in_front_in_ms: standing.delta_to_car_in_front_in_ms.into(),
+            delta_to_race_leader_in_ms: standing.delta_to_race_leader_in_ms.into(),
+            pit_status: standing.pit_status.into(),
+            num_pit_stops: standing.num_pit_stops.into(),
+            penalties: standing.penalties.into(),
+            result_status: standing.result_status.into(),
+        }
+    }
+}
+
+impl From<LoggedEvent> for proto::LoggedEvent {
+    fn from(event: LoggedEvent) -> Self {
+        Self {
+            received_at: event.received_at,
+            session_time: event.session_time,
+            code: event.code,
+            details: Some(event.details.into()),
+        }
+    }
+}
+
+impl From<&SessionSnapshot> for proto::SessionState {
+    fn from(snapshot: &SessionSnapshot) -> Self {
+        Self {
+            session_uid: snapshot.session_uid,
+            session: snapshot.session.map(Into::into),
+            lap: snapshot.lap.map(Into::into),
+            participants: snapshot.participants.map(Into::into),
+            car_status: snapshot.car_status.map(Into::into),
+            car_damage: snapshot.car_damage.map(Into::into),
+            final_classification: snapshot.final_classification.map(Into::into),
+            standings: snapshot.standings().into_iter().map(Into::into).collect(),
+            events: snapshot.events.iter().cloned().map(Into::into).collect(),
+            feed: snapshot
+                .feed
+                .map(|feed| feed.to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl From<FeedStats> for proto::FeedStats {
+    fn from(stats: FeedStats) -> Self {
+        Self {
+            feed: stats.feed.to_string(),
+            packets: stats.packets,
+            bytes: stats.bytes,
+            first_received: stats.first_received,
+            last_received: stats.last_received,
+            player_car_index: stats.player_car_index.into(),
+            player_name: stats.player_name,
+            packets_by_type: stats.packets_by_type.into_iter().collect(),
+        }
+    }
+}
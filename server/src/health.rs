@@ -0,0 +1,259 @@
+//! Stream health: how much of each feed is lost, duplicated or reordered on the way here.
+//!
+//! Packets sent at the rate set in the game's menus (motion, lap, car telemetry, car status and
+//! extended motion) all go out together on the same frame, so they're checked frame by frame
+//! using `overall_frame_identifier`, which unlike `frame_identifier` never goes back after a
+//! flashback:
+//!
+//! - a type is missing from a frame when the frames either side of it have it, since motion
+//!   packets stop while the player isn't in control
+//! - a whole frame is missing when the gap since the previous frame is well over the usual
+//!   gap between sent frames, which depends on the send rate
+//!
+//! Other packet types are sent on timers of their own, so for them only duplicates and
+//! reordering are counted. A duplicate is a datagram identical to a recent one, and a datagram is
+//! out of order when it's from an earlier frame than the last one of its type.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use serde::Serialize;
+use telemetry::{Attributes, Packet, PacketDescriptor};
+
+use crate::feed::FeedId;
+use crate::frame::per_frame;
+use crate::sink::{Sink, SinkError};
+
+/// Frames kept open for late packets before counting what's missing from them
+const OPEN_FRAMES: usize = 8;
+/// Recent datagrams remembered per feed to spot duplicates
+const DUPLICATE_WINDOW: usize = 512;
+/// Gaps between sent frames used to work out the usual one
+const GAP_SAMPLES: usize = 32;
+/// Frames counted as missing one packet type, remembered in case the packet turns up late
+const MISSED_WINDOW: usize = 256;
+
+/// Health of one packet type of a feed
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PacketHealth {
+    pub received: u64,
+    /// Estimated, only for packets sent on every frame
+    pub missing: u64,
+    pub duplicates: u64,
+    pub out_of_order: u64,
+    /// `missing` as a percentage of the packets that should have arrived
+    pub loss_percent: f64,
+}
+
+/// Health of a feed as a whole and per packet type
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FeedHealth {
+    pub feed: FeedId,
+    pub received: u64,
+    pub missing: u64,
+    /// Frames none of whose packets arrived
+    pub missing_frames: u64,
+    pub duplicates: u64,
+    pub out_of_order: u64,
+    pub loss_percent: f64,
+    /// By packet short name, e.g. `car_telemetry`
+    pub packets: BTreeMap<String, PacketHealth>,
+}
+
+fn loss_percent(received: u64, missing: u64) -> f64 {
+    match received + missing {
+        0 => 0.0,
+        expected => missing as f64 * 100.0 / expected as f64,
+    }
+}
+
+#[derive(Debug, Default)]
+struct TypeCounts {
+    health: PacketHealth,
+    last_frame: Option<u32>,
+}
+
+#[derive(Debug, Default)]
+struct FeedMonitor {
+    types: BTreeMap<u8, TypeCounts>,
+    /// Per-frame packet types received in each frame still waiting for late packets
+    open_frames: BTreeMap<u32, HashSet<u8>>,
+    /// The last frame that stopped waiting, and the types it had
+    closed: Option<(u32, HashSet<u8>)>,
+    gaps: VecDeque<u32>,
+    missing_frames: u64,
+    missed: VecDeque<(u32, u8)>,
+    recent: VecDeque<u64>,
+    recent_set: HashSet<u64>,
+}
+
+impl FeedMonitor {
+    fn record(&mut self, raw: &[u8], packet: &Packet) {
+        let packet_id = u8::from(packet.packet_id());
+        let frame = packet.header().overall_frame_identifier;
+
+        let mut hasher = DefaultHasher::new();
+        raw.hash(&mut hasher);
+        let hash = hasher.finish();
+        let counts = self.types.entry(packet_id).or_default();
+        if !self.recent_set.insert(hash) {
+            counts.health.duplicates += 1;
+            return;
+        }
+        self.recent.push_back(hash);
+        if self.recent.len() > DUPLICATE_WINDOW {
+            if let Some(oldest) = self.recent.pop_front() {
+                self.recent_set.remove(&oldest);
+            }
+        }
+
+        counts.health.received += 1;
+        match counts.last_frame {
+            Some(last) if frame < last => counts.health.out_of_order += 1,
+            _ => counts.last_frame = Some(frame),
+        }
+        if !per_frame(packet_id) {
+            return;
+        }
+
+        if self
+            .closed
+            .as_ref()
+            .is_some_and(|(closed, _)| frame <= *closed)
+        {
+            // too late for its frame, which may have counted it as missing
+            if let Some(i) = self.missed.iter().position(|m| *m == (frame, packet_id)) {
+                self.missed.remove(i);
+                let health = &mut self.types.entry(packet_id).or_default().health;
+                health.missing = health.missing.saturating_sub(1);
+            }
+            return;
+        }
+        self.open_frames.entry(frame).or_default().insert(packet_id);
+        while self.open_frames.len() > OPEN_FRAMES {
+            self.close_oldest();
+        }
+    }
+
+    /// Stops waiting on the oldest open frame and counts what never arrived for it
+    fn close_oldest(&mut self) {
+        let Some((frame, types)) = self.open_frames.pop_first() else {
+            return;
+        };
+        let next = self.open_frames.values().next();
+
+        if let Some((previous, previous_types)) = &self.closed {
+            let gap = frame - previous;
+            let mut lost = 0;
+            if self.gaps.len() >= GAP_SAMPLES / 4 {
+                let mut sorted: Vec<u32> = self.gaps.iter().copied().collect();
+                sorted.sort_unstable();
+                let usual = sorted[sorted.len() / 2].max(1);
+                if gap * 2 > usual * 3 {
+                    lost = ((gap + usual / 2) / usual).saturating_sub(1) as u64;
+                }
+            }
+            if lost > 0 {
+                self.missing_frames += lost;
+                for packet_id in previous_types.intersection(&types) {
+                    self.types.entry(*packet_id).or_default().health.missing += lost;
+                }
+            }
+            self.gaps.push_back(gap);
+            if self.gaps.len() > GAP_SAMPLES {
+                self.gaps.pop_front();
+            }
+
+            let expected = previous_types
+                .iter()
+                .filter(|id| !types.contains(id) && next.is_some_and(|next| next.contains(id)));
+            for packet_id in expected {
+                self.types.entry(*packet_id).or_default().health.missing += 1;
+                self.missed.push_back((frame, *packet_id));
+                if self.missed.len() > MISSED_WINDOW {
+                    self.missed.pop_front();
+                }
+            }
+        }
+        self.closed = Some((frame, types));
+    }
+
+    fn health(&self, feed: FeedId) -> FeedHealth {
+        let mut report = FeedHealth {
+            feed,
+            received: 0,
+            missing: 0,
+            missing_frames: self.missing_frames,
+            duplicates: 0,
+            out_of_order: 0,
+            loss_percent: 0.0,
+            packets: BTreeMap::new(),
+        };
+        for (packet_id, counts) in &self.types {
+            let Some(descriptor) = PacketDescriptor::for_id(*packet_id) else {
+                continue;
+            };
+            let mut health = counts.health.clone();
+            health.loss_percent = loss_percent(health.received, health.missing);
+            report.received += health.received;
+            report.missing += health.missing;
+            report.duplicates += health.duplicates;
+            report.out_of_order += health.out_of_order;
+            report.packets.insert(descriptor.short_name(), health);
+        }
+        report.loss_percent = loss_percent(report.received, report.missing);
+        report
+    }
+}
+
+/// Watches every feed for lost, duplicated and reordered datagrams. Like
+/// [`SessionState`](crate::SessionState), each sender only has one feed at a time
+#[derive(Debug, Default)]
+pub struct HealthMonitor {
+    feeds: Mutex<HashMap<FeedId, FeedMonitor>>,
+}
+
+impl HealthMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Health of every feed so far
+    pub fn report(&self) -> Vec<FeedHealth> {
+        let feeds = self.feeds.lock().unwrap_or_else(|e| e.into_inner());
+        let mut report: Vec<FeedHealth> = feeds
+            .iter()
+            .map(|(feed, monitor)| monitor.health(*feed))
+            .collect();
+        report.sort_by_key(|health| health.feed);
+        report
+    }
+}
+
+#[async_trait]
+impl Sink for HealthMonitor {
+    fn name(&self) -> &str {
+        "health monitor"
+    }
+
+    async fn handle(
+        &self,
+        src: SocketAddr,
+        _: SystemTime,
+        raw: &[u8],
+        packet: &Packet,
+    ) -> Result<(), SinkError> {
+        let id = FeedId::new(src, packet);
+        let mut feeds = self.feeds.lock().unwrap_or_else(|e| e.into_inner());
+        if !feeds.contains_key(&id) {
+            feeds.retain(|feed, _| feed.src != src);
+        }
+        feeds.entry(id).or_default().record(raw, packet);
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+//! Reading capture files back for people: summaries for `server inspect`, and the packets in them
+//! as text for `server export`.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::json;
+use telemetry::{FromBytes, Packet};
+
+use crate::capture::{CaptureReader, CaptureSummary};
+use crate::output::{Format, PacketFilter};
+
+/// Opens a capture file, saying which one in any error
+pub fn open(path: &Path) -> io::Result<CaptureReader<BufReader<File>>> {
+    File::open(path)
+        .and_then(|file| CaptureReader::new(BufReader::new(file)))
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))
+}
+
+/// Writes what `reader` holds: a few lines of text, or a JSON object on one line if `as_json`
+pub fn write_summary(
+    out: &mut impl Write,
+    path: &Path,
+    reader: CaptureReader<impl Read>,
+    as_json: bool,
+) -> io::Result<()> {
+    let header = reader.header().clone();
+    let summary = CaptureSummary::read(reader);
+    let unix_millis = |time: Option<SystemTime>| {
+        time.and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_millis() as u64)
+    };
+
+    if as_json {
+        let sessions: Vec<_> = summary
+            .sessions
+            .iter()
+            .map(|session| {
+                json!({
+                    "feed": session.feed,
+                    "session_uid": session.session_uid,
+                    "packet_format": session.packet_format,
+                    "packets": session.packets,
+                    "first_session_time": session.first_session_time,
+                    "last_session_time": session.last_session_time,
+                })
+            })
+            .collect();
+        let summary = json!({
+            "file": path.display().to_string(),
+            "capture_version": header.capture_version,
+            "tool_version": header.tool_version,
+            "records": summary.records,
+            "first_received_at": unix_millis(summary.first_received),
+            "last_received_at": unix_millis(summary.last_received),
+            "duration": summary.duration().as_secs_f64(),
+            "packets": summary.packets,
+            "sessions": sessions,
+            "decode_errors": summary.decode_errors,
+            "read_error": summary.read_error,
+            "finished_at": unix_millis(summary.footer.as_ref().map(|footer| footer.finished)),
+        });
+        return writeln!(out, "{summary}");
+    }
+
+    writeln!(out, "{}", path.display())?;
+    writeln!(
+        out,
+        "  written by {} (capture version {})",
+        header.tool_version, header.capture_version
+    )?;
+    writeln!(
+        out,
+        "  {} datagrams over {:.1}s",
+        summary.records,
+        summary.duration().as_secs_f64()
+    )?;
+    writeln!(out, "  sessions:")?;
+    for session in &summary.sessions {
+        writeln!(
+            out,
+            "    {:016x}  from {}  format {}  {} packets  session time {:.3}s to {:.3}s",
+            session.session_uid,
+            session.feed.src,
+            session.packet_format,
+            session.packets,
+            session.first_session_time,
+            session.last_session_time
+        )?;
+    }
+    writeln!(out, "  packets:")?;
+    for (name, count) in &summary.packets {
+        writeln!(out, "    {name:<22}{count}")?;
+    }
+    let errors: u64 = summary.decode_errors.values().sum();
+    writeln!(out, "  decode errors: {errors}")?;
+    for (error, count) in &summary.decode_errors {
+        writeln!(out, "    {count:>6}  {error}")?;
+    }
+    if let Some(e) = &summary.read_error {
+        writeln!(out, "  stopped early: {e}")?;
+    }
+    // older versions never finished their files
+    if summary.footer.is_none() && header.capture_version >= 2 {
+        writeln!(out, "  never finished, the recording was cut short")?;
+    }
+    Ok(())
+}
+
+/// Writes every packet in `captures` that `filter` lets through as `format`, in order. Returns how
+/// many datagrams were skipped for not decoding
+pub fn export(
+    out: &mut impl Write,
+    captures: &[PathBuf],
+    format: Format,
+    filter: &PacketFilter,
+) -> io::Result<u64> {
+    let mut skipped = 0;
+    for path in captures {
+        for record in open(path)? {
+            let record = record?;
+            let Ok(packet) = Packet::from_bytes(&record.data) else {
+                skipped += 1;
+                continue;
+            };
+            if let Some(text) = format.format(filter, record.src, record.received_at, &packet) {
+                writeln!(out, "{text}")?;
+            }
+        }
+    }
+    Ok(skipped)
+}
@@ -0,0 +1,49 @@
+pub mod api;
+mod bind;
+pub mod capture;
+pub mod cli;
+pub mod config;
+mod dispatch;
+mod feed;
+mod flashback;
+mod frame;
+pub mod grpc;
+mod health;
+pub mod inspect;
+mod lifecycle;
+pub mod logging;
+pub mod metrics;
+mod mqtt;
+pub mod output;
+mod outputs;
+mod rate_limit;
+mod recorder;
+mod relay;
+pub mod replay;
+mod server;
+mod sink;
+mod state;
+mod subscription;
+pub mod websocket;
+
+pub use bind::BindOptions;
+pub use dispatch::{SINK_CLOSE_TIMEOUT, SINK_QUEUE_CAPACITY};
+pub use feed::{FeedId, FeedStats};
+pub use flashback::{FlashbackDetector, Rewind};
+pub use frame::{Frame, FrameAssembler, FrameSubscription};
+pub use health::{FeedHealth, HealthMonitor, PacketHealth};
+pub use lifecycle::{
+    EndReason, LifecycleEvent, LifecycleSubscription, Season, SessionInfo, SessionManager, Weekend,
+    SESSION_HISTORY_CAPACITY, SESSION_TIMEOUT,
+};
+pub use mqtt::{MqttOptions, MqttPublisher, QoS, MQTT_CLOSE_TIMEOUT, MQTT_QUEUE_CAPACITY};
+pub use outputs::{Outputs, RELOAD_CLOSE_TIMEOUT};
+pub use recorder::{Recorder, RECORDER_FLUSH_INTERVAL, RECORDER_QUEUE_CAPACITY};
+pub use relay::{Destination, Relay};
+pub use server::{Server, DECODE_ERROR_LOG_INTERVAL};
+pub use sink::{DebugSink, ReloadableSink, Sink, SinkError};
+pub use state::{LoggedEvent, SessionSnapshot, SessionState, Standing, EVENT_LOG_CAPACITY};
+pub use subscription::{
+    Received, ReceivedPacket, RecvError, Subscriber, Subscription, TypedSubscription,
+    SUBSCRIPTION_CAPACITY,
+};
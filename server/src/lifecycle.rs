@@ -0,0 +1,380 @@
+//! Session lifecycle: when each sender's sessions start and end, and how they link up.
+//!
+//! A session starts with the first packet of a new session UID, or with an `SSTA` event once
+//! the sender's previous session has ended. It ends with an `SEND` event, the final
+//! classification, the sender starting another session, or nothing arriving from it for
+//! [`SESSION_TIMEOUT`]. A sender that comes back after timing out starts a new session.
+//!
+//! The session packet's link identifiers, which persist across saves, group sessions into
+//! weekends and weekends into seasons, so a weekend played over several sittings still ends up
+//! together.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use serde::Serialize;
+use telemetry::Packet;
+use tokio::sync::broadcast;
+
+use crate::feed::FeedId;
+use crate::sink::{Sink, SinkError};
+use crate::subscription::RecvError;
+
+/// How long a session can go without packets before it's considered over
+pub const SESSION_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How many ended sessions are kept before dropping the oldest
+pub const SESSION_HISTORY_CAPACITY: usize = 1000;
+
+/// How many notifications a subscriber can fall behind before it starts missing them
+const LIFECYCLE_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndReason {
+    /// The game sent `SEND`
+    SessionEnded,
+    FinalClassification,
+    /// The sender started another session
+    Replaced,
+    /// Nothing arrived for [`SESSION_TIMEOUT`]
+    TimedOut,
+}
+
+impl std::fmt::Display for EndReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EndReason::SessionEnded => write!(f, "session ended"),
+            EndReason::FinalClassification => write!(f, "final classification"),
+            EndReason::Replaced => write!(f, "replaced by a new session"),
+            EndReason::TimedOut => write!(f, "timed out"),
+        }
+    }
+}
+
+/// One session of one sender
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SessionInfo {
+    pub feed: FeedId,
+    /// Unset until a session packet has arrived, like the link identifiers
+    pub session_type: Option<u8>,
+    pub track_id: Option<i8>,
+    pub session_link_identifier: Option<u32>,
+    pub weekend_link_identifier: Option<u32>,
+    pub season_link_identifier: Option<u32>,
+    /// Unix time in milliseconds
+    pub started_at: u64,
+    pub last_received: u64,
+    pub ended_at: Option<u64>,
+    pub end_reason: Option<EndReason>,
+}
+
+impl SessionInfo {
+    fn new(feed: FeedId, now: u64) -> Self {
+        Self {
+            feed,
+            session_type: None,
+            track_id: None,
+            session_link_identifier: None,
+            weekend_link_identifier: None,
+            season_link_identifier: None,
+            started_at: now,
+            last_received: now,
+            ended_at: None,
+            end_reason: None,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.end_reason.is_none()
+    }
+}
+
+/// A change to a session, in the order they happen
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "lifecycle", rename_all = "snake_case")]
+pub enum LifecycleEvent {
+    Started(SessionInfo),
+    /// The session packet arrived, with the session's type, track and link identifiers
+    Identified(SessionInfo),
+    Ended(SessionInfo),
+}
+
+/// The sessions of one weekend, oldest first
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Weekend {
+    pub weekend_link_identifier: u32,
+    pub season_link_identifier: Option<u32>,
+    pub sessions: Vec<SessionInfo>,
+}
+
+/// The weekends of one season, in the order they started
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Season {
+    pub season_link_identifier: u32,
+    pub weekends: Vec<Weekend>,
+}
+
+#[derive(Debug, Default)]
+struct Sessions {
+    /// The latest session of each sender, which may have ended
+    current: HashMap<SocketAddr, SessionInfo>,
+    /// Ended sessions, oldest first
+    ended: VecDeque<SessionInfo>,
+}
+
+impl Sessions {
+    fn end(&mut self, src: SocketAddr, reason: EndReason, now: u64) -> Option<LifecycleEvent> {
+        let session = self.current.get_mut(&src).filter(|s| s.is_active())?;
+        session.ended_at = Some(now);
+        session.end_reason = Some(reason);
+        let session = session.clone();
+        if self.ended.len() == SESSION_HISTORY_CAPACITY {
+            self.ended.pop_front();
+        }
+        self.ended.push_back(session.clone());
+        Some(LifecycleEvent::Ended(session))
+    }
+}
+
+/// Tracks when every sender's sessions start and end, see the [module docs](self)
+pub struct SessionManager {
+    sessions: Mutex<Sessions>,
+    timeout: Duration,
+    notifications: broadcast::Sender<LifecycleEvent>,
+}
+
+impl Default for SessionManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionManager {
+    pub fn new() -> Self {
+        let (notifications, _) = broadcast::channel(LIFECYCLE_CAPACITY);
+        Self {
+            sessions: Mutex::default(),
+            timeout: SESSION_TIMEOUT,
+            notifications,
+        }
+    }
+
+    /// Ends sessions after `timeout` without packets instead of [`SESSION_TIMEOUT`]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Subscribes to every lifecycle change from now on
+    pub fn subscribe(&self) -> LifecycleSubscription {
+        LifecycleSubscription {
+            receiver: self.notifications.subscribe(),
+        }
+    }
+
+    /// Every session still kept, oldest first
+    pub fn sessions(&self) -> Vec<SessionInfo> {
+        let sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let mut all: Vec<SessionInfo> = sessions
+            .ended
+            .iter()
+            .chain(sessions.current.values().filter(|s| s.is_active()))
+            .cloned()
+            .collect();
+        all.sort_by_key(|session| (session.started_at, session.feed));
+        all
+    }
+
+    /// Sessions that haven't ended yet
+    pub fn active(&self) -> Vec<SessionInfo> {
+        self.sessions()
+            .into_iter()
+            .filter(SessionInfo::is_active)
+            .collect()
+    }
+
+    /// Sessions grouped by weekend, leaving out those without a session packet yet
+    pub fn weekends(&self) -> Vec<Weekend> {
+        let mut weekends: Vec<Weekend> = Vec::new();
+        for session in self.sessions() {
+            let Some(weekend_link_identifier) = session.weekend_link_identifier else {
+                continue;
+            };
+            match weekends
+                .iter_mut()
+                .find(|w| w.weekend_link_identifier == weekend_link_identifier)
+            {
+                Some(weekend) => weekend.sessions.push(session),
+                None => weekends.push(Weekend {
+                    weekend_link_identifier,
+                    season_link_identifier: session.season_link_identifier,
+                    sessions: vec![session],
+                }),
+            }
+        }
+        weekends
+    }
+
+    /// Weekends grouped by season
+    pub fn seasons(&self) -> Vec<Season> {
+        let mut seasons: Vec<Season> = Vec::new();
+        for weekend in self.weekends() {
+            let Some(season_link_identifier) = weekend.season_link_identifier else {
+                continue;
+            };
+            match seasons
+                .iter_mut()
+                .find(|s| s.season_link_identifier == season_link_identifier)
+            {
+                Some(season) => season.weekends.push(weekend),
+                None => seasons.push(Season {
+                    season_link_identifier,
+                    weekends: vec![weekend],
+                }),
+            }
+        }
+        seasons
+    }
+
+    /// Ends every session that has gone without packets for longer than the timeout, returning
+    /// the ones ended
+    pub fn expire_idle(&self) -> Vec<SessionInfo> {
+        let now = unix_millis();
+        let timeout = self.timeout.as_millis() as u64;
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let idle: Vec<SocketAddr> = sessions
+            .current
+            .iter()
+            .filter(|(_, s)| s.is_active() && now.saturating_sub(s.last_received) > timeout)
+            .map(|(src, _)| *src)
+            .collect();
+        let mut ended = Vec::new();
+        for src in idle {
+            if let Some(event) = sessions.end(src, EndReason::TimedOut, now) {
+                if let LifecycleEvent::Ended(session) = &event {
+                    ended.push(session.clone());
+                }
+                self.notify(event);
+            }
+        }
+        ended
+    }
+
+    fn notify(&self, event: LifecycleEvent) {
+        // only fails when nobody is subscribed
+        let _ = self.notifications.send(event);
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[async_trait]
+impl Sink for SessionManager {
+    fn name(&self) -> &str {
+        "session manager"
+    }
+
+    async fn handle(
+        &self,
+        src: SocketAddr,
+        _: SystemTime,
+        _raw: &[u8],
+        packet: &Packet,
+    ) -> Result<(), SinkError> {
+        let id = FeedId::new(src, packet);
+        let now = unix_millis();
+        let code = match packet {
+            Packet::Event(event) => Some(event.event_string_code),
+            _ => None,
+        };
+
+        let mut events = Vec::new();
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let start = match sessions.current.get(&src) {
+            None => true,
+            Some(session) if session.feed != id => {
+                events.extend(sessions.end(src, EndReason::Replaced, now));
+                true
+            }
+            // a finished session only starts again when the game says so
+            Some(session) => match session.end_reason {
+                None => false,
+                Some(EndReason::TimedOut) => true,
+                Some(_) => code == Some(*b"SSTA"),
+            },
+        };
+        if start {
+            let session = SessionInfo::new(id, now);
+            events.push(LifecycleEvent::Started(session.clone()));
+            sessions.current.insert(src, session);
+        }
+
+        if let Some(session) = sessions.current.get_mut(&src).filter(|s| s.is_active()) {
+            session.last_received = now;
+            if let Packet::Session(data) = packet {
+                let identified = (
+                    Some(data.session_type),
+                    Some(data.track_id),
+                    Some(data.session_link_identifier),
+                    Some(data.weekend_link_identifier),
+                    Some(data.season_link_identifier),
+                );
+                let known = (
+                    session.session_type,
+                    session.track_id,
+                    session.session_link_identifier,
+                    session.weekend_link_identifier,
+                    session.season_link_identifier,
+                );
+                if identified != known {
+                    (
+                        session.session_type,
+                        session.track_id,
+                        session.session_link_identifier,
+                        session.weekend_link_identifier,
+                        session.season_link_identifier,
+                    ) = identified;
+                    events.push(LifecycleEvent::Identified(session.clone()));
+                }
+            }
+            let ended = match packet {
+                Packet::FinalClassification(_) => Some(EndReason::FinalClassification),
+                _ if code == Some(*b"SEND") => Some(EndReason::SessionEnded),
+                _ => None,
+            };
+            if let Some(reason) = ended {
+                events.extend(sessions.end(src, reason, now));
+            }
+        }
+        drop(sessions);
+
+        for event in events {
+            self.notify(event);
+        }
+        Ok(())
+    }
+}
+
+/// Live stream of lifecycle changes, see [`SessionManager::subscribe`]
+pub struct LifecycleSubscription {
+    receiver: broadcast::Receiver<LifecycleEvent>,
+}
+
+impl LifecycleSubscription {
+    pub async fn recv(&mut self) -> Result<LifecycleEvent, RecvError> {
+        match self.receiver.recv().await {
+            Ok(event) => Ok(event),
+            Err(broadcast::error::RecvError::Lagged(n)) => Err(RecvError::Lagged(n)),
+            Err(broadcast::error::RecvError::Closed) => Err(RecvError::Closed),
+        }
+    }
+}
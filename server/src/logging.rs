@@ -0,0 +1,387 @@
+//! Log output: `tracing` events written to stderr, one line each, as text or JSON.
+//!
+//! Text lines hold the level and message followed by the event's fields and then those of the
+//! spans it happened in, innermost first, e.g.
+//! `WARN  undecodable datagram src=192.168.1.20:52344 len=3 suppressed=0`. JSON lines carry the
+//! same for log shippers, as `{"timestamp": <unix ms>, "level", "target", "message", "fields":
+//! {...}, "spans": [{"name", ...}]}`. Messages from dependencies that use the `log` crate are
+//! written the same way, without fields.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_core::span::Current;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format {s}, expected text or json")),
+        }
+    }
+}
+
+/// Fields in the order they were given
+type Values = Vec<(&'static str, Value)>;
+
+/// Collects the fields of an event or span, with `message` kept apart
+#[derive(Default)]
+struct Fields {
+    message: Option<String>,
+    values: Values,
+}
+
+impl Fields {
+    fn insert(&mut self, field: &Field, value: Value) {
+        if field.name() == "message" {
+            self.message = Some(match value {
+                Value::String(message) => message,
+                value => value.to_string(),
+            });
+        } else {
+            self.values.push((field.name(), value));
+        }
+    }
+}
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.insert(field, Value::String(format!("{value:?}")));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, Value::String(value.to_string()));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, value.into());
+    }
+}
+
+struct Span {
+    metadata: &'static Metadata<'static>,
+    fields: Values,
+    /// The span it was created in, kept open for as long as this one is
+    parent: Option<u64>,
+    /// Handles to the span still open, including its children's
+    refs: usize,
+}
+
+thread_local! {
+    /// Spans entered on this thread, innermost last
+    static ENTERED: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Writes log lines, see the [module docs](self)
+pub struct Logger {
+    level: RwLock<LevelFilter>,
+    format: RwLock<LogFormat>,
+    writer: Mutex<Box<dyn Write + Send>>,
+    spans: Mutex<HashMap<u64, Span>>,
+    next_span: AtomicU64,
+}
+
+impl Logger {
+    /// Logs to stderr, where every status message goes so stdout only has output
+    pub fn new(level: LevelFilter, format: LogFormat) -> Self {
+        Self::with_writer(level, format, std::io::stderr())
+    }
+
+    pub fn with_writer(
+        level: LevelFilter,
+        format: LogFormat,
+        writer: impl Write + Send + 'static,
+    ) -> Self {
+        Self {
+            level: RwLock::new(level),
+            format: RwLock::new(format),
+            writer: Mutex::new(Box::new(writer)),
+            spans: Mutex::default(),
+            next_span: AtomicU64::new(1),
+        }
+    }
+
+    /// Makes this the logger for the whole process, for `tracing` and `log` alike. Fails if
+    /// another one already is
+    pub fn install(self) -> Result<Arc<Self>, String> {
+        let logger = Arc::new(self);
+        tracing::subscriber::set_global_default(logger.clone()).map_err(|e| e.to_string())?;
+        // installed once for the life of the process, so leaking it is fine
+        let bridge = Box::leak(Box::new(LogBridge(logger.clone())));
+        log::set_logger(bridge).map_err(|e| e.to_string())?;
+        log::set_max_level(log::LevelFilter::Trace);
+        Ok(logger)
+    }
+
+    pub fn level(&self) -> LevelFilter {
+        *self.level.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set_level(&self, level: LevelFilter) {
+        *self.level.write().unwrap_or_else(|e| e.into_inner()) = level;
+    }
+
+    pub fn format(&self) -> LogFormat {
+        *self.format.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set_format(&self, format: LogFormat) {
+        *self.format.write().unwrap_or_else(|e| e.into_inner()) = format;
+    }
+
+    fn write(&self, level: &Level, target: &str, message: &str, fields: &Values) {
+        // the span this thread is in and the ones it was created in, innermost first, so an
+        // event on another task still says what it was about
+        let spans: Vec<(&'static str, Values)> = {
+            let spans = self.spans.lock().unwrap_or_else(|e| e.into_inner());
+            let mut current = ENTERED.with(|entered| entered.borrow().last().copied());
+            std::iter::from_fn(|| {
+                let span = spans.get(&current?)?;
+                current = span.parent;
+                Some((span.metadata.name(), span.fields.clone()))
+            })
+            .collect()
+        };
+
+        let format = self.format();
+        let line = match format {
+            LogFormat::Text => {
+                let mut line = format!("{:<5} {message}", level.as_str());
+                let span_fields = spans.iter().flat_map(|(_, fields)| fields);
+                for (key, value) in fields.iter().chain(span_fields) {
+                    match value {
+                        Value::String(s) if !s.contains(char::is_whitespace) => {
+                            line.push_str(&format!(" {key}={s}"))
+                        }
+                        value => line.push_str(&format!(" {key}={value}")),
+                    }
+                }
+                line
+            }
+            LogFormat::Json => {
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or(0);
+                let spans: Vec<Value> = spans
+                    .into_iter()
+                    .map(|(name, fields)| {
+                        let mut span = object(fields);
+                        span.insert("name".to_string(), name.into());
+                        Value::Object(span)
+                    })
+                    .collect();
+                json!({
+                    "timestamp": timestamp,
+                    "level": level.as_str(),
+                    "target": target,
+                    "message": message,
+                    "fields": object(fields.clone()),
+                    "spans": spans,
+                })
+                .to_string()
+            }
+        };
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writeln!(writer, "{line}");
+    }
+}
+
+impl Subscriber for Logger {
+    fn register_callsite(&self, _: &'static Metadata<'static>) -> Interest {
+        // the level can change at any time, so it's checked on every event
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        // spans are kept whatever their level, so a warning still says which packet it was about
+        metadata.is_span() || self.level() >= *metadata.level()
+    }
+
+    fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+        let mut fields = Fields::default();
+        attributes.record(&mut fields);
+        let id = self.next_span.fetch_add(1, Ordering::Relaxed);
+        let parent = match attributes.parent() {
+            Some(parent) => Some(parent.into_u64()),
+            None if attributes.is_contextual() => {
+                ENTERED.with(|entered| entered.borrow().last().copied())
+            }
+            None => None,
+        };
+        let mut spans = self.spans.lock().unwrap_or_else(|e| e.into_inner());
+        let parent = parent.filter(|parent| match spans.get_mut(parent) {
+            Some(parent) => {
+                parent.refs += 1;
+                true
+            }
+            None => false,
+        });
+        let span = Span {
+            metadata: attributes.metadata(),
+            fields: fields.values,
+            parent,
+            refs: 1,
+        };
+        spans.insert(id, span);
+        Id::from_u64(id)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut fields = Fields::default();
+        values.record(&mut fields);
+        let mut spans = self.spans.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(span) = spans.get_mut(&span.into_u64()) {
+            for (key, value) in fields.values {
+                match span.fields.iter_mut().find(|(k, _)| *k == key) {
+                    Some((_, old)) => *old = value,
+                    None => span.fields.push((key, value)),
+                }
+            }
+        }
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let metadata = event.metadata();
+        let message = fields.message.unwrap_or_default();
+        self.write(
+            metadata.level(),
+            metadata.target(),
+            &message,
+            &fields.values,
+        );
+    }
+
+    fn enter(&self, span: &Id) {
+        ENTERED.with(|entered| entered.borrow_mut().push(span.into_u64()));
+    }
+
+    fn exit(&self, span: &Id) {
+        ENTERED.with(|entered| {
+            let mut entered = entered.borrow_mut();
+            if let Some(i) = entered.iter().rposition(|id| *id == span.into_u64()) {
+                entered.remove(i);
+            }
+        });
+    }
+
+    fn current_span(&self) -> Current {
+        let spans = self.spans.lock().unwrap_or_else(|e| e.into_inner());
+        let current = ENTERED.with(|entered| entered.borrow().last().copied());
+        match current.and_then(|id| Some((id, spans.get(&id)?))) {
+            Some((id, span)) => Current::new(Id::from_u64(id), span.metadata),
+            None => Current::none(),
+        }
+    }
+
+    fn clone_span(&self, span: &Id) -> Id {
+        let mut spans = self.spans.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(span) = spans.get_mut(&span.into_u64()) {
+            span.refs += 1;
+        }
+        span.clone()
+    }
+
+    fn try_close(&self, span: Id) -> bool {
+        let mut spans = self.spans.lock().unwrap_or_else(|e| e.into_inner());
+        let id = span.into_u64();
+        let Some(span) = spans.get_mut(&id) else {
+            return false;
+        };
+        span.refs -= 1;
+        if span.refs > 0 {
+            return false;
+        }
+        // closing a span lets go of its parent, which may only have been open for it
+        let mut parent = spans.remove(&id).and_then(|span| span.parent);
+        while let Some(id) = parent.take() {
+            if let Some(span) = spans.get_mut(&id) {
+                span.refs -= 1;
+                if span.refs == 0 {
+                    parent = spans.remove(&id).and_then(|span| span.parent);
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Writes `log` records through a [`Logger`]
+struct LogBridge(Arc<Logger>);
+
+impl log::Log for LogBridge {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.0.level() >= level(metadata.level())
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            let message = record.args().to_string();
+            self.0.write(
+                &level(record.level()),
+                record.target(),
+                &message,
+                &Values::new(),
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn object(values: Values) -> Map<String, Value> {
+    values
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect()
+}
+
+fn level(level: log::Level) -> Level {
+    match level {
+        log::Level::Error => Level::ERROR,
+        log::Level::Warn => Level::WARN,
+        log::Level::Info => Level::INFO,
+        log::Level::Debug => Level::DEBUG,
+        log::Level::Trace => Level::TRACE,
+    }
+}
@@ -1,43 +1,453 @@
-use telemetry::FromBytes;
-use tokio::net::{ToSocketAddrs, UdpSocket};
+use std::collections::HashMap;
+use std::fs::File;
+use std::future::{Future, IntoFuture};
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
 
-pub struct Server {
-    pub socket: UdpSocket,
+use clap::Parser;
+use futures::future::{try_join_all, BoxFuture};
+use futures::FutureExt;
+use server::cli::{
+    parse_command, Cli, Command, ExportArgs, InspectArgs, ListenArgs, Logging, RecordArgs,
+    ReplayArgs,
+};
+use server::config::Config;
+use server::grpc::TelemetryService;
+use server::inspect;
+use server::logging::Logger;
+use server::replay::{ReplayOptions, Replayer, SeekTarget, Speed};
+use server::{
+    api, metrics, websocket, BindOptions, FeedHealth, FeedId, HealthMonitor, LifecycleEvent,
+    Outputs, PacketHealth, Recorder, Server, SessionManager, SessionState,
+};
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio_stream::wrappers::TcpListenerStream;
+use tracing::level_filters::LevelFilter;
+use tracing::{error, info, warn};
+
+/// How often `listen` reports on stream health
+const HEALTH_INTERVAL: Duration = Duration::from_secs(30);
+/// How often `listen` checks for sessions that have gone quiet
+const SESSION_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// How long shutting down can take before giving up on whatever hasn't finished
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A server receiving on every address in `addrs`
+fn addr_list(binds: &[BindOptions]) -> String {
+    binds
+        .iter()
+        .map(|bind| {
+            let mut addr = bind.addr.to_string();
+            if bind.broadcast {
+                addr.push_str(" with broadcasts");
+            }
+            for group in &bind.multicast {
+                addr.push_str(&format!(" in {group}"));
+            }
+            addr
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
-impl Server {
-    pub async fn new<T: ToSocketAddrs>(addr: T) -> std::io::Result<Self> {
-        let socket = UdpSocket::bind(addr).await?;
-        Ok(Self { socket })
+/// Reloads the config at `path` on every SIGHUP. An invalid file leaves everything as it was
+#[cfg(unix)]
+async fn reload_on_hangup(
+    path: PathBuf,
+    mut current: Config,
+    outputs: Outputs,
+    server: &Server,
+    logging: Logging,
+) -> io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = signal(SignalKind::hangup())?;
+    while hangups.recv().await.is_some() {
+        let config = match Config::load(&path) {
+            Ok(config) => config,
+            Err(e) => {
+                error!(
+                    path = %path.display(),
+                    error = e.to_string().trim_end(),
+                    "keeping the current config"
+                );
+                continue;
+            }
+        };
+        for section in current.needs_restart(&config) {
+            warn!(section, "config section changed, restart to apply it");
+        }
+        logging.apply(&config);
+        current.log_level = config.log_level;
+        current.log_format = config.log_format;
+        outputs.reload(server, &mut current, &config).await;
+        info!(path = %path.display(), "reloaded config");
     }
+    Ok(())
+}
 
-    pub async fn listen(&self) -> std::io::Result<()> {
-        let mut buf = vec![0; 2048];
-        loop {
-            let (len, addr) = self.socket.recv_from(&mut buf).await?;
+/// Warns about every feed that lost, duplicated or reordered datagrams in the last interval
+async fn report_health(health: Arc<HealthMonitor>) -> io::Result<()> {
+    let mut previous: HashMap<FeedId, FeedHealth> = HashMap::new();
+    let mut interval = tokio::time::interval(HEALTH_INTERVAL);
+    loop {
+        interval.tick().await;
+        let report = health.report();
+        for feed in &report {
+            let before = previous.get(&feed.feed);
+            let since = |count: fn(&PacketHealth) -> u64, name: &str| {
+                let earlier = before
+                    .and_then(|before| before.packets.get(name))
+                    .map_or(0, count);
+                count(&feed.packets[name]).saturating_sub(earlier)
+            };
+            let (mut received, mut missing, mut duplicates, mut out_of_order) = (0, 0, 0, 0);
+            let mut lossy = Vec::new();
+            for name in feed.packets.keys() {
+                let type_received = since(|h| h.received, name);
+                let type_missing = since(|h| h.missing, name);
+                received += type_received;
+                missing += type_missing;
+                duplicates += since(|h| h.duplicates, name);
+                out_of_order += since(|h| h.out_of_order, name);
+                if type_missing > 0 {
+                    let percent =
+                        type_missing as f64 * 100.0 / (type_received + type_missing) as f64;
+                    lossy.push(format!("{name} {percent:.1}%"));
+                }
+            }
+            if missing + duplicates + out_of_order == 0 {
+                continue;
+            }
+            let percent = missing as f64 * 100.0 / (received + missing).max(1) as f64;
+            warn!(
+                feed = %feed.feed,
+                loss_percent = format!("{percent:.1}"),
+                duplicates,
+                out_of_order,
+                lossy = lossy.join(", "),
+                "stream unhealthy in the last {}s",
+                HEALTH_INTERVAL.as_secs()
+            );
+        }
+        previous = report
+            .into_iter()
+            .map(|health| (health.feed, health))
+            .collect();
+    }
+}
 
-            match telemetry::Packet::from_bytes(&buf[..len]) {
-                Ok(packet) => {
-                    println!("Received {} bytes from {}", len, addr);
-                    println!("{:#?}", packet);
+/// Logs sessions starting and ending, and ends the ones that have gone quiet
+async fn track_sessions(sessions: Arc<SessionManager>) -> io::Result<()> {
+    let mut lifecycle = sessions.subscribe();
+    let mut interval = tokio::time::interval(SESSION_CHECK_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                sessions.expire_idle();
+            }
+            event = lifecycle.recv() => match event {
+                Ok(LifecycleEvent::Started(session)) => {
+                    info!(feed = %session.feed, "session started");
                 }
-                Err(e) => {
-                    eprintln!("{e}");
+                Ok(LifecycleEvent::Identified(session)) => info!(
+                    feed = %session.feed,
+                    session_type = session.session_type.unwrap_or_default(),
+                    track_id = session.track_id.unwrap_or_default(),
+                    weekend = session.weekend_link_identifier.unwrap_or_default(),
+                    season = session.season_link_identifier.unwrap_or_default(),
+                    "session identified"
+                ),
+                Ok(LifecycleEvent::Ended(session)) => {
+                    let reason = session.end_reason.map(|r| r.to_string()).unwrap_or_default();
+                    info!(feed = %session.feed, reason, "session ended");
                 }
+                Err(_) => {}
+            },
+        }
+    }
+}
+
+/// Completes on Ctrl+C, or SIGTERM on Unix
+async fn shutdown_signal() {
+    let interrupt = async {
+        // without a handler there's nothing to wait for
+        if tokio::signal::ctrl_c().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let Ok(mut terminate) = signal(SignalKind::terminate()) else {
+            return interrupt.await;
+        };
+        tokio::select! {
+            () = interrupt => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    interrupt.await;
+}
+
+/// Stops services once Ctrl+C or SIGTERM arrives
+struct Shutdown {
+    stop: watch::Sender<bool>,
+}
+
+impl Shutdown {
+    fn new() -> Self {
+        Self {
+            stop: watch::Sender::new(false),
+        }
+    }
+
+    /// Completes once shutting down has begun, for services to stop on
+    fn stopped(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut stopping = self.stop.subscribe();
+        async move {
+            let _ = stopping.wait_for(|stopping| *stopping).await;
+        }
+    }
+
+    /// Runs `serving` and `background` until one of them fails or a signal arrives, then stops
+    /// `serving` and gives it [`SHUTDOWN_TIMEOUT`] to finish. `background` is simply dropped
+    async fn run(
+        self,
+        serving: Vec<BoxFuture<'_, io::Result<()>>>,
+        background: Vec<BoxFuture<'_, io::Result<()>>>,
+    ) -> io::Result<()> {
+        let mut serving = try_join_all(serving);
+        let background = async {
+            try_join_all(background).await?;
+            std::future::pending().await
+        };
+        tokio::select! {
+            result = &mut serving => return result.map(drop),
+            result = background => return result,
+            () = shutdown_signal() => {}
+        }
+
+        info!(timeout = ?SHUTDOWN_TIMEOUT, "shutting down");
+        self.stop.send_replace(true);
+        match tokio::time::timeout(SHUTDOWN_TIMEOUT, serving).await {
+            Ok(result) => {
+                result?;
+                info!("shut down");
             }
+            Err(_) => warn!("gave up waiting for shutdown to finish"),
         }
+        Ok(())
     }
 }
 
-#[tokio::main]
-async fn main() -> std::io::Result<()> {
-    let addr: String = std::env::args()
-        .nth(1)
-        // fallback to loopback addr.
-        .unwrap_or_else(|| "127.0.0.1:20777".to_string());
-    let server = Server::new(&addr).await?;
-
-    println!("Listening on {addr}");
-    server.listen().await?;
+async fn listen(args: ListenArgs, logging: Logging) -> io::Result<()> {
+    let path = args.config.clone();
+    let config = match &path {
+        Some(path) => Config::load(path),
+        None => {
+            let config = args.into_config();
+            config.and_then(|config| config.validate().map(|()| config))
+        }
+    }
+    .map_err(|e| match &path {
+        Some(path) => io::Error::other(format!("{}: {e}", path.display())),
+        None => io::Error::other(e),
+    })?;
+    logging.apply(&config);
+
+    let mut server = Server::with_options(&config.bind)?;
+    let outputs = Outputs::register(&mut server);
+    outputs.start(&server, &config)?;
+
+    // everything served alongside ingest, which stops if any of them fails. Serving finishes
+    // what it's doing on shutdown, while the background tasks are just dropped
+    let shutdown = Shutdown::new();
+    let mut serving: Vec<BoxFuture<io::Result<()>>> = Vec::new();
+    let mut background: Vec<BoxFuture<io::Result<()>>> = Vec::new();
+    if let Some(websocket) = &config.websocket {
+        let listener = TcpListener::bind(websocket.bind).await?;
+        let serve = websocket::serve(listener, server.subscriber(), shutdown.stopped());
+        serving.push(serve.boxed());
+        info!(
+            url = format!("ws://{}/ws", websocket.bind),
+            "serving WebSocket clients"
+        );
+    }
+    // shared by the HTTP API and gRPC
+    let state = Arc::new(SessionState::new());
+    if config.http.is_some() || config.grpc.is_some() {
+        server.add_sink(state.clone());
+    }
+    let health = Arc::new(HealthMonitor::new());
+    server.add_sink(health.clone());
+    background.push(report_health(health.clone()).boxed());
+    let sessions = Arc::new(SessionManager::new());
+    server.add_sink(sessions.clone());
+    background.push(track_sessions(sessions).boxed());
+    if let Some(http) = &config.http {
+        let listener = TcpListener::bind(http.bind).await?;
+        serving.push(
+            axum::serve(
+                listener,
+                api::router(state.clone(), health.clone())
+                    .merge(metrics::router(server.metrics(), health.clone())),
+            )
+            .with_graceful_shutdown(shutdown.stopped())
+            .into_future()
+            .boxed(),
+        );
+        info!(
+            api = format!("http://{}/api", http.bind),
+            metrics = format!("http://{}/metrics", http.bind),
+            "serving the HTTP API and metrics"
+        );
+    }
+    if let Some(grpc) = &config.grpc {
+        let listener = TcpListener::bind(grpc.bind).await?;
+        let service = TelemetryService::new(server.subscriber(), state.clone());
+        let serve = tonic::transport::Server::builder()
+            .add_service(service.into_server())
+            .serve_with_incoming_shutdown(TcpListenerStream::new(listener), shutdown.stopped());
+        serving.push(serve.map(|result| result.map_err(io::Error::other)).boxed());
+        info!(addr = %grpc.bind, "serving gRPC");
+    }
+
+    info!(addrs = addr_list(&config.bind), "listening");
+    #[cfg(unix)]
+    if let Some(path) = path {
+        background.push(reload_on_hangup(path, config, outputs, &server, logging).boxed());
+    }
+    serving.push(server.listen_until(shutdown.stopped()).boxed());
+    shutdown.run(serving, background).await
+}
+
+async fn record(args: RecordArgs) -> io::Result<()> {
+    let binds = args
+        .bind
+        .options()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let server = Server::with_options(&binds)?;
+    let mut recorder = Recorder::new(&args.dir)?;
+    if !args.packets.packets.is_empty() {
+        recorder = recorder.only(args.packets.packets.iter().cloned());
+    }
+    server.set_recorder(Some(Arc::new(recorder)));
+    info!(
+        addrs = addr_list(&binds),
+        dir = %args.dir.display(),
+        "recording packets"
+    );
+    let shutdown = Shutdown::new();
+    let serving = vec![server.listen_until(shutdown.stopped()).boxed()];
+    shutdown.run(serving, Vec::new()).await
+}
+
+async fn replay(args: ReplayArgs) -> io::Result<()> {
+    let options = ReplayOptions {
+        speed: if args.max { Speed::Max } else { args.speed },
+        looping: args.looping,
+        start: args
+            .from_time
+            .map(SeekTarget::SessionTime)
+            .or(args.from_lap.map(SeekTarget::Lap)),
+        packet_ids: args.packets.packet_ids(),
+    };
+    let mut replayer = Replayer::new(args.captures, options);
+    let handle = replayer.handle();
+    // stdin is read on its own thread so a blocked read never stalls playback
+    std::thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            match parse_command(&line) {
+                Some(command) => {
+                    if !handle.send(command) {
+                        break;
+                    }
+                }
+                None => warn!(command = line, "unknown command"),
+            }
+        }
+    });
+
+    info!(target = %args.target, "replaying");
+    let sent = replayer.run(args.target).await?;
+    info!(sent, "replay finished");
+    Ok(())
+}
+
+/// Summarises every readable file, failing if any of them couldn't be read
+fn inspect(args: InspectArgs) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    let mut failed = 0;
+    for path in &args.captures {
+        match inspect::open(path) {
+            Ok(reader) => inspect::write_summary(&mut stdout, path, reader, args.json)?,
+            Err(e) => {
+                error!(error = %e, "couldn't read capture");
+                failed += 1;
+            }
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        _ => Err(io::Error::other(format!(
+            "{failed} of {} files couldn't be read",
+            args.captures.len()
+        ))),
+    }
+}
+
+fn export(args: ExportArgs) -> io::Result<()> {
+    let mut output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    let filter = args.filter.filter();
+    let skipped = inspect::export(&mut output, &args.captures, args.format.into(), &filter)?;
+    output.flush()?;
+    if skipped > 0 {
+        warn!(skipped, "skipped datagrams that didn't decode");
+    }
     Ok(())
 }
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let logger = Logger::new(
+        cli.log_level.unwrap_or(LevelFilter::INFO),
+        cli.log_format.unwrap_or_default(),
+    )
+    .install()
+    .expect("no other logger is installed");
+    let logging = Logging {
+        logger,
+        level: cli.log_level,
+        format: cli.log_format,
+    };
+
+    let result = match cli.command {
+        Command::Listen(args) => listen(*args, logging).await,
+        Command::Record(args) => record(args).await,
+        Command::Replay(args) => replay(args).await,
+        Command::Inspect(args) => inspect(args),
+        Command::Export(args) => export(args),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        // output piped into e.g. `head` that has seen enough
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
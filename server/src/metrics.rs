@@ -0,0 +1,359 @@
+//! Prometheus metrics for keeping an eye on a running server.
+//!
+//! [`router`] serves them at `/metrics` in the Prometheus text format. Everything that grows is
+//! a counter, so rates such as bytes per second or each sender's packet rate come from
+//! `rate()` in the query, e.g. `rate(f1_source_packets_total[1m])`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use telemetry::{PacketDescriptor, PacketError};
+
+use crate::health::{FeedHealth, HealthMonitor, PacketHealth};
+
+/// Upper bounds of the sink latency buckets, in seconds
+const LATENCY_BUCKETS: [f64; 10] = [
+    0.000_05, 0.000_1, 0.000_5, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0,
+];
+
+/// Label for each [`PacketError`] variant
+fn error_kind(error: &PacketError) -> &'static str {
+    match error {
+        PacketError::SerialisationError(_) => "serialisation",
+        PacketError::InvalidPacketID(_) => "invalid_packet_id",
+        PacketError::EventCodeOutOfBounds(_) => "event_code_out_of_bounds",
+        PacketError::EventDecodeError() => "event_decode",
+        PacketError::BufferTooShort { .. } => "buffer_too_short",
+        PacketError::CarIndexOutOfBounds(_) => "car_index_out_of_bounds",
+    }
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Observations at or below each of [`LATENCY_BUCKETS`], not cumulative
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|le| seconds <= *le) {
+            self.buckets[bucket] += 1;
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    datagrams: u64,
+    bytes: u64,
+    /// By packet short name
+    decoded: BTreeMap<String, u64>,
+    /// By [`error_kind`]
+    decode_errors: BTreeMap<&'static str, u64>,
+    /// Packets and bytes by sender
+    sources: BTreeMap<SocketAddr, (u64, u64)>,
+    /// By sink name
+    sink_latency: BTreeMap<String, Histogram>,
+    /// Packets dropped because the sink's queue was full, by sink name
+    sink_dropped: BTreeMap<String, u64>,
+}
+
+/// What a [`Server`](crate::Server) has received and how quickly its sinks keep up
+#[derive(Debug)]
+pub struct Metrics {
+    counters: Mutex<Counters>,
+    subscribers: AtomicU64,
+    /// Shared with the server's subscriptions
+    lagged: Arc<AtomicU64>,
+}
+
+impl Metrics {
+    pub(crate) fn new(lagged: Arc<AtomicU64>) -> Self {
+        Self {
+            counters: Mutex::default(),
+            subscribers: AtomicU64::new(0),
+            lagged,
+        }
+    }
+
+    /// Counts a datagram, and the packet it decoded to or why it didn't
+    pub(crate) fn received(
+        &self,
+        src: SocketAddr,
+        len: usize,
+        decoded: Result<u8, &PacketError>,
+        subscribers: usize,
+    ) {
+        self.subscribers
+            .store(subscribers as u64, Ordering::Relaxed);
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        counters.datagrams += 1;
+        counters.bytes += len as u64;
+        let source = counters.sources.entry(src).or_default();
+        source.0 += 1;
+        source.1 += len as u64;
+        match decoded {
+            Ok(packet_id) => {
+                if let Some(descriptor) = PacketDescriptor::for_id(packet_id) {
+                    *counters.decoded.entry(descriptor.short_name()).or_default() += 1;
+                }
+            }
+            Err(e) => *counters.decode_errors.entry(error_kind(e)).or_default() += 1,
+        }
+    }
+
+    pub(crate) fn sink_handled(&self, sink: &str, took: Duration) {
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        if !counters.sink_latency.contains_key(sink) {
+            counters
+                .sink_latency
+                .insert(sink.to_string(), Histogram::default());
+        }
+        if let Some(histogram) = counters.sink_latency.get_mut(sink) {
+            histogram.observe(took.as_secs_f64());
+        }
+    }
+
+    pub(crate) fn sink_dropped(&self, sink: &str) {
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(dropped) = counters.sink_dropped.get_mut(sink) {
+            *dropped += 1;
+        } else {
+            counters.sink_dropped.insert(sink.to_string(), 1);
+        }
+    }
+
+    /// Everything in the Prometheus text format, with stream health from `health` if given
+    pub fn render(&self, health: Option<&HealthMonitor>) -> String {
+        let mut out = String::new();
+        let counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+
+        let total = |value: u64| vec![(Vec::new(), value.to_string())];
+        family(
+            &mut out,
+            ("f1_datagrams_received_total", "counter"),
+            "Datagrams received",
+            total(counters.datagrams),
+        );
+        family(
+            &mut out,
+            ("f1_bytes_received_total", "counter"),
+            "Bytes of datagrams received",
+            total(counters.bytes),
+        );
+        family(
+            &mut out,
+            ("f1_packets_decoded_total", "counter"),
+            "Packets decoded by type",
+            counters
+                .decoded
+                .iter()
+                .map(|(name, count)| (vec![("type", name.clone())], count.to_string()))
+                .collect(),
+        );
+        family(
+            &mut out,
+            ("f1_decode_errors_total", "counter"),
+            "Datagrams that failed to decode by error",
+            counters
+                .decode_errors
+                .iter()
+                .map(|(kind, count)| (vec![("error", kind.to_string())], count.to_string()))
+                .collect(),
+        );
+        family(
+            &mut out,
+            ("f1_source_packets_total", "counter"),
+            "Datagrams received by sender",
+            counters
+                .sources
+                .iter()
+                .map(|(src, (packets, _))| (vec![("src", src.to_string())], packets.to_string()))
+                .collect(),
+        );
+        family(
+            &mut out,
+            ("f1_source_bytes_total", "counter"),
+            "Bytes received by sender",
+            counters
+                .sources
+                .iter()
+                .map(|(src, (_, bytes))| (vec![("src", src.to_string())], bytes.to_string()))
+                .collect(),
+        );
+        family(
+            &mut out,
+            ("f1_subscribers", "gauge"),
+            "Live packet subscriptions",
+            total(self.subscribers.load(Ordering::Relaxed)),
+        );
+        family(
+            &mut out,
+            ("f1_subscriber_lagged_packets_total", "counter"),
+            "Packets dropped for subscribers that fell behind",
+            total(self.lagged.load(Ordering::Relaxed)),
+        );
+
+        family(
+            &mut out,
+            ("f1_sink_dropped_packets_total", "counter"),
+            "Packets dropped for sinks that fell behind, by sink",
+            counters
+                .sink_dropped
+                .iter()
+                .map(|(sink, count)| (vec![("sink", sink.clone())], count.to_string()))
+                .collect(),
+        );
+
+        let mut latency = Vec::new();
+        for (sink, histogram) in &counters.sink_latency {
+            let sink = || ("sink", sink.clone());
+            let mut cumulative = 0;
+            for (le, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += count;
+                let labels = vec![sink(), ("le", le.to_string())];
+                latency.push(("_bucket", labels, cumulative.to_string()));
+            }
+            let labels = vec![sink(), ("le", "+Inf".to_string())];
+            latency.push(("_bucket", labels, histogram.count.to_string()));
+            latency.push(("_sum", vec![sink()], histogram.sum.to_string()));
+            latency.push(("_count", vec![sink()], histogram.count.to_string()));
+        }
+        let name = "f1_sink_duration_seconds";
+        header(
+            &mut out,
+            name,
+            "histogram",
+            "Time each sink took to handle a packet",
+        );
+        for (suffix, labels, value) in latency {
+            sample(&mut out, &format!("{name}{suffix}"), &labels, &value);
+        }
+        drop(counters);
+
+        if let Some(health) = health {
+            render_health(&mut out, health);
+        }
+        out
+    }
+}
+
+fn render_health(out: &mut String, health: &HealthMonitor) {
+    let report = health.report();
+    let per_feed = |value: fn(&FeedHealth) -> String| {
+        report
+            .iter()
+            .map(|feed| (vec![("feed", feed.feed.to_string())], value(feed)))
+            .collect()
+    };
+    let per_type = |value: fn(&PacketHealth) -> u64| {
+        report
+            .iter()
+            .flat_map(|feed| {
+                feed.packets.iter().map(move |(packet, health)| {
+                    let labels = vec![("feed", feed.feed.to_string()), ("type", packet.clone())];
+                    (labels, value(health).to_string())
+                })
+            })
+            .collect()
+    };
+
+    family(
+        out,
+        ("f1_frames_missing_total", "counter"),
+        "Frames none of whose packets arrived, by feed",
+        per_feed(|feed| feed.missing_frames.to_string()),
+    );
+    family(
+        out,
+        ("f1_packet_loss_ratio", "gauge"),
+        "Share of per-frame packets lost over the whole feed, by feed",
+        per_feed(|feed| (feed.loss_percent / 100.0).to_string()),
+    );
+    family(
+        out,
+        ("f1_packets_missing_total", "counter"),
+        "Packets estimated lost, by feed and type",
+        per_type(|health| health.missing),
+    );
+    family(
+        out,
+        ("f1_packets_duplicated_total", "counter"),
+        "Duplicate datagrams, by feed and type",
+        per_type(|health| health.duplicates),
+    );
+    family(
+        out,
+        ("f1_packets_out_of_order_total", "counter"),
+        "Datagrams from an earlier frame than the last of their type, by feed and type",
+        per_type(|health| health.out_of_order),
+    );
+}
+
+type Labels = Vec<(&'static str, String)>;
+
+/// Writes a metric with a sample for each of `samples`
+fn family(
+    out: &mut String,
+    (name, kind): (&str, &str),
+    help: &str,
+    samples: Vec<(Labels, String)>,
+) {
+    header(out, name, kind, help);
+    for (labels, value) in samples {
+        sample(out, name, &labels, &value);
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, String)], value: &str) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(key, value)| format!("{key}=\"{}\"", escape(value)))
+            .collect();
+        let _ = write!(out, "{{{}}}", labels.join(","));
+    }
+    let _ = writeln!(out, " {value}");
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Routes serving the metrics at `/metrics`
+pub fn router(metrics: Arc<Metrics>, health: Arc<HealthMonitor>) -> Router {
+    Router::new()
+        .route("/metrics", get(scrape))
+        .with_state((metrics, health))
+}
+
+async fn scrape(
+    State((metrics, health)): State<(Arc<Metrics>, Arc<HealthMonitor>)>,
+) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics.render(Some(&health)),
+    )
+}
@@ -0,0 +1,368 @@
+//! Publishes decoded packets to an MQTT broker, one topic per car and packet type.
+//!
+//! Topics live under `<prefix>/<session_uid>/`, with the prefix defaulting to `f1`:
+//!
+//! - `car/<idx>/<type>` for every car's entry of a per-car array, e.g.
+//!   `f1/<session_uid>/car/3/telemetry`, and for packets about a single car such as
+//!   `session_history`
+//! - `<type>` for the rest of a packet, e.g. `f1/<session_uid>/session`, or the time trial
+//!   fields of the lap packet at `f1/<session_uid>/lap`
+//! - `event/<code>` for events, e.g. `f1/<session_uid>/event/FTLP`, with the session time and
+//!   event details
+//! - `rewind` when the game flashes back, with the frame and session time it went back to
+//!
+//! With [`MqttPublisher::sender_topics`] each sender gets a tree of its own, with its address
+//! as a level above the session UID, e.g. `f1/192.168.1.20:52344/<session_uid>/session`. That
+//! keeps rigs apart should two of them ever share a session UID.
+//!
+//! `<type>` is the packet's short name without a leading `car_`, so car telemetry is published
+//! to `telemetry` and car status to `status`. Payloads are JSON objects using the field names of
+//! the UDP specification.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use async_trait::async_trait;
+use rumqttc::{AsyncClient, ClientError, Event, EventLoop, Outgoing, Packet as MqttPacket};
+pub use rumqttc::{MqttOptions, QoS};
+use serde_json::{json, Map, Value};
+use telemetry::{Attributes, Packet, PacketDescriptor, PacketID, MAX_CARS};
+use tokio::task::JoinHandle;
+
+use crate::feed::FeedId;
+use crate::flashback::Rewind;
+use crate::rate_limit::RateLimit;
+use crate::sink::{Sink, SinkError};
+
+/// Publishes waiting to be sent before new ones are dropped
+pub const MQTT_QUEUE_CAPACITY: usize = 4096;
+
+/// How long closing waits by default to send what's queued and disconnect before giving up on
+/// the broker
+pub const MQTT_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait before reconnecting after the broker connection fails
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Publishes decoded packets to an MQTT broker.
+///
+/// Publishing never waits on the broker: while it's unreachable, or slower than the game, publishes
+/// beyond [`MQTT_QUEUE_CAPACITY`] are dropped and counted in [`MqttPublisher::dropped`].
+pub struct MqttPublisher {
+    client: AsyncClient,
+    /// Polls the connection until the publisher closes, or stopped when it's dropped
+    driver: Mutex<Option<JoinHandle<()>>>,
+    prefix: String,
+    /// Whether topics have a level for the sender's address
+    sender_topics: bool,
+    qos: QoS,
+    retain: bool,
+    /// Raw packet IDs to publish, every type if `None`
+    packet_ids: Option<Vec<u8>>,
+    /// Car indexes to publish, every car if `None`
+    cars: Option<Vec<usize>>,
+    /// Fields to publish per raw packet ID, every field of types without an entry
+    fields: HashMap<u8, Vec<String>>,
+    rate_limit: Mutex<RateLimit>,
+    close_timeout: Duration,
+    published: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl MqttPublisher {
+    /// Connects to the broker in `options` in the background, reconnecting whenever the
+    /// connection is lost, until the publisher is dropped. Must be called from within a Tokio
+    /// runtime.
+    pub fn new(options: MqttOptions) -> Self {
+        let (client, event_loop) = AsyncClient::new(options, MQTT_QUEUE_CAPACITY);
+        Self {
+            client,
+            driver: Mutex::new(Some(tokio::spawn(drive(event_loop)))),
+            prefix: "f1".to_string(),
+            sender_topics: false,
+            qos: QoS::AtMostOnce,
+            retain: false,
+            packet_ids: None,
+            cars: None,
+            fields: HashMap::new(),
+            rate_limit: Mutex::new(RateLimit::default()),
+            close_timeout: MQTT_CLOSE_TIMEOUT,
+            published: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Publishes under `prefix` instead of `f1`
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Publishes under `<prefix>/<src>/<session_uid>/` rather than `<prefix>/<session_uid>/`
+    pub fn sender_topics(mut self, sender_topics: bool) -> Self {
+        self.sender_topics = sender_topics;
+        self
+    }
+
+    pub fn qos(mut self, qos: QoS) -> Self {
+        self.qos = qos;
+        self
+    }
+
+    /// Asks the broker to keep the last message of every topic for new subscribers
+    pub fn retain(mut self, retain: bool) -> Self {
+        self.retain = retain;
+        self
+    }
+
+    /// How long closing waits on the broker, [`MQTT_CLOSE_TIMEOUT`] unless set
+    pub fn close_timeout(mut self, timeout: Duration) -> Self {
+        self.close_timeout = timeout;
+        self
+    }
+
+    /// Only publishes these packet types
+    pub fn only(mut self, packet_ids: impl IntoIterator<Item = PacketID>) -> Self {
+        self.packet_ids = Some(packet_ids.into_iter().map(u8::from).collect());
+        self
+    }
+
+    /// Only publishes these cars' topics. Topics that aren't about a car are still published
+    pub fn cars(mut self, cars: impl IntoIterator<Item = usize>) -> Self {
+        self.cars = Some(cars.into_iter().collect());
+        self
+    }
+
+    /// Only includes these fields in the payloads of `packet_id`, e.g. `speed` and `gear` for
+    /// car telemetry. Topics left without any of the fields aren't published
+    pub fn fields<S: Into<String>>(
+        mut self,
+        packet_id: PacketID,
+        fields: impl IntoIterator<Item = S>,
+    ) -> Self {
+        let fields = fields.into_iter().map(Into::into).collect();
+        self.fields.insert(packet_id.into(), fields);
+        self
+    }
+
+    /// Publishes at most `hz` packets per second of each packet type. Events are never limited
+    pub fn max_rate(mut self, hz: f64) -> Self {
+        self.rate_limit = Mutex::new(RateLimit::new(Some(hz)));
+        self
+    }
+
+    /// Messages handed to the client for publishing
+    pub fn published(&self) -> u64 {
+        self.published.load(Ordering::Relaxed)
+    }
+
+    /// Messages dropped because the queue to the broker was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn accepts(&self, src: SocketAddr, packet_id: u8, now: Instant) -> bool {
+        if let Some(ids) = &self.packet_ids {
+            if !ids.contains(&packet_id) {
+                return false;
+            }
+        }
+        if packet_id == u8::from(PacketID::Event) {
+            return true;
+        }
+        let mut rate_limit = self.rate_limit.lock().unwrap_or_else(|e| e.into_inner());
+        rate_limit.allow(src, packet_id, now)
+    }
+
+    fn wants_car(&self, car: usize) -> bool {
+        self.cars.as_ref().is_none_or(|cars| cars.contains(&car))
+    }
+
+    fn publish(&self, topic: String, payload: &Value) -> Result<(), SinkError> {
+        let payload = serde_json::to_vec(payload)?;
+        match self
+            .client
+            .try_publish(topic, self.qos, self.retain, payload)
+        {
+            Ok(()) => {
+                self.published.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(ClientError::TryRequest(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(e) => return Err(e.into()),
+        }
+        Ok(())
+    }
+
+    /// Topic every message about `feed` is published under
+    fn session_topic(&self, feed: &FeedId) -> String {
+        if self.sender_topics {
+            format!("{}/{}/{}", self.prefix, feed.src, feed.session_uid)
+        } else {
+            format!("{}/{}", self.prefix, feed.session_uid)
+        }
+    }
+
+    /// Splits `packet` into the messages to publish, as `(topic, payload)` pairs
+    fn messages(&self, feed: FeedId, packet: &Packet) -> Vec<(String, Value)> {
+        let session_time = packet.header().session_time;
+        let session = self.session_topic(&feed);
+
+        if let Packet::Event(event) = packet {
+            let code = String::from_utf8_lossy(&event.event_string_code).into_owned();
+            let details = event.event_details;
+            let payload = json!({
+                "session_time": session_time,
+                "details": details,
+            });
+            return vec![(format!("{session}/event/{code}"), payload)];
+        }
+
+        let Some(descriptor) = PacketDescriptor::for_id(packet.packet_id().into()) else {
+            return Vec::new();
+        };
+        let short_name = descriptor.short_name();
+        let name = short_name.strip_prefix("car_").unwrap_or(&short_name);
+        // `Packet` serialises as `{"Variant": {...}}`, we only want the inner packet
+        let mut fields = match serde_json::to_value(packet) {
+            Ok(Value::Object(variant)) => match variant.into_iter().next() {
+                Some((_, Value::Object(fields))) => fields,
+                _ => return Vec::new(),
+            },
+            _ => return Vec::new(),
+        };
+        fields.remove("header");
+
+        let mut messages = Vec::new();
+        if let Some(car) = fields.get("car_idx").and_then(Value::as_u64) {
+            let car = car as usize;
+            if self.wants_car(car) {
+                messages.push((format!("{session}/car/{car}/{name}"), Value::Object(fields)));
+            }
+        } else {
+            let per_car: Vec<String> = fields
+                .iter()
+                .filter(|(_, value)| value.as_array().is_some_and(|a| a.len() == MAX_CARS))
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in per_car {
+                let Some(Value::Array(entries)) = fields.remove(&key) else {
+                    continue;
+                };
+                for (car, entry) in entries.into_iter().enumerate() {
+                    if self.wants_car(car) {
+                        messages.push((format!("{session}/car/{car}/{name}"), entry));
+                    }
+                }
+            }
+            if !fields.is_empty() {
+                messages.push((format!("{session}/{name}"), Value::Object(fields)));
+            }
+        }
+
+        if let Some(wanted) = self.fields.get(&descriptor.id) {
+            messages.retain_mut(|(_, payload)| {
+                let Value::Object(fields) = payload else {
+                    return true;
+                };
+                let selected: Map<String, Value> = wanted
+                    .iter()
+                    .filter_map(|field| Some((field.clone(), fields.remove(field)?)))
+                    .collect();
+                *fields = selected;
+                !fields.is_empty()
+            });
+        }
+        messages
+    }
+}
+
+/// Polls the connection to the broker, which is what actually sends queued publishes, until
+/// the disconnect queued behind them has gone out
+async fn drive(mut event_loop: EventLoop) {
+    let (host, port) = event_loop.mqtt_options.broker_address();
+    let mut failing = false;
+    loop {
+        match event_loop.poll().await {
+            Ok(Event::Incoming(MqttPacket::ConnAck(_))) => {
+                failing = false;
+                tracing::info!(host, port, "connected to MQTT broker");
+            }
+            Ok(Event::Outgoing(Outgoing::Disconnect)) => return,
+            Ok(_) => {}
+            Err(e) => {
+                // report the first failure rather than every reconnect attempt
+                if !failing {
+                    tracing::warn!(host, port, error = %e, "MQTT connection failed");
+                    failing = true;
+                }
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        }
+    }
+}
+
+impl Drop for MqttPublisher {
+    fn drop(&mut self) {
+        let driver = self.driver.get_mut().unwrap_or_else(|e| e.into_inner());
+        if let Some(driver) = driver.take() {
+            driver.abort();
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for MqttPublisher {
+    fn name(&self) -> &str {
+        "mqtt"
+    }
+
+    async fn handle(
+        &self,
+        src: SocketAddr,
+        _: SystemTime,
+        _raw: &[u8],
+        packet: &Packet,
+    ) -> Result<(), SinkError> {
+        if !self.accepts(src, packet.packet_id().into(), Instant::now()) {
+            return Ok(());
+        }
+
+        for (topic, payload) in self.messages(FeedId::new(src, packet), packet) {
+            self.publish(topic, &payload)?;
+        }
+        Ok(())
+    }
+
+    async fn rewind(&self, rewind: &Rewind) -> Result<(), SinkError> {
+        let topic = format!("{}/rewind", self.session_topic(&rewind.feed));
+        self.publish(topic, &serde_json::to_value(rewind)?)
+    }
+
+    /// Sends whatever is still queued, then disconnects. Gives up on a broker that takes longer
+    /// than the [close timeout](MqttPublisher::close_timeout), dropping what it hasn't taken
+    async fn close(&self) -> Result<(), SinkError> {
+        let driver = self.driver.lock().unwrap_or_else(|e| e.into_inner()).take();
+        let Some(mut driver) = driver else {
+            return Ok(());
+        };
+        let closed = tokio::time::timeout(self.close_timeout, async {
+            self.client.disconnect().await?;
+            (&mut driver).await?;
+            Ok::<_, SinkError>(())
+        })
+        .await;
+        match closed {
+            Ok(result) => result,
+            Err(_) => {
+                driver.abort();
+                let timeout = self.close_timeout;
+                Err(format!("broker didn't take what was queued within {timeout:?}").into())
+            }
+        }
+    }
+}
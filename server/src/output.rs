@@ -0,0 +1,246 @@
+//! Turns decoded packets into text, for printing as they arrive or exporting from captures.
+
+use std::io::Write;
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use telemetry::{Attributes, Packet, PacketDescriptor, PacketID, MAX_CARS};
+
+use crate::feed::FeedId;
+use crate::sink::{Sink, SinkError};
+
+/// Which packets, and which cars within them, a consumer wants
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PacketFilter {
+    /// Raw packet IDs, every type if empty
+    pub packet_ids: Vec<u8>,
+    /// Car indexes, every car if empty
+    pub cars: Vec<usize>,
+    /// Senders, every sender if empty
+    pub sources: Vec<SocketAddr>,
+}
+
+impl PacketFilter {
+    pub fn new(
+        packet_ids: impl IntoIterator<Item = PacketID>,
+        cars: impl IntoIterator<Item = usize>,
+    ) -> Self {
+        Self {
+            packet_ids: packet_ids.into_iter().map(u8::from).collect(),
+            ..Self::default()
+        }
+        .cars(cars)
+    }
+
+    /// Only keeps these cars, in the order listed. A car listed twice is only kept once
+    pub fn cars(mut self, cars: impl IntoIterator<Item = usize>) -> Self {
+        self.cars.clear();
+        for car in cars {
+            if !self.cars.contains(&car) {
+                self.cars.push(car);
+            }
+        }
+        self
+    }
+
+    /// Only accepts packets sent from these addresses
+    pub fn sources(mut self, sources: impl IntoIterator<Item = SocketAddr>) -> Self {
+        self.sources = sources.into_iter().collect();
+        self
+    }
+
+    pub fn accepts(&self, packet_id: u8) -> bool {
+        self.packet_ids.is_empty() || self.packet_ids.contains(&packet_id)
+    }
+
+    pub fn accepts_source(&self, src: SocketAddr) -> bool {
+        self.sources.is_empty() || self.sources.contains(&src)
+    }
+
+    /// `packet` as JSON with every per-car array narrowed down to the chosen cars, in the order
+    /// they were listed. `None` if the packet is about a single car that wasn't chosen, or is an
+    /// event that only involves cars that weren't
+    pub fn to_json(&self, packet: &Packet) -> Option<Value> {
+        // `Packet` serialises as `{"Variant": {...}}`, we only want the inner packet
+        let mut packet = match serde_json::to_value(packet).ok()? {
+            Value::Object(variant) => variant.into_iter().next()?.1,
+            _ => return None,
+        };
+        if self.cars.is_empty() {
+            return Some(packet);
+        }
+
+        let Value::Object(fields) = &mut packet else {
+            return Some(packet);
+        };
+        if let Some(car) = fields.get("car_idx").and_then(Value::as_u64) {
+            return self.cars.contains(&(car as usize)).then_some(packet);
+        }
+        if let Some(details) = fields.get("event_details") {
+            let involved = involved_cars(details);
+            if !involved.is_empty() && !involved.iter().any(|car| self.cars.contains(car)) {
+                return None;
+            }
+        }
+        for field in fields.values_mut() {
+            if let Value::Array(entries) = field {
+                if entries.len() == MAX_CARS {
+                    *entries = self.cars.iter().map(|car| entries[*car].take()).collect();
+                }
+            }
+        }
+        Some(packet)
+    }
+
+    /// The JSON message for a packet, `{"type", "src", "feed", "received_at", "packet"}` with
+    /// `cars` added when filtering by car
+    pub fn message(
+        &self,
+        src: SocketAddr,
+        received_at: SystemTime,
+        packet: &Packet,
+    ) -> Option<Value> {
+        let descriptor = PacketDescriptor::for_id(packet.packet_id().into())?;
+        if !self.accepts(descriptor.id) || !self.accepts_source(src) {
+            return None;
+        }
+        let mut message = json!({
+            "type": descriptor.short_name(),
+            "src": src.to_string(),
+            "feed": FeedId::new(src, packet),
+            "received_at": unix_millis(received_at),
+            "packet": self.to_json(packet)?,
+        });
+        if !self.cars.is_empty() {
+            message["cars"] = json!(self.cars);
+        }
+        Some(message)
+    }
+}
+
+/// The cars an event's details are about, e.g. both cars of an overtake. Other indexes, like
+/// the fastest car in a speed trap event, only describe the field
+fn involved_cars(details: &Value) -> Vec<usize> {
+    // `{"Penalty": {...}}`, or just `"SessionStarted"` for events without details
+    let Some(Value::Object(details)) = details
+        .as_object()
+        .and_then(|variant| variant.values().next())
+    else {
+        return Vec::new();
+    };
+    details
+        .iter()
+        .filter(|(name, _)| *name == "vehicle_idx" || name.ends_with("_vehicle_idx"))
+        .filter_map(|(_, idx)| idx.as_u64())
+        .map(|idx| idx as usize)
+        .collect()
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// Indented JSON of every packet under a line saying which feed it came from
+    #[default]
+    Pretty,
+    /// One JSON message per line
+    Json,
+    /// One short line per packet with its header and, for events, the event code
+    Compact,
+}
+
+impl Format {
+    /// Formats a packet without a trailing newline, or returns `None` if `filter` rejects it
+    pub fn format(
+        self,
+        filter: &PacketFilter,
+        src: SocketAddr,
+        received_at: SystemTime,
+        packet: &Packet,
+    ) -> Option<String> {
+        match self {
+            Format::Pretty => {
+                let message = filter.message(src, received_at, packet)?;
+                let packet = serde_json::to_string_pretty(&message["packet"]).ok()?;
+                Some(format!(
+                    "{} from {}\n{packet}",
+                    message["type"].as_str()?,
+                    message["feed"].as_str()?
+                ))
+            }
+            Format::Json => Some(filter.message(src, received_at, packet)?.to_string()),
+            Format::Compact => {
+                let descriptor = PacketDescriptor::for_id(packet.packet_id().into())?;
+                if !filter.accepts(descriptor.id)
+                    || !filter.accepts_source(src)
+                    || filter.to_json(packet).is_none()
+                {
+                    return None;
+                }
+                let header = packet.header();
+                let (session_uid, session_time) = (header.session_uid, header.session_time);
+                let frame = header.frame_identifier;
+                let millis = unix_millis(received_at) % 86_400_000;
+                let mut line = format!(
+                    "{:02}:{:02}:{:02}.{:03} {src} {session_uid:016x} {session_time:>9.3} {frame:>7} {}",
+                    millis / 3_600_000,
+                    millis / 60_000 % 60,
+                    millis / 1000 % 60,
+                    millis % 1000,
+                    descriptor.short_name(),
+                );
+                if let Packet::Event(event) = packet {
+                    line.push(' ');
+                    line.push_str(&String::from_utf8_lossy(&event.event_string_code));
+                }
+                Some(line)
+            }
+        }
+    }
+}
+
+/// Prints every packet `filter` accepts to stdout
+pub struct Printer {
+    format: Format,
+    filter: PacketFilter,
+}
+
+impl Printer {
+    pub fn new(format: Format, filter: PacketFilter) -> Self {
+        Self { format, filter }
+    }
+}
+
+#[async_trait]
+impl Sink for Printer {
+    fn name(&self) -> &str {
+        "printer"
+    }
+
+    async fn handle(
+        &self,
+        src: SocketAddr,
+        received_at: SystemTime,
+        _raw: &[u8],
+        packet: &Packet,
+    ) -> Result<(), SinkError> {
+        let Some(text) = self.format.format(&self.filter, src, received_at, packet) else {
+            return Ok(());
+        };
+        writeln!(std::io::stdout().lock(), "{text}")?;
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<(), SinkError> {
+        std::io::stdout().flush()?;
+        Ok(())
+    }
+}
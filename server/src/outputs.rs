@@ -0,0 +1,176 @@
+//! The outputs `server listen` sends packets to besides its services: printing, recording,
+//! relaying and publishing to MQTT, started from a [`Config`] and restarted when it's reloaded.
+
+use std::fmt::Display;
+use std::future::Future;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tracing::{error, info, warn};
+
+use crate::config::{Config, MqttConfig, OutputConfig, RecorderConfig, RelayConfig};
+use crate::mqtt::{MqttOptions, MqttPublisher};
+use crate::output::Printer;
+use crate::recorder::Recorder;
+use crate::relay::{Destination, Relay};
+use crate::server::Server;
+use crate::sink::{ReloadableSink, Sink};
+
+/// How long an output replaced by a reload gets to finish what it's writing
+pub const RELOAD_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn printer(config: &OutputConfig) -> Option<Arc<dyn Sink>> {
+    config
+        .enabled
+        .then(|| Arc::new(Printer::new(config.format, config.filter())) as Arc<dyn Sink>)
+}
+
+fn recorder(config: Option<&RecorderConfig>) -> io::Result<Option<Arc<Recorder>>> {
+    let Some(config) = config else {
+        return Ok(None);
+    };
+    let mut recorder = Recorder::new(&config.dir)?;
+    if !config.packets.is_empty() {
+        recorder = recorder.only(config.packets.iter().cloned());
+    }
+    info!(dir = %config.dir.display(), "recording");
+    Ok(Some(Arc::new(recorder)))
+}
+
+fn relay(configs: &[RelayConfig]) -> io::Result<Option<Arc<Relay>>> {
+    if configs.is_empty() {
+        return Ok(None);
+    }
+    let destinations = configs
+        .iter()
+        .map(|relay| {
+            let mut destination = Destination::new(relay.addr);
+            if !relay.packets.is_empty() {
+                destination = destination.only(relay.packets.iter().cloned());
+            }
+            if let Some(hz) = relay.max_rate {
+                destination = destination.max_rate(hz);
+            }
+            destination
+        })
+        .collect();
+    let relay = Relay::new(destinations)?;
+    for config in configs {
+        info!(destination = %config.addr, "relaying");
+    }
+    Ok(Some(Arc::new(relay)))
+}
+
+fn mqtt(config: Option<&MqttConfig>) -> Option<Arc<dyn Sink>> {
+    let config = config?;
+    let client_id = config
+        .client_id
+        .clone()
+        .unwrap_or_else(|| format!("f1-telemetry-{}", std::process::id()));
+    let options = MqttOptions::new(client_id, &config.host, config.port);
+    let mut publisher = MqttPublisher::new(options)
+        .prefix(&config.prefix)
+        .sender_topics(config.sender_topics)
+        .qos(config.qos)
+        .retain(config.retain);
+    if !config.packets.is_empty() {
+        publisher = publisher.only(config.packets.iter().cloned());
+    }
+    if !config.cars.is_empty() {
+        publisher = publisher.cars(config.cars.iter().copied());
+    }
+    for (packet_id, fields) in &config.fields {
+        publisher = publisher.fields(packet_id.clone(), fields);
+    }
+    if let Some(hz) = config.max_rate {
+        publisher = publisher.max_rate(hz);
+    }
+    info!(host = %config.host, port = config.port, "publishing to MQTT broker");
+    Some(Arc::new(publisher))
+}
+
+/// The sinks a reload can change. They're registered with the server up front and swapped out
+/// while it runs, as are the recorder and relay
+pub struct Outputs {
+    printer: Arc<ReloadableSink>,
+    mqtt: Arc<ReloadableSink>,
+}
+
+impl Outputs {
+    /// Adds every output to `server`, each doing nothing until [`start`](Self::start)
+    pub fn register(server: &mut Server) -> Self {
+        let outputs = Self {
+            printer: Arc::new(ReloadableSink::new("printer")),
+            mqtt: Arc::new(ReloadableSink::new("mqtt")),
+        };
+        for sink in [&outputs.printer, &outputs.mqtt] {
+            server.add_sink(sink.clone());
+        }
+        outputs
+    }
+
+    /// Starts the outputs `config` turns on
+    pub fn start(&self, server: &Server, config: &Config) -> io::Result<()> {
+        self.printer.replace(printer(&config.output));
+        server.set_recorder(recorder(config.recorder.as_ref())?);
+        server.set_relay(relay(&config.relay)?);
+        self.mqtt.replace(mqtt(config.mqtt.as_ref()));
+        Ok(())
+    }
+
+    /// Restarts the outputs whose sections differ between `current` and `new`, copying those
+    /// sections into `current`. An output that fails to restart keeps running as it was
+    pub async fn reload(&self, server: &Server, current: &mut Config, new: &Config) {
+        if current.output != new.output {
+            close_sink(self.printer.replace(printer(&new.output))).await;
+            current.output = new.output.clone();
+        }
+        if current.recorder != new.recorder {
+            match recorder(new.recorder.as_ref()) {
+                Ok(recorder) => {
+                    if let Some(replaced) = server.set_recorder(recorder) {
+                        close_replaced("recorder", replaced.close()).await;
+                    }
+                    current.recorder = new.recorder.clone();
+                }
+                Err(e) => error!(error = %e, "couldn't restart the recorder, keeping the old one"),
+            }
+        }
+        if current.relay != new.relay {
+            match relay(&new.relay) {
+                Ok(relay) => {
+                    if let Some(replaced) = server.set_relay(relay) {
+                        close_replaced("relay", replaced.close()).await;
+                    }
+                    current.relay = new.relay.clone();
+                }
+                Err(e) => error!(error = %e, "couldn't restart the relay, keeping the old one"),
+            }
+        }
+        if current.mqtt != new.mqtt {
+            close_sink(self.mqtt.replace(mqtt(new.mqtt.as_ref()))).await;
+            current.mqtt = new.mqtt.clone();
+        }
+    }
+}
+
+/// Closes a sink a reload replaced
+async fn close_sink(sink: Option<Arc<dyn Sink>>) {
+    if let Some(sink) = sink {
+        close_replaced(sink.name(), sink.close()).await;
+    }
+}
+
+/// Waits for an output a reload replaced to close, giving up after [`RELOAD_CLOSE_TIMEOUT`]
+async fn close_replaced<E: Display>(name: &str, close: impl Future<Output = Result<(), E>>) {
+    match tokio::time::timeout(RELOAD_CLOSE_TIMEOUT, close).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => error!(sink = name, error = %e, "replaced sink failed to close"),
+        Err(_) => warn!(
+            sink = name,
+            timeout = ?RELOAD_CLOSE_TIMEOUT,
+            "gave up waiting for replaced sink to close"
+        ),
+    }
+}
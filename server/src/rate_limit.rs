@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// Lets through at most one packet of each type from each sender per interval, so a limited
+/// consumer still sees every type it's interested in from every rig
+#[derive(Debug, Default)]
+pub(crate) struct RateLimit {
+    min_interval: Option<Duration>,
+    last_sent: HashMap<(SocketAddr, u8), Instant>,
+}
+
+impl RateLimit {
+    /// `hz` packets per second of each type and sender, unlimited if `None` or not positive
+    pub(crate) fn new(hz: Option<f64>) -> Self {
+        Self {
+            min_interval: hz
+                .filter(|hz| *hz > 0.0)
+                .map(|hz| Duration::from_secs_f64(1.0 / hz)),
+            last_sent: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn allow(&mut self, src: SocketAddr, packet_id: u8, now: Instant) -> bool {
+        let Some(min_interval) = self.min_interval else {
+            return true;
+        };
+        match self.last_sent.get(&(src, packet_id)) {
+            Some(last) if now.duration_since(*last) < min_interval => false,
+            _ => {
+                self.last_sent.insert((src, packet_id), now);
+                true
+            }
+        }
+    }
+}
+
+/// Lets through one log message per sender per interval, so a sender stuck sending garbage
+/// can't flood the logs
+#[derive(Debug)]
+pub(crate) struct LogLimit {
+    interval: Duration,
+    /// When each sender was last logged, and how many of its messages were held back since
+    senders: HashMap<SocketAddr, (Instant, u64)>,
+}
+
+impl LogLimit {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            senders: HashMap::new(),
+        }
+    }
+
+    /// Whether to log a message from `src`, with how many were held back since the last one
+    pub(crate) fn allow(&mut self, src: SocketAddr, now: Instant) -> Option<u64> {
+        match self.senders.get_mut(&src) {
+            Some((last, suppressed)) if now.duration_since(*last) < self.interval => {
+                *suppressed += 1;
+                None
+            }
+            _ => {
+                let (_, suppressed) = self.senders.insert(src, (now, 0)).unwrap_or((now, 0));
+                Some(suppressed)
+            }
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use telemetry::{PacketHeader, PacketID};
+use tracing::{error, info, warn};
+
+use crate::capture::{CaptureHeader, CaptureRecord, CaptureWriter, EXTENSION};
+use crate::feed::FeedId;
+use crate::rate_limit::LogLimit;
+
+/// How often each file is flushed while packets keep arriving, and how soon after they stop. An
+/// abrupt stop loses the records since the last flush, rather than every datagram costing a
+/// write to disk
+pub const RECORDER_FLUSH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Datagrams waiting to be written before further ones are dropped
+pub const RECORDER_QUEUE_CAPACITY: usize = 4096;
+
+/// How often write errors for one sender are logged; the rest are only counted
+const WRITE_ERROR_LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+struct Recording {
+    path: PathBuf,
+    writer: CaptureWriter<BufWriter<File>>,
+    flushed: Instant,
+}
+
+/// Writes every datagram to a capture file in `dir`, one file per feed. A sender starting a new
+/// session finishes the file of its previous one, and the rest are finished when the recorder
+/// is closed or dropped.
+///
+/// Registered with [`Server::set_recorder`](crate::Server::set_recorder), it's handed datagrams
+/// as they're received, before decoding, so files also keep the ones this server can't decode
+/// and every record has the time the datagram came off the socket. Those without a header
+/// naming their session go in their sender's current file.
+///
+/// Files are written on a thread of the recorder's own, so a slow disk holds up neither the
+/// runtime nor receiving. Once [`RECORDER_QUEUE_CAPACITY`] datagrams are waiting for it, further
+/// ones are dropped and counted.
+pub struct Recorder {
+    /// Raw packet IDs to record, every type if `None`
+    packet_ids: Option<Vec<u8>>,
+    files: Arc<Files>,
+    /// `None` once closed, which lets the writer finish
+    queue: Mutex<Option<mpsc::SyncSender<CaptureRecord>>>,
+    writer: Mutex<Option<JoinHandle<std::io::Result<()>>>>,
+    dropped: AtomicU64,
+    /// Whether the last datagram was dropped, so falling behind is reported once rather than for
+    /// every datagram
+    overflowing: AtomicBool,
+}
+
+/// The files being written, shared with the writer thread
+struct Files {
+    dir: PathBuf,
+    recordings: Mutex<HashMap<FeedId, Recording>>,
+}
+
+impl Recorder {
+    pub fn new(dir: impl AsRef<Path>) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        let files = Arc::new(Files {
+            dir: dir.as_ref().to_path_buf(),
+            recordings: Mutex::new(HashMap::new()),
+        });
+        let (queue, queued) = mpsc::sync_channel(RECORDER_QUEUE_CAPACITY);
+        let writer = {
+            let files = files.clone();
+            std::thread::Builder::new()
+                .name("recorder".to_string())
+                .spawn(move || files.write_all(queued))?
+        };
+        Ok(Self {
+            packet_ids: None,
+            files,
+            queue: Mutex::new(Some(queue)),
+            writer: Mutex::new(Some(writer)),
+            dropped: AtomicU64::new(0),
+            overflowing: AtomicBool::new(false),
+        })
+    }
+
+    /// Only records these packet types. Datagrams without a header we know are then skipped too
+    pub fn only(mut self, packet_ids: impl IntoIterator<Item = PacketID>) -> Self {
+        self.packet_ids = Some(packet_ids.into_iter().map(u8::from).collect());
+        self
+    }
+
+    /// Queues `raw` to be written, without waiting for the disk
+    pub fn record(&self, src: SocketAddr, raw: &[u8], received_at: SystemTime) {
+        if let Some(ids) = &self.packet_ids {
+            let packet_id = PacketHeader::peek(raw).ok().map(|header| header.packet_id);
+            if !packet_id.is_some_and(|id| ids.contains(&id)) {
+                return;
+            }
+        }
+        let queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(queue) = &*queue else {
+            return;
+        };
+        let record = CaptureRecord {
+            received_at,
+            src,
+            data: raw.to_vec(),
+        };
+        match queue.try_send(record) {
+            Ok(()) => {
+                if self.overflowing.swap(false, Ordering::Relaxed) {
+                    info!("recorder caught up");
+                }
+            }
+            Err(TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                if !self.overflowing.swap(true, Ordering::Relaxed) {
+                    warn!(
+                        capacity = RECORDER_QUEUE_CAPACITY,
+                        "recorder fell behind, dropping datagrams"
+                    );
+                }
+            }
+            // the writer is gone, which it has already been reported for
+            Err(TrySendError::Disconnected(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Datagrams dropped because the writer had fallen behind
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Paths of the files currently being written to, one per feed
+    pub fn current_paths(&self) -> Vec<PathBuf> {
+        let recordings = self
+            .files
+            .recordings
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let mut paths: Vec<PathBuf> = recordings
+            .values()
+            .map(|recording| recording.path.clone())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    /// Stops taking datagrams, then waits for the writer to get through the ones queued and
+    /// finish every file. Closing again does nothing
+    pub async fn close(&self) -> std::io::Result<()> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner()).take();
+        let writer = self.writer.lock().unwrap_or_else(|e| e.into_inner()).take();
+        let Some(writer) = writer else {
+            return Ok(());
+        };
+        tokio::task::spawn_blocking(move || writer.join())
+            .await?
+            .map_err(|_| std::io::Error::other("recorder thread panicked"))?
+    }
+}
+
+impl Files {
+    /// Writes everything queued until the recorder is closed or dropped, then finishes every
+    /// file
+    fn write_all(&self, queued: mpsc::Receiver<CaptureRecord>) -> std::io::Result<()> {
+        let mut errors = LogLimit::new(WRITE_ERROR_LOG_INTERVAL);
+        loop {
+            match queued.recv_timeout(RECORDER_FLUSH_INTERVAL) {
+                Ok(record) => {
+                    if let Err(e) = self.write(&record) {
+                        if let Some(suppressed) = errors.allow(record.src, Instant::now()) {
+                            error!(src = %record.src, error = %e, suppressed, "couldn't record datagram");
+                        }
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => self.flush_idle()?,
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        self.finish_all()
+    }
+
+    fn start(&self, packet_format: u16, feed: FeedId) -> std::io::Result<Recording> {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        // e.g. `192.168.1.20-52344`, without the characters file systems object to
+        let sender: String = feed
+            .src
+            .to_string()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        let path = self.dir.join(format!(
+            "{started}-{:016x}-{}.{EXTENSION}",
+            feed.session_uid,
+            sender.trim_matches('-')
+        ));
+
+        let file = BufWriter::new(File::create(&path)?);
+        let header = CaptureHeader::new(packet_format, feed.session_uid);
+        let writer = CaptureWriter::new(file, &header)?;
+        Ok(Recording {
+            path,
+            writer,
+            flushed: Instant::now(),
+        })
+    }
+
+    /// Appends `record` to the file for its feed, first finishing the file of any earlier
+    /// session from the same sender
+    fn write(&self, record: &CaptureRecord) -> std::io::Result<()> {
+        // a poisoned lock only means another write panicked, the files themselves are still usable
+        let mut recordings = self.recordings.lock().unwrap_or_else(|e| e.into_inner());
+        let header = PacketHeader::peek(&record.data).ok();
+        let feed = match header {
+            Some(header) => FeedId {
+                src: record.src,
+                session_uid: header.session_uid,
+            },
+            // no session to go by, so the sender's current one, or session 0 if it has none
+            None => recordings
+                .keys()
+                .find(|feed| feed.src == record.src)
+                .copied()
+                .unwrap_or(FeedId {
+                    src: record.src,
+                    session_uid: 0,
+                }),
+        };
+        if !recordings.contains_key(&feed) {
+            let previous: Vec<FeedId> = recordings
+                .keys()
+                .filter(|recording| recording.src == feed.src)
+                .copied()
+                .collect();
+            for previous in previous {
+                if let Some(recording) = recordings.remove(&previous) {
+                    recording.writer.finish()?;
+                }
+            }
+            let packet_format = header.map_or(0, |header| header.packet_format);
+            let recording = self.start(packet_format, feed)?;
+            recordings.insert(feed, recording);
+        }
+
+        // only `None` if starting the recording failed, which has already returned
+        if let Some(recording) = recordings.get_mut(&feed) {
+            recording.writer.write_record(record)?;
+            if recording.flushed.elapsed() >= RECORDER_FLUSH_INTERVAL {
+                recording.writer.flush()?;
+                recording.flushed = Instant::now();
+            }
+        }
+        Ok(())
+    }
+
+    /// Flushes the files written to since they were last flushed, once datagrams stop coming
+    fn flush_idle(&self) -> std::io::Result<()> {
+        let mut recordings = self.recordings.lock().unwrap_or_else(|e| e.into_inner());
+        for recording in recordings.values_mut() {
+            recording.writer.flush()?;
+            recording.flushed = Instant::now();
+        }
+        Ok(())
+    }
+
+    /// Finishes every file, carrying on past ones that fail and returning the first error
+    fn finish_all(&self) -> std::io::Result<()> {
+        let recordings: Vec<Recording> = self
+            .recordings
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .drain()
+            .map(|(_, recording)| recording)
+            .collect();
+        let mut result = Ok(());
+        for recording in recordings {
+            if let Err(e) = recording.writer.finish() {
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+}
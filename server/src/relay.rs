@@ -0,0 +1,162 @@
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use telemetry::{PacketHeader, PacketID};
+
+use crate::rate_limit::{LogLimit, RateLimit};
+
+/// How often failures to send to one destination are logged; the rest are only counted in
+/// [`Destination::skipped`]
+const SEND_ERROR_LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A downstream address the relay forwards datagrams to
+#[derive(Debug)]
+pub struct Destination {
+    pub addr: SocketAddr,
+    /// Raw packet IDs to forward, every type if `None`
+    packet_ids: Option<Vec<u8>>,
+    rate_limit: Mutex<RateLimit>,
+    send_errors: Mutex<LogLimit>,
+    forwarded: AtomicU64,
+    skipped: AtomicU64,
+}
+
+impl Destination {
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            packet_ids: None,
+            rate_limit: Mutex::new(RateLimit::default()),
+            send_errors: Mutex::new(LogLimit::new(SEND_ERROR_LOG_INTERVAL)),
+            forwarded: AtomicU64::new(0),
+            skipped: AtomicU64::new(0),
+        }
+    }
+
+    /// Only forwards these packet types
+    pub fn only(mut self, packet_ids: impl IntoIterator<Item = PacketID>) -> Self {
+        self.packet_ids = Some(packet_ids.into_iter().map(u8::from).collect());
+        self
+    }
+
+    /// Forwards at most `hz` packets per second of each packet type
+    pub fn max_rate(mut self, hz: f64) -> Self {
+        self.rate_limit = Mutex::new(RateLimit::new(Some(hz)));
+        self
+    }
+
+    /// Datagrams forwarded to this destination
+    pub fn forwarded(&self) -> u64 {
+        self.forwarded.load(Ordering::Relaxed)
+    }
+
+    /// Datagrams not forwarded to this destination, by its filter or rate limit or because
+    /// sending failed
+    pub fn skipped(&self) -> u64 {
+        self.skipped.load(Ordering::Relaxed)
+    }
+
+    /// `packet_id` is `None` for datagrams without a header we know, which only pass without a
+    /// packet filter and share a rate limit
+    fn accepts(&self, src: SocketAddr, packet_id: Option<u8>, now: Instant) -> bool {
+        if let Some(ids) = &self.packet_ids {
+            if !packet_id.is_some_and(|id| ids.contains(&id)) {
+                return false;
+            }
+        }
+        let mut rate_limit = self.rate_limit.lock().unwrap_or_else(|e| e.into_inner());
+        rate_limit.allow(src, packet_id.unwrap_or(u8::MAX), now)
+    }
+}
+
+/// Forwards every datagram unchanged to a list of downstream addresses.
+///
+/// Registered with [`Server::set_relay`](crate::Server::set_relay), it's handed datagrams as
+/// they're received, before decoding, so downstream tools also get the ones this server can't
+/// decode. Sending never waits: a datagram that doesn't fit in the socket's send buffer is
+/// dropped. Datagrams from every sender leave from the relay's one socket, so downstream tools
+/// can only tell rigs apart by session UID.
+pub struct Relay {
+    /// `None` once closed
+    socket: RwLock<Option<UdpSocket>>,
+    destinations: Vec<Destination>,
+}
+
+impl Relay {
+    pub fn new(destinations: Vec<Destination>) -> std::io::Result<Self> {
+        // a v6 socket can reach v4 destinations through mapped addresses, the reverse can't
+        let bind: SocketAddr = if destinations.iter().any(|d| d.addr.is_ipv6()) {
+            ([0u16; 8], 0).into()
+        } else {
+            ([0, 0, 0, 0], 0).into()
+        };
+        let socket = UdpSocket::bind(bind)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket: RwLock::new(Some(socket)),
+            destinations,
+        })
+    }
+
+    pub fn destinations(&self) -> &[Destination] {
+        &self.destinations
+    }
+
+    /// Sends `raw` on to every destination whose filter and rate limit let it through, or
+    /// nowhere once the relay is closed
+    pub fn forward(&self, src: SocketAddr, raw: &[u8]) {
+        let socket = self.socket.read().unwrap_or_else(|e| e.into_inner());
+        let Some(socket) = &*socket else {
+            return;
+        };
+        let packet_id = PacketHeader::peek(raw).ok().map(|header| header.packet_id);
+        let now = Instant::now();
+
+        for destination in &self.destinations {
+            if !destination.accepts(src, packet_id, now) {
+                destination.skipped.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            let addr = match (destination.addr, socket.local_addr()) {
+                (SocketAddr::V4(v4), Ok(SocketAddr::V6(_))) => {
+                    SocketAddr::new(v4.ip().to_ipv6_mapped().into(), v4.port())
+                }
+                (addr, _) => addr,
+            };
+            // one unreachable destination shouldn't stop the others
+            match socket.send_to(raw, addr) {
+                Ok(_) => {
+                    destination.forwarded.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(e) => {
+                    destination.skipped.fetch_add(1, Ordering::Relaxed);
+                    let allowed = destination
+                        .send_errors
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .allow(destination.addr, now);
+                    if let Some(suppressed) = allowed {
+                        tracing::warn!(
+                            destination = %destination.addr,
+                            error = %e,
+                            suppressed,
+                            "relay failed"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Stops forwarding and lets go of the socket, once a datagram being sent has gone. Sending
+    /// never waits, so neither does closing. Closing again does nothing
+    pub async fn close(&self) -> std::io::Result<()> {
+        self.socket
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        Ok(())
+    }
+}
@@ -0,0 +1,334 @@
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use telemetry::{Attributes, PacketHeader, PacketID, PacketLapData};
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+use tokio::time::{sleep_until, Instant};
+
+use crate::capture::{CaptureReader, CaptureRecord};
+
+pub const MIN_SPEED: f64 = 0.5;
+pub const MAX_SPEED: f64 = 20.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Speed {
+    /// Scales the original inter-packet timing, e.g. 2.0 plays twice as fast
+    Multiplier(f64),
+    /// Sends every datagram as fast as possible
+    Max,
+}
+
+impl Speed {
+    /// Returns `None` if `multiplier` is outside [`MIN_SPEED`]..=[`MAX_SPEED`]
+    pub fn multiplier(multiplier: f64) -> Option<Self> {
+        (MIN_SPEED..=MAX_SPEED)
+            .contains(&multiplier)
+            .then_some(Self::Multiplier(multiplier))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SeekTarget {
+    /// First datagram at or after this `PacketHeader::session_time`
+    SessionTime(f32),
+    /// First datagram once the player's car has started this lap
+    Lap(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplayCommand {
+    Pause,
+    Resume,
+    /// Seeks within the file currently being played
+    Seek(SeekTarget),
+    SetSpeed(Speed),
+    Stop,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayOptions {
+    pub speed: Speed,
+    /// Starts again from the first file once the last one finishes
+    pub looping: bool,
+    /// Where to start in the first file
+    pub start: Option<SeekTarget>,
+    /// Raw packet IDs to send, every datagram if `None`
+    pub packet_ids: Option<Vec<u8>>,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        Self {
+            speed: Speed::Multiplier(1.0),
+            looping: false,
+            start: None,
+            packet_ids: None,
+        }
+    }
+}
+
+/// Controls a running [`Replayer`] from another task or thread
+#[derive(Debug, Clone)]
+pub struct ReplayHandle {
+    sender: mpsc::UnboundedSender<ReplayCommand>,
+}
+
+impl ReplayHandle {
+    /// Returns `false` if the replay has already finished
+    pub fn send(&self, command: ReplayCommand) -> bool {
+        self.sender.send(command).is_ok()
+    }
+}
+
+/// Seek points for a single capture file
+struct Index {
+    /// (record offset, session time) for every record
+    records: Vec<(u64, f32)>,
+    /// (lap, record offset) for the first record of each lap of the player's car
+    laps: Vec<(u8, u64)>,
+}
+
+impl Index {
+    fn build(path: &Path) -> io::Result<Self> {
+        let mut reader = CaptureReader::new(BufReader::new(File::open(path)?))?;
+        let mut records = Vec::new();
+        let mut laps: Vec<(u8, u64)> = Vec::new();
+
+        loop {
+            let offset = reader.position();
+            let Some(record) = reader.read_record()? else {
+                break;
+            };
+            // datagrams that don't decode are still replayed, they just can't be seeked to
+            let Ok(header) = PacketHeader::peek(&record.data) else {
+                continue;
+            };
+            records.push((offset, header.session_time));
+
+            if header.packet_id() == PacketID::Lap {
+                let player = header.player_car_index as usize;
+                if let Ok(lap) = PacketLapData::decode_car(&record.data, player) {
+                    if laps
+                        .last()
+                        .is_none_or(|(last, _)| lap.current_lap_num > *last)
+                    {
+                        laps.push((lap.current_lap_num, offset));
+                    }
+                }
+            }
+        }
+        Ok(Self { records, laps })
+    }
+
+    fn find(&self, target: SeekTarget) -> Option<u64> {
+        match target {
+            SeekTarget::SessionTime(time) => self
+                .records
+                .iter()
+                .find(|(_, session_time)| *session_time >= time)
+                .map(|(offset, _)| *offset),
+            SeekTarget::Lap(lap) => self
+                .laps
+                .iter()
+                .find(|(current, _)| *current >= lap)
+                .map(|(_, offset)| *offset),
+        }
+    }
+}
+
+enum Flow {
+    Continue,
+    Stop,
+}
+
+/// Re-emits the datagrams in capture files to a target address
+pub struct Replayer {
+    files: Vec<PathBuf>,
+    /// Built the first time each file is played, so looping doesn't re-read them
+    indexes: Vec<Option<Index>>,
+    options: ReplayOptions,
+    sender: mpsc::UnboundedSender<ReplayCommand>,
+    commands: mpsc::UnboundedReceiver<ReplayCommand>,
+    paused: bool,
+    /// (instant, capture time) the current pacing is measured from
+    anchor: Option<(Instant, SystemTime)>,
+}
+
+impl Replayer {
+    pub fn new(files: Vec<PathBuf>, options: ReplayOptions) -> Self {
+        let (sender, commands) = mpsc::unbounded_channel();
+        Self {
+            indexes: files.iter().map(|_| None).collect(),
+            files,
+            options,
+            sender,
+            commands,
+            paused: false,
+            anchor: None,
+        }
+    }
+
+    pub fn handle(&self) -> ReplayHandle {
+        ReplayHandle {
+            sender: self.sender.clone(),
+        }
+    }
+
+    /// Plays every file in order, returning the number of datagrams sent
+    pub async fn run(&mut self, target: SocketAddr) -> io::Result<u64> {
+        let bind: SocketAddr = if target.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(bind).await?;
+        let mut sent = 0;
+
+        loop {
+            let before = sent;
+            for (i, path) in self.files.clone().iter().enumerate() {
+                let start = if i == 0 && before == 0 {
+                    self.options.start
+                } else {
+                    None
+                };
+                let index = match self.indexes[i].take() {
+                    Some(index) => index,
+                    None => Index::build(path)?,
+                };
+                let flow = self
+                    .play(path, &index, &socket, target, start, &mut sent)
+                    .await?;
+                self.indexes[i] = Some(index);
+                if let Flow::Stop = flow {
+                    return Ok(sent);
+                }
+            }
+            // nothing left to loop over if a whole pass sent nothing
+            if !self.options.looping || sent == before {
+                return Ok(sent);
+            }
+        }
+    }
+
+    async fn play(
+        &mut self,
+        path: &Path,
+        index: &Index,
+        socket: &UdpSocket,
+        target: SocketAddr,
+        start: Option<SeekTarget>,
+        sent: &mut u64,
+    ) -> io::Result<Flow> {
+        let mut reader = CaptureReader::new(BufReader::new(File::open(path)?))?;
+        if let Some(offset) = start.and_then(|target| index.find(target)) {
+            reader.seek(offset)?;
+        }
+        self.anchor = None;
+
+        // a record read before a command interrupted its wait
+        let mut pending: Option<CaptureRecord> = None;
+        loop {
+            if self.paused {
+                // `self.sender` keeps the channel open, so this never returns `None`
+                if let Some(command) = self.commands.recv().await {
+                    if let Flow::Stop = self.apply(command, index, &mut reader, &mut pending)? {
+                        return Ok(Flow::Stop);
+                    }
+                }
+                continue;
+            }
+
+            let record = match pending.take() {
+                Some(record) => record,
+                None => match reader.read_record()? {
+                    Some(record) => record,
+                    None => return Ok(Flow::Continue),
+                },
+            };
+            if !self.wants(&record) {
+                continue;
+            }
+
+            match self.due(&record) {
+                Some(due) => {
+                    tokio::select! {
+                        _ = sleep_until(due) => {}
+                        Some(command) = self.commands.recv() => {
+                            pending = Some(record);
+                            if let Flow::Stop = self.apply(command, index, &mut reader, &mut pending)? {
+                                return Ok(Flow::Stop);
+                            }
+                            continue;
+                        }
+                    }
+                }
+                None => {
+                    if let Ok(command) = self.commands.try_recv() {
+                        pending = Some(record);
+                        if let Flow::Stop = self.apply(command, index, &mut reader, &mut pending)? {
+                            return Ok(Flow::Stop);
+                        }
+                        continue;
+                    }
+                }
+            }
+
+            socket.send_to(&record.data, target).await?;
+            *sent += 1;
+        }
+    }
+
+    /// Whether `record` passes the packet filter. Datagrams that don't decode only pass without one
+    fn wants(&self, record: &CaptureRecord) -> bool {
+        let Some(ids) = &self.options.packet_ids else {
+            return true;
+        };
+        PacketHeader::peek(&record.data)
+            .is_ok_and(|header| ids.contains(&u8::from(header.packet_id())))
+    }
+
+    /// When `record` should be sent, or `None` to send it straight away
+    fn due(&mut self, record: &CaptureRecord) -> Option<Instant> {
+        let Speed::Multiplier(multiplier) = self.options.speed else {
+            return None;
+        };
+        let (instant, captured) = *self
+            .anchor
+            .get_or_insert((Instant::now(), record.received_at));
+        // records received out of order are sent immediately
+        let elapsed = record
+            .received_at
+            .duration_since(captured)
+            .unwrap_or_default();
+        Some(instant + elapsed.div_f64(multiplier))
+    }
+
+    fn apply(
+        &mut self,
+        command: ReplayCommand,
+        index: &Index,
+        reader: &mut CaptureReader<BufReader<File>>,
+        pending: &mut Option<CaptureRecord>,
+    ) -> io::Result<Flow> {
+        match command {
+            ReplayCommand::Pause => self.paused = true,
+            ReplayCommand::Resume => self.paused = false,
+            ReplayCommand::Seek(target) => {
+                if let Some(offset) = index.find(target) {
+                    reader.seek(offset)?;
+                    *pending = None;
+                }
+            }
+            ReplayCommand::SetSpeed(speed) => self.options.speed = speed,
+            ReplayCommand::Stop => return Ok(Flow::Stop),
+        }
+        // timing restarts from the next record sent
+        self.anchor = None;
+        Ok(Flow::Continue)
+    }
+}
@@ -0,0 +1,336 @@
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use futures::future::try_join_all;
+use telemetry::{Attributes, FromBytes, FromPacket, Packet};
+use tokio::net::{ToSocketAddrs, UdpSocket};
+use tokio::sync::watch;
+use tracing::{debug_span, error, info, info_span, warn, Instrument};
+
+use crate::bind::BindOptions;
+use crate::dispatch::{Dispatcher, SINK_CLOSE_TIMEOUT};
+use crate::feed::FeedId;
+use crate::flashback::FlashbackDetector;
+use crate::frame::FrameSubscription;
+use crate::metrics::Metrics;
+use crate::rate_limit::LogLimit;
+use crate::recorder::Recorder;
+use crate::relay::Relay;
+use crate::sink::Sink;
+use crate::subscription::{
+    Broadcaster, ReceivedPacket, Subscriber, Subscription, TypedSubscription,
+};
+
+/// How often undecodable datagrams from one sender are logged; the rest are only counted
+pub const DECODE_ERROR_LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Most datagrams taken from each socket's buffer after being told to stop, so a sender that
+/// never lets up can't hold up shutting down
+const DRAIN_LIMIT: usize = 4096;
+
+pub struct Server {
+    sockets: Vec<UdpSocket>,
+    sinks: Vec<Arc<dyn Sink>>,
+    /// Swappable while listening, e.g. on a config reload
+    relay: RwLock<Option<Arc<Relay>>>,
+    recorder: RwLock<Option<Arc<Recorder>>>,
+    broadcaster: Broadcaster,
+    flashbacks: Mutex<FlashbackDetector>,
+    metrics: Arc<Metrics>,
+    decode_errors: Mutex<LogLimit>,
+    sink_close_timeout: Duration,
+}
+
+impl Server {
+    pub async fn new<T: ToSocketAddrs>(addr: T) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(addr).await?;
+        Ok(Self::with_sockets(vec![socket]))
+    }
+
+    /// Receives on sockets set up elsewhere, e.g. in ways [`BindOptions`] doesn't cover
+    pub fn with_sockets(sockets: Vec<UdpSocket>) -> Self {
+        let broadcaster = Broadcaster::new();
+        let metrics = Arc::new(Metrics::new(broadcaster.lagged_counter()));
+        Self {
+            sockets,
+            sinks: Vec::new(),
+            relay: RwLock::new(None),
+            recorder: RwLock::new(None),
+            broadcaster,
+            flashbacks: Mutex::new(FlashbackDetector::new()),
+            metrics,
+            decode_errors: Mutex::new(LogLimit::new(DECODE_ERROR_LOG_INTERVAL)),
+            sink_close_timeout: SINK_CLOSE_TIMEOUT,
+        }
+    }
+
+    /// Receives on every one of `binds`, e.g. for broadcast or multicast telemetry
+    pub fn with_options(binds: &[BindOptions]) -> std::io::Result<Self> {
+        if binds.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "no address to bind",
+            ));
+        }
+        let sockets = binds
+            .iter()
+            .map(BindOptions::open)
+            .collect::<Result<_, _>>()?;
+        Ok(Self::with_sockets(sockets))
+    }
+
+    /// Also receives on `addr`, with packets from every socket going to the same sinks and
+    /// subscribers
+    pub async fn bind<T: ToSocketAddrs>(&mut self, addr: T) -> std::io::Result<&mut Self> {
+        self.sockets.push(UdpSocket::bind(addr).await?);
+        Ok(self)
+    }
+
+    /// Like [`Server::bind`], with the socket set up as `options` says
+    pub fn bind_with(&mut self, options: &BindOptions) -> std::io::Result<&mut Self> {
+        self.sockets.push(options.open()?);
+        Ok(self)
+    }
+
+    /// Addresses of the bound sockets, in the order they were bound
+    pub fn local_addrs(&self) -> std::io::Result<Vec<SocketAddr>> {
+        self.sockets.iter().map(UdpSocket::local_addr).collect()
+    }
+
+    /// Subscribes to every packet decoded from now on.
+    ///
+    /// Subscribers never slow down ingest; one that falls too far behind gets
+    /// [`RecvError::Lagged`](crate::RecvError::Lagged) and misses the oldest packets.
+    pub fn subscribe_all(&self) -> Subscription {
+        self.broadcaster.subscribe()
+    }
+
+    /// A handle for subscribing later, e.g. once per client of a service
+    pub fn subscriber(&self) -> Subscriber {
+        self.broadcaster.subscriber()
+    }
+
+    /// Subscribes to a single packet type, e.g. `server.subscribe::<PacketLapData>()`
+    pub fn subscribe<T: FromPacket + Copy>(&self) -> TypedSubscription<T> {
+        TypedSubscription::new(self.broadcaster.subscribe())
+    }
+
+    /// Subscribes to the per-frame packets of every feed, put together into frames. Frames
+    /// missing packets are handed out `timeout` after their first packet arrived
+    pub fn subscribe_frames(&self, timeout: Duration) -> FrameSubscription {
+        FrameSubscription::new(self.broadcaster.subscribe(), timeout)
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.broadcaster.subscriber_count()
+    }
+
+    /// Total packets dropped for subscribers that fell behind
+    pub fn lagged_packets(&self) -> u64 {
+        self.broadcaster.lagged()
+    }
+
+    /// Counters for the packets received so far, for serving with
+    /// [`metrics::router`](crate::metrics::router)
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    /// Registers a sink to receive every decoded packet, on a task of its own while listening
+    pub fn add_sink<S: Sink + 'static>(&mut self, sink: S) -> &mut Self {
+        self.sinks.push(Arc::new(sink));
+        self
+    }
+
+    /// How long shutting down waits for the sinks and the recorder to finish before giving up on
+    /// them, [`SINK_CLOSE_TIMEOUT`] unless set
+    pub fn sink_close_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.sink_close_timeout = timeout;
+        self
+    }
+
+    /// Forwards every datagram received from now on through `relay`, including ones that don't
+    /// decode, or stops relaying if `None`. Returns the relay it replaces, which is left for the
+    /// caller to [close](Relay::close)
+    pub fn set_relay(&self, relay: Option<Arc<Relay>>) -> Option<Arc<Relay>> {
+        let mut current = self.relay.write().unwrap_or_else(|e| e.into_inner());
+        std::mem::replace(&mut *current, relay)
+    }
+
+    /// Records every datagram received from now on with `recorder`, including ones that don't
+    /// decode, or stops recording if `None`. Returns the recorder it replaces, which is left for
+    /// the caller to [close](Recorder::close)
+    pub fn set_recorder(&self, recorder: Option<Arc<Recorder>>) -> Option<Arc<Recorder>> {
+        let mut current = self.recorder.write().unwrap_or_else(|e| e.into_inner());
+        std::mem::replace(&mut *current, recorder)
+    }
+
+    /// Receives on every socket until one of them fails
+    pub async fn listen(&self) -> std::io::Result<()> {
+        self.listen_until(std::future::pending()).await
+    }
+
+    /// Receives on every socket until `shutdown` completes or one of them fails.
+    ///
+    /// Shutting down stops receiving, hands the datagrams already waiting in the sockets' buffers
+    /// to the sinks, then [closes](Sink::close) every sink and the recorder and ends every
+    /// subscription. So does a socket failing, before its error is returned. Sinks that haven't
+    /// closed within the [close timeout](Self::sink_close_timeout) are left behind.
+    pub async fn listen_until(&self, shutdown: impl Future<Output = ()>) -> std::io::Result<()> {
+        let (stop, stopped) = watch::channel(false);
+        let dispatcher = Dispatcher::start(&self.sinks, self.metrics.clone());
+        let received = {
+            let receiving = try_join_all(self.sockets.iter().map(|socket| {
+                let addr = socket.local_addr().ok().map(|addr| addr.to_string());
+                self.receive(socket, &dispatcher, stopped.clone())
+                    .instrument(info_span!("listen", addr = addr.as_deref()))
+            }));
+            tokio::pin!(receiving);
+            tokio::select! {
+                result = &mut receiving => result,
+                () = shutdown => {
+                    let _ = stop.send(true);
+                    receiving.await
+                }
+            }
+        };
+        // a failed socket still closes the sinks and subscriptions before reporting it
+        dispatcher.close(self.sink_close_timeout).await;
+        let recorder = self
+            .recorder
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        if let Some(recorder) = recorder {
+            match tokio::time::timeout(self.sink_close_timeout, recorder.close()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => error!(error = %e, "couldn't finish recording"),
+                Err(_) => warn!(
+                    timeout = ?self.sink_close_timeout,
+                    "gave up waiting for the recorder to finish"
+                ),
+            }
+        }
+        self.broadcaster.close();
+        received.map(drop)
+    }
+
+    async fn receive(
+        &self,
+        socket: &UdpSocket,
+        dispatcher: &Dispatcher,
+        mut stopped: watch::Receiver<bool>,
+    ) -> std::io::Result<()> {
+        let mut buf = vec![0; 2048];
+        loop {
+            let (len, addr) = tokio::select! {
+                // checked first so a flood of datagrams can't keep it from stopping
+                biased;
+                _ = stopped.wait_for(|stopped| *stopped) => break,
+                received = socket.recv_from(&mut buf) => received?,
+            };
+            self.received(addr, &buf[..len], dispatcher);
+        }
+        for _ in 0..DRAIN_LIMIT {
+            match try_recv_from(socket, &mut buf) {
+                Ok((len, addr)) => self.received(addr, &buf[..len], dispatcher),
+                Err(_) => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// Relays and records a datagram, then decodes it and hands it on or logs why it didn't
+    /// decode
+    fn received(&self, addr: SocketAddr, raw: &[u8], dispatcher: &Dispatcher) {
+        let received_at = SystemTime::now();
+        if let Some(relay) = &*self.relay.read().unwrap_or_else(|e| e.into_inner()) {
+            relay.forward(addr, raw);
+        }
+        if let Some(recorder) = &*self.recorder.read().unwrap_or_else(|e| e.into_inner()) {
+            recorder.record(addr, raw, received_at);
+        }
+        let len = raw.len();
+        let decoded = Packet::from_bytes(raw);
+        self.metrics.received(
+            addr,
+            len,
+            decoded.as_ref().map(|packet| packet.packet_id().into()),
+            self.broadcaster.subscriber_count(),
+        );
+        match decoded {
+            Ok(packet) => {
+                let header = packet.header();
+                let (frame, session_uid) = (header.frame_identifier, header.session_uid);
+                let span = debug_span!(
+                    "packet",
+                    src = %addr,
+                    packet_id = header.packet_id,
+                    frame,
+                    session_uid,
+                );
+                span.in_scope(|| self.handle(addr, received_at, raw, packet, dispatcher));
+            }
+            Err(e) => {
+                let allowed = self
+                    .decode_errors
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .allow(addr, Instant::now());
+                if let Some(suppressed) = allowed {
+                    warn!(src = %addr, len, error = %e, suppressed, "undecodable datagram");
+                }
+            }
+        }
+    }
+
+    /// Flashback detection, subscribers and sinks for one decoded packet
+    fn handle(
+        &self,
+        addr: SocketAddr,
+        received_at: SystemTime,
+        raw: &[u8],
+        packet: Packet,
+        dispatcher: &Dispatcher,
+    ) {
+        let feed = FeedId::new(addr, &packet);
+        let rewind = self
+            .flashbacks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .observe(feed, &packet);
+        let received = ReceivedPacket {
+            src: addr,
+            feed,
+            received_at,
+            packet: Arc::new(packet),
+            rewind,
+        };
+        if let Some(rewind) = &rewind {
+            info!(
+                feed = %feed,
+                to_frame = rewind.frame_identifier,
+                session_time = rewind.session_time,
+                "flashed back"
+            );
+            dispatcher.rewind(rewind);
+        }
+        self.broadcaster.send(received.clone());
+        dispatcher.packet(addr, received_at, raw, received.packet);
+    }
+}
+
+/// Takes a datagram waiting on `socket` without blocking. Unlike [`UdpSocket::try_recv_from`]
+/// this asks the OS directly, so it finds datagrams that arrived before the runtime noticed the
+/// socket was readable
+fn try_recv_from(socket: &UdpSocket, buf: &mut [u8]) -> std::io::Result<(usize, SocketAddr)> {
+    let socket = socket2::SockRef::from(socket);
+    let addr = socket
+        .peek_sender()?
+        .as_socket()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "not an IP sender"))?;
+    let len = std::io::Read::read(&mut &*socket, buf)?;
+    Ok((len, addr))
+}
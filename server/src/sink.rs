@@ -0,0 +1,166 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use telemetry::Packet;
+
+use crate::flashback::Rewind;
+
+pub type SinkError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Somewhere decoded packets go once they've been received.
+///
+/// Every sink registered with a [`Server`](crate::Server) sees every packet, in order, on a task
+/// of its own. A sink returning an error (or panicking) is reported and skipped for that packet
+/// without affecting the others, and one that's slow only falls behind itself: once
+/// [`SINK_QUEUE_CAPACITY`](crate::SINK_QUEUE_CAPACITY) packets are waiting for it, further ones
+/// are dropped for that sink and counted in the server's metrics.
+#[async_trait]
+pub trait Sink: Send + Sync {
+    /// Used to identify the sink in error reports
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Handles a decoded packet. `received_at` is when it came off the socket, which can be a
+    /// while before the sink gets to it if the sink has fallen behind
+    async fn handle(
+        &self,
+        src: SocketAddr,
+        received_at: SystemTime,
+        raw: &[u8],
+        packet: &Packet,
+    ) -> Result<(), SinkError>;
+
+    /// Called when a feed flashes back, before the packet that revealed it is handled. Sinks
+    /// that build anything up from past packets should drop what came after the point the game
+    /// went back to, since the game is about to send it again
+    async fn rewind(&self, rewind: &Rewind) -> Result<(), SinkError> {
+        let _ = rewind;
+        Ok(())
+    }
+
+    /// Called once the server has stopped and every packet has been handled. Sinks that write
+    /// anything out should flush and finish it here
+    async fn close(&self) -> Result<(), SinkError> {
+        Ok(())
+    }
+}
+
+/// Lets a sink be registered with the server while still being shared, e.g. with an HTTP API
+#[async_trait]
+impl<S: Sink + ?Sized> Sink for Arc<S> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    async fn handle(
+        &self,
+        src: SocketAddr,
+        received_at: SystemTime,
+        raw: &[u8],
+        packet: &Packet,
+    ) -> Result<(), SinkError> {
+        (**self).handle(src, received_at, raw, packet).await
+    }
+
+    async fn rewind(&self, rewind: &Rewind) -> Result<(), SinkError> {
+        (**self).rewind(rewind).await
+    }
+
+    async fn close(&self) -> Result<(), SinkError> {
+        (**self).close().await
+    }
+}
+
+/// A sink that can be swapped out, or removed, while the server is running, e.g. when the
+/// config is reloaded
+pub struct ReloadableSink {
+    name: String,
+    inner: RwLock<Option<Arc<dyn Sink>>>,
+}
+
+impl ReloadableSink {
+    /// Starts out empty, ignoring every packet
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            inner: RwLock::new(None),
+        }
+    }
+
+    /// Sends packets to `sink` from now on, or drops them if `None`. Packets already being
+    /// handled finish with the old sink, which is returned for the caller to
+    /// [close](Sink::close)
+    pub fn replace(&self, sink: Option<Arc<dyn Sink>>) -> Option<Arc<dyn Sink>> {
+        let mut inner = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        std::mem::replace(&mut *inner, sink)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_none()
+    }
+}
+
+#[async_trait]
+impl Sink for ReloadableSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn handle(
+        &self,
+        src: SocketAddr,
+        received_at: SystemTime,
+        raw: &[u8],
+        packet: &Packet,
+    ) -> Result<(), SinkError> {
+        // cloned so the lock isn't held across the await
+        let sink = self.inner.read().unwrap_or_else(|e| e.into_inner()).clone();
+        match sink {
+            Some(sink) => sink.handle(src, received_at, raw, packet).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn rewind(&self, rewind: &Rewind) -> Result<(), SinkError> {
+        let sink = self.inner.read().unwrap_or_else(|e| e.into_inner()).clone();
+        match sink {
+            Some(sink) => sink.rewind(rewind).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn close(&self) -> Result<(), SinkError> {
+        let sink = self.inner.read().unwrap_or_else(|e| e.into_inner()).clone();
+        match sink {
+            Some(sink) => sink.close().await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Logs every packet at debug level
+pub struct DebugSink;
+
+#[async_trait]
+impl Sink for DebugSink {
+    fn name(&self) -> &str {
+        "debug"
+    }
+
+    async fn handle(
+        &self,
+        src: SocketAddr,
+        _: SystemTime,
+        raw: &[u8],
+        packet: &Packet,
+    ) -> Result<(), SinkError> {
+        tracing::debug!(src = %src, bytes = raw.len(), packet = ?packet, "received packet");
+        Ok(())
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use serde::Serialize;
+use telemetry::{
+    Attributes, EventDataDetails, Packet, PacketCarDamageData, PacketCarStatusData,
+    PacketFinalClassificationData, PacketLapData, PacketParticipantsData, PacketSessionData,
+    MAX_CARS,
+};
+
+use crate::feed::{FeedId, FeedStats};
+use crate::flashback::Rewind;
+use crate::sink::{Sink, SinkError};
+
+/// How many events the log keeps before dropping the oldest
+pub const EVENT_LOG_CAPACITY: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LoggedEvent {
+    /// Unix time in milliseconds
+    pub received_at: u64,
+    pub session_time: f32,
+    /// Four letter event code, e.g. `FTLP`
+    pub code: String,
+    pub details: EventDataDetails,
+}
+
+/// One row of the standings, built from the lap and participants packets
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Standing {
+    pub position: u8,
+    pub car_index: usize,
+    /// Empty until a participants packet has arrived
+    pub name: String,
+    pub team_id: Option<u8>,
+    pub race_number: Option<u8>,
+    pub current_lap: u8,
+    pub last_lap_time_in_ms: u32,
+    pub delta_to_car_in_front_in_ms: u16,
+    pub delta_to_race_leader_in_ms: u16,
+    pub pit_status: u8,
+    pub num_pit_stops: u8,
+    pub penalties: u8,
+    pub result_status: u8,
+}
+
+/// The latest packets of one feed
+#[derive(Debug, Clone, Default)]
+pub struct SessionSnapshot {
+    /// Unset until the first packet arrives
+    pub feed: Option<FeedId>,
+    pub session_uid: Option<u64>,
+    pub session: Option<PacketSessionData>,
+    pub lap: Option<PacketLapData>,
+    pub participants: Option<PacketParticipantsData>,
+    pub car_status: Option<PacketCarStatusData>,
+    pub car_damage: Option<PacketCarDamageData>,
+    pub final_classification: Option<PacketFinalClassificationData>,
+    /// Oldest first
+    pub events: VecDeque<LoggedEvent>,
+}
+
+impl SessionSnapshot {
+    /// Active cars ordered by race position, empty until a lap packet has arrived
+    pub fn standings(&self) -> Vec<Standing> {
+        let Some(lap) = self.lap else {
+            return Vec::new();
+        };
+        let laps = lap.lap_data;
+
+        let mut standings: Vec<Standing> = laps
+            .iter()
+            .enumerate()
+            // 0 = invalid, 1 = inactive
+            .filter(|(_, lap)| lap.result_status >= 2)
+            .map(|(car_index, lap)| {
+                let participant = self.participants.map(|p| p.participants[car_index]);
+                Standing {
+                    position: lap.car_position,
+                    car_index,
+                    name: participant.map(|p| p.display_name()).unwrap_or_default(),
+                    team_id: participant.map(|p| p.team_id),
+                    race_number: participant.map(|p| p.race_number),
+                    current_lap: lap.current_lap_num,
+                    last_lap_time_in_ms: lap.last_lap_time_in_ms,
+                    delta_to_car_in_front_in_ms: lap.delta_to_car_in_front_in_ms,
+                    delta_to_race_leader_in_ms: lap.delta_to_race_leader_in_ms,
+                    pit_status: lap.pit_status,
+                    num_pit_stops: lap.num_pit_stops,
+                    penalties: lap.penalties,
+                    result_status: lap.result_status,
+                }
+            })
+            .collect();
+        standings.sort_by_key(|standing| standing.position);
+        standings
+    }
+
+    fn new(feed: FeedId) -> Self {
+        Self {
+            feed: Some(feed),
+            session_uid: Some(feed.session_uid),
+            ..Default::default()
+        }
+    }
+
+    /// Forgets the events after the point the game went back to, apart from the flashback itself
+    fn rewind(&mut self, rewind: &Rewind) {
+        self.events
+            .retain(|event| event.session_time <= rewind.session_time || event.code == "FLBK");
+    }
+
+    fn update(&mut self, packet: &Packet, received_at: u64) {
+        let header = packet.header();
+        match packet {
+            Packet::Session(session) => self.session = Some(*session),
+            Packet::Lap(lap) => self.lap = Some(*lap),
+            Packet::Participants(participants) => self.participants = Some(*participants),
+            Packet::CarStatus(status) => self.car_status = Some(*status),
+            Packet::CarDamage(damage) => self.car_damage = Some(*damage),
+            Packet::FinalClassification(classification) => {
+                self.final_classification = Some(*classification)
+            }
+            Packet::Event(event) => {
+                if self.events.len() == EVENT_LOG_CAPACITY {
+                    self.events.pop_front();
+                }
+                let code = event.event_string_code;
+                self.events.push_back(LoggedEvent {
+                    received_at,
+                    session_time: header.session_time,
+                    code: String::from_utf8_lossy(&code).into_owned(),
+                    details: event.event_details,
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Feed {
+    snapshot: SessionSnapshot,
+    stats: FeedStats,
+}
+
+#[derive(Debug, Default)]
+struct Feeds {
+    feeds: HashMap<FeedId, Feed>,
+    /// The feed that sent the most recent packet
+    latest: Option<FeedId>,
+}
+
+/// Keeps a [`SessionSnapshot`] and [`FeedStats`] up to date for every feed. Each sender only
+/// has one feed at a time: starting a new session drops its previous one
+#[derive(Debug, Default)]
+pub struct SessionState {
+    feeds: RwLock<Feeds>,
+}
+
+impl SessionState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `f` against the snapshot of the feed that sent the latest packet, without copying
+    /// it. The snapshot is empty until the first packet arrives
+    pub fn read<R>(&self, f: impl FnOnce(&SessionSnapshot) -> R) -> R {
+        let feeds = self.feeds.read().unwrap_or_else(|e| e.into_inner());
+        match feeds.latest.and_then(|latest| feeds.feeds.get(&latest)) {
+            Some(feed) => f(&feed.snapshot),
+            None => f(&SessionSnapshot::default()),
+        }
+    }
+
+    /// Runs `f` against the snapshot of `feed`, or returns `None` if there's no such feed
+    pub fn read_feed<R>(&self, feed: &FeedId, f: impl FnOnce(&SessionSnapshot) -> R) -> Option<R> {
+        let feeds = self.feeds.read().unwrap_or_else(|e| e.into_inner());
+        feeds.feeds.get(feed).map(|feed| f(&feed.snapshot))
+    }
+
+    pub fn snapshot(&self) -> SessionSnapshot {
+        self.read(SessionSnapshot::clone)
+    }
+
+    pub fn latest_feed(&self) -> Option<FeedId> {
+        self.feeds.read().unwrap_or_else(|e| e.into_inner()).latest
+    }
+
+    /// Stats of every feed, in the order they started
+    pub fn feeds(&self) -> Vec<FeedStats> {
+        let feeds = self.feeds.read().unwrap_or_else(|e| e.into_inner());
+        let mut stats: Vec<FeedStats> = feeds
+            .feeds
+            .values()
+            .map(|feed| {
+                let mut stats = feed.stats.clone();
+                let car = stats.player_car_index as usize;
+                if let Some(participants) = feed.snapshot.participants.filter(|_| car < MAX_CARS) {
+                    stats.player_name = participants.participants[car].display_name();
+                }
+                stats
+            })
+            .collect();
+        stats.sort_by_key(|stats| (stats.first_received, stats.feed));
+        stats
+    }
+}
+
+#[async_trait]
+impl Sink for SessionState {
+    fn name(&self) -> &str {
+        "session state"
+    }
+
+    async fn handle(
+        &self,
+        src: SocketAddr,
+        _: SystemTime,
+        raw: &[u8],
+        packet: &Packet,
+    ) -> Result<(), SinkError> {
+        let id = FeedId::new(src, packet);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+
+        let mut feeds = self.feeds.write().unwrap_or_else(|e| e.into_inner());
+        if !feeds.feeds.contains_key(&id) {
+            // a sender starting a new session is done with the previous one
+            feeds.feeds.retain(|feed, _| feed.src != src);
+            let feed = Feed {
+                snapshot: SessionSnapshot::new(id),
+                stats: FeedStats::new(id, now),
+            };
+            feeds.feeds.insert(id, feed);
+        }
+        feeds.latest = Some(id);
+        if let Some(feed) = feeds.feeds.get_mut(&id) {
+            feed.snapshot.update(packet, now);
+            feed.stats.count(raw.len(), now, packet);
+        }
+        Ok(())
+    }
+
+    async fn rewind(&self, rewind: &Rewind) -> Result<(), SinkError> {
+        let mut feeds = self.feeds.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(feed) = feeds.feeds.get_mut(&rewind.feed) {
+            feed.snapshot.rewind(rewind);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use telemetry::{FromPacket, Packet};
+use tokio::sync::broadcast;
+
+use crate::feed::FeedId;
+use crate::flashback::Rewind;
+
+/// How many packets a subscriber can fall behind before it starts missing them
+pub const SUBSCRIPTION_CAPACITY: usize = 1024;
+
+/// A decoded packet along with where and when it was received
+#[derive(Debug, Clone)]
+pub struct ReceivedPacket {
+    pub src: SocketAddr,
+    /// The sender and session the packet belongs to
+    pub feed: FeedId,
+    pub received_at: SystemTime,
+    pub packet: Arc<Packet>,
+    /// Set on the first packet after a flashback
+    pub rewind: Option<Rewind>,
+}
+
+/// A single packet type out of a [`ReceivedPacket`]
+#[derive(Debug, Clone)]
+pub struct Received<T> {
+    pub src: SocketAddr,
+    pub feed: FeedId,
+    pub received_at: SystemTime,
+    pub packet: T,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvError {
+    /// The subscriber fell behind and this many packets were dropped for it. The next call to
+    /// `recv` carries on from the oldest packet still buffered.
+    Lagged(u64),
+    /// The server has shut down
+    Closed,
+}
+
+impl std::fmt::Display for RecvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RecvError::Lagged(n) => write!(f, "subscriber lagged behind by {} packets", n),
+            RecvError::Closed => write!(f, "server closed"),
+        }
+    }
+}
+
+impl std::error::Error for RecvError {}
+
+/// `None` once the server closes it
+type SharedSender = Arc<RwLock<Option<broadcast::Sender<ReceivedPacket>>>>;
+
+/// The sending half, owned by the server
+pub(crate) struct Broadcaster {
+    sender: SharedSender,
+    lagged: Arc<AtomicU64>,
+}
+
+impl Broadcaster {
+    pub(crate) fn new() -> Self {
+        let (sender, _) = broadcast::channel(SUBSCRIPTION_CAPACITY);
+        Self {
+            sender: Arc::new(RwLock::new(Some(sender))),
+            lagged: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Never waits on subscribers: slow ones lose the oldest packets instead
+    pub(crate) fn send(&self, packet: ReceivedPacket) {
+        if let Some(sender) = &*self.sender.read().unwrap_or_else(|e| e.into_inner()) {
+            // only fails when nobody is subscribed
+            let _ = sender.send(packet);
+        }
+    }
+
+    pub(crate) fn subscribe(&self) -> Subscription {
+        self.subscriber().subscribe()
+    }
+
+    pub(crate) fn subscriber(&self) -> Subscriber {
+        Subscriber {
+            sender: self.sender.clone(),
+            lagged: self.lagged.clone(),
+        }
+    }
+
+    /// Ends every subscription once it has received what was already sent
+    pub(crate) fn close(&self) {
+        self.sender
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+    }
+
+    pub(crate) fn subscriber_count(&self) -> usize {
+        self.sender
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .map_or(0, broadcast::Sender::receiver_count)
+    }
+
+    /// Total packets dropped across all subscribers because they fell behind
+    pub(crate) fn lagged(&self) -> u64 {
+        self.lagged.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn lagged_counter(&self) -> Arc<AtomicU64> {
+        self.lagged.clone()
+    }
+}
+
+/// Subscribes to the server on demand, e.g. once per client of a service. Unlike a
+/// [`Subscription`] kept around to copy, it isn't a subscriber itself, so it neither holds on
+/// to packets nor counts towards [`Server::subscriber_count`](crate::Server::subscriber_count)
+#[derive(Clone)]
+pub struct Subscriber {
+    sender: SharedSender,
+    lagged: Arc<AtomicU64>,
+}
+
+impl Subscriber {
+    /// A new subscription starting from the next packet, or one that's already closed if the
+    /// server has shut down
+    pub fn subscribe(&self) -> Subscription {
+        let sender = self.sender.read().unwrap_or_else(|e| e.into_inner());
+        let receiver = match &*sender {
+            Some(sender) => sender.subscribe(),
+            // a channel without a sender, so the subscription is closed from the start
+            None => broadcast::channel(1).1,
+        };
+        Subscription {
+            receiver,
+            lagged: self.lagged.clone(),
+        }
+    }
+}
+
+/// Live stream of every packet the server decodes
+pub struct Subscription {
+    receiver: broadcast::Receiver<ReceivedPacket>,
+    lagged: Arc<AtomicU64>,
+}
+
+impl Subscription {
+    /// A new subscription to the same server, starting from the next packet
+    pub fn resubscribe(&self) -> Subscription {
+        Subscription {
+            receiver: self.receiver.resubscribe(),
+            lagged: self.lagged.clone(),
+        }
+    }
+
+    pub async fn recv(&mut self) -> Result<ReceivedPacket, RecvError> {
+        match self.receiver.recv().await {
+            Ok(packet) => Ok(packet),
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                self.lagged.fetch_add(n, Ordering::Relaxed);
+                Err(RecvError::Lagged(n))
+            }
+            Err(broadcast::error::RecvError::Closed) => Err(RecvError::Closed),
+        }
+    }
+}
+
+/// Live stream of one packet type, see [`Server::subscribe`](crate::Server::subscribe)
+pub struct TypedSubscription<T> {
+    inner: Subscription,
+    _packet: PhantomData<fn() -> T>,
+}
+
+impl<T: FromPacket + Copy> TypedSubscription<T> {
+    pub(crate) fn new(inner: Subscription) -> Self {
+        Self {
+            inner,
+            _packet: PhantomData,
+        }
+    }
+
+    /// Waits for the next packet of type `T`, skipping over everything else
+    pub async fn recv(&mut self) -> Result<Received<T>, RecvError> {
+        loop {
+            let received = self.inner.recv().await?;
+            if let Some(packet) = T::from_packet(&received.packet) {
+                return Ok(Received {
+                    src: received.src,
+                    feed: received.feed,
+                    received_at: received.received_at,
+                    packet: *packet,
+                });
+            }
+        }
+    }
+}
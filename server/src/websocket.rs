@@ -0,0 +1,275 @@
+//! WebSocket endpoint streaming decoded packets to browser dashboards.
+//!
+//! Clients connect to `/ws`, optionally with `?format=msgpack` to get MessagePack binary
+//! frames instead of JSON text frames. Every client starts out receiving every packet, and can
+//! narrow that down at any time by sending a JSON [`ClientFilter`], e.g.
+//! `{"packets": ["car_telemetry", "lap"], "cars": [0, 3], "max_rate": 10}`, or
+//! `{"sources": ["192.168.1.20:52344"]}` to follow a single rig when several send to the server.
+//!
+//! Each message looks like `{"type": "car_telemetry", "src": "...", "feed": "<src>/<session_uid>",
+//! "received_at": <unix ms>, "packet": {...}}`. With a car filter, every per-car array in
+//! `packet` only holds the chosen cars, in the order listed in the message's `cars` field, and
+//! per-car packets (e.g. `session_history`) for other cars are skipped.
+//!
+//! When a followed sender flashes back, a `{"type": "rewind", "feed": ..., "rewind": {...}}`
+//! message with the [`Rewind`](crate::Rewind) comes before the first packet after it, whatever
+//! the filter.
+//!
+//! Once the server shuts down, clients get a close frame with code 1001 (going away). A client
+//! that hasn't taken a message within [`CLIENT_SEND_TIMEOUT`] is disconnected, so one that stops
+//! reading can't hold up shutting down.
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::extract::ws::{close_code, CloseFrame, Message, WebSocket};
+use axum::extract::{Query, State, WebSocketUpgrade};
+use axum::response::Response;
+use axum::routing::get;
+use axum::Router;
+use futures::stream::SplitSink;
+use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use telemetry::{Attributes, PacketDescriptor, MAX_CARS};
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+
+use crate::output::PacketFilter;
+use crate::rate_limit::RateLimit;
+use crate::subscription::{ReceivedPacket, RecvError, Subscriber, Subscription};
+
+/// How long a client gets to take each message before it's disconnected
+pub const CLIENT_SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Json,
+    Msgpack,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Params {
+    #[serde(default)]
+    format: Format,
+}
+
+/// Sent by a client to choose what it receives, replacing any earlier filter
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientFilter {
+    /// Packet short names, e.g. `car_telemetry`. Every type if empty
+    pub packets: Vec<String>,
+    /// Car indexes. Every car if empty
+    pub cars: Vec<usize>,
+    /// At most this many packets per second of each type
+    pub max_rate: Option<f64>,
+    /// Addresses of the senders to follow. Every sender if empty
+    pub sources: Vec<SocketAddr>,
+}
+
+/// A [`ClientFilter`] resolved against the packet registry
+#[derive(Debug, Default)]
+struct ClientState {
+    filter: PacketFilter,
+    rate_limit: RateLimit,
+}
+
+impl TryFrom<ClientFilter> for ClientState {
+    type Error = String;
+
+    fn try_from(filter: ClientFilter) -> Result<Self, Self::Error> {
+        let packet_ids = filter
+            .packets
+            .iter()
+            .map(|name| {
+                PacketDescriptor::for_name(name)
+                    .map(|descriptor| descriptor.id)
+                    .ok_or(format!("unknown packet type {name}"))
+            })
+            .collect::<Result<_, _>>()?;
+        if let Some(car) = filter.cars.iter().find(|car| **car >= MAX_CARS) {
+            return Err(format!("car index {car} is out of bounds"));
+        }
+        Ok(Self {
+            filter: PacketFilter {
+                packet_ids,
+                ..PacketFilter::default()
+            }
+            .cars(filter.cars)
+            .sources(filter.sources),
+            rate_limit: RateLimit::new(filter.max_rate),
+        })
+    }
+}
+
+impl ClientState {
+    /// The rewind message to send before `received`, if there is one and this client follows
+    /// its sender
+    fn rewind(&self, received: &ReceivedPacket) -> Option<Value> {
+        let rewind = received.rewind?;
+        if !self.filter.accepts_source(received.src) {
+            return None;
+        }
+        Some(json!({ "type": "rewind", "feed": rewind.feed, "rewind": rewind }))
+    }
+
+    /// Builds the message for `received`, or `None` if this client doesn't want it
+    fn message(&mut self, received: &ReceivedPacket) -> Option<Value> {
+        let packet_id = received.packet.packet_id().into();
+        if !self.filter.accepts(packet_id)
+            || !self.filter.accepts_source(received.src)
+            || !self
+                .rate_limit
+                .allow(received.src, packet_id, Instant::now())
+        {
+            return None;
+        }
+        self.filter
+            .message(received.src, received.received_at, &received.packet)
+    }
+}
+
+fn encode(message: &Value, format: Format) -> Option<Message> {
+    match format {
+        Format::Json => Some(Message::Text(message.to_string())),
+        Format::Msgpack => rmp_serde::to_vec_named(message).ok().map(Message::Binary),
+    }
+}
+
+struct Endpoint {
+    subscriber: Subscriber,
+    /// Clients connected
+    clients: watch::Sender<usize>,
+}
+
+/// Counts a client as connected for as long as it's held
+struct Connected(watch::Sender<usize>);
+
+impl Connected {
+    fn new(clients: &watch::Sender<usize>) -> Self {
+        clients.send_modify(|count| *count += 1);
+        Self(clients.clone())
+    }
+}
+
+impl Drop for Connected {
+    fn drop(&mut self) {
+        self.0.send_modify(|count| *count -= 1);
+    }
+}
+
+/// Routes serving the WebSocket endpoint at `/ws`. Each client subscribes through `subscriber`
+/// when it connects, so a slow client only ever loses its own packets.
+pub fn router(subscriber: Subscriber) -> Router {
+    endpoint(subscriber, watch::Sender::new(0))
+}
+
+fn endpoint(subscriber: Subscriber, clients: watch::Sender<usize>) -> Router {
+    Router::new()
+        .route("/ws", get(upgrade))
+        .with_state(Arc::new(Endpoint {
+            subscriber,
+            clients,
+        }))
+}
+
+/// Serves [`router`] on `listener` until `shutdown` completes, then waits for every client to
+/// be sent its close frame, which happens once the server closes its subscriptions
+pub async fn serve(
+    listener: TcpListener,
+    subscriber: Subscriber,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> std::io::Result<()> {
+    let clients = watch::Sender::new(0);
+    let mut connected = clients.subscribe();
+    axum::serve(listener, endpoint(subscriber, clients))
+        .with_graceful_shutdown(shutdown)
+        .await?;
+    // axum stops tracking connections once they're upgraded, so they're counted here
+    let _ = connected.wait_for(|count| *count == 0).await;
+    Ok(())
+}
+
+async fn upgrade(
+    ws: WebSocketUpgrade,
+    Query(params): Query<Params>,
+    State(endpoint): State<Arc<Endpoint>>,
+) -> Response {
+    let subscription = endpoint.subscriber.subscribe();
+    let connected = Connected::new(&endpoint.clients);
+    ws.on_upgrade(move |socket| client(socket, subscription, params.format, connected))
+}
+
+async fn client(
+    socket: WebSocket,
+    mut subscription: Subscription,
+    format: Format,
+    _connected: Connected,
+) {
+    let (mut sender, mut receiver) = socket.split();
+    let mut state = ClientState::default();
+
+    loop {
+        tokio::select! {
+            received = subscription.recv() => {
+                let received = match received {
+                    Ok(received) => received,
+                    // the client only misses packets, it carries on from the oldest buffered
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => {
+                        let close = CloseFrame {
+                            code: close_code::AWAY,
+                            reason: "server shutting down".into(),
+                        };
+                        send(&mut sender, Message::Close(Some(close))).await;
+                        break;
+                    }
+                };
+                let messages = state
+                    .rewind(&received)
+                    .into_iter()
+                    .chain(state.message(&received))
+                    .filter_map(|message| encode(&message, format));
+                let mut sent = true;
+                for message in messages.collect::<Vec<_>>() {
+                    sent = send(&mut sender, message).await;
+                    if !sent {
+                        break;
+                    }
+                }
+                if !sent {
+                    break;
+                }
+            }
+            message = receiver.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    // pings are answered by axum, anything else is ignored
+                    Some(Ok(_)) => continue,
+                };
+                let filter = serde_json::from_str::<ClientFilter>(&text).map_err(|e| e.to_string());
+                match filter.and_then(ClientState::try_from) {
+                    Ok(new_state) => state = new_state,
+                    Err(e) => {
+                        let error = Message::Text(json!({ "error": e }).to_string());
+                        if !send(&mut sender, error).await {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Whether the client took `message` within [`CLIENT_SEND_TIMEOUT`]
+async fn send(sender: &mut SplitSink<WebSocket, Message>, message: Message) -> bool {
+    let sent = tokio::time::timeout(CLIENT_SEND_TIMEOUT, sender.send(message)).await;
+    matches!(sent, Ok(Ok(())))
+}
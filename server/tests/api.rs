@@ -0,0 +1,167 @@
+mod common;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::Value;
+use server::{api, HealthMonitor, Server, SessionState};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+
+use common::corpus;
+
+/// Starts a server with the HTTP API, returning the UDP and HTTP addresses
+async fn start() -> (SocketAddr, SocketAddr) {
+    let mut server = Server::new("127.0.0.1:0").await.unwrap();
+    let state = Arc::new(SessionState::new());
+    let health = Arc::new(HealthMonitor::new());
+    server.add_sink(state.clone());
+    server.add_sink(health.clone());
+    let udp = server.local_addrs().unwrap()[0];
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let http = listener.local_addr().unwrap();
+
+    let router = api::router(state, health);
+    tokio::spawn(async move { axum::serve(listener, router).await });
+    tokio::spawn(async move { server.listen().await });
+    (udp, http)
+}
+
+/// The status and JSON body of `GET path`
+async fn get(http: SocketAddr, path: &str) -> (u16, Value) {
+    let mut stream = TcpStream::connect(http).await.unwrap();
+    let request = format!("GET {path} HTTP/1.1\r\nHost: {http}\r\nConnection: close\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    tokio::time::timeout(Duration::from_secs(5), stream.read_to_string(&mut response))
+        .await
+        .expect("timed out waiting for a response")
+        .unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, serde_json::from_str(body).unwrap())
+}
+
+async fn send_udp(server: SocketAddr, names: &[&str]) {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    for name in names {
+        socket.send_to(&corpus(name), server).await.unwrap();
+    }
+}
+
+/// Waits until a feed has sent `count` packets, returning its stats
+async fn feed_with(http: SocketAddr, count: u64) -> Value {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let (_, feeds) = get(http, "/api/feeds").await;
+            if let Some(feed) = feeds
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|feed| feed["packets"] == count)
+            {
+                return feed.clone();
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("timed out waiting for the packets")
+}
+
+#[tokio::test]
+async fn serves_the_session_state() {
+    let (udp, http) = start().await;
+    send_udp(
+        udp,
+        &[
+            "session-zeroed",
+            "lap-zeroed",
+            "car_status-zeroed",
+            "event-ftlp",
+        ],
+    )
+    .await;
+    let feed = feed_with(http, 4).await;
+    assert_eq!(feed["packets_by_type"]["car_status"], 1);
+
+    let (status, session) = get(http, "/api/session").await;
+    assert_eq!(status, 200);
+    assert!(session.get("track_id").is_some(), "{session}");
+
+    let (status, standings) = get(http, "/api/standings").await;
+    assert_eq!(status, 200);
+    assert!(standings.is_array());
+
+    let (status, car) = get(http, "/api/cars/3/status").await;
+    assert_eq!(status, 200);
+    assert!(car.get("fuel_in_tank").is_some(), "{car}");
+
+    let (status, events) = get(http, "/api/events").await;
+    assert_eq!(status, 200);
+    assert_eq!(events.as_array().unwrap().len(), 1);
+
+    // the health monitor is a sink of its own, so may not have caught up yet
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let (status, health) = get(http, "/api/health").await;
+            assert_eq!(status, 200);
+            if health.as_array().unwrap().len() == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("timed out waiting for the feed's health");
+
+    let (status, openapi) = get(http, "/api/openapi.json").await;
+    assert_eq!(status, 200);
+    assert!(openapi.get("openapi").is_some());
+}
+
+#[tokio::test]
+async fn feeds_can_be_chosen() {
+    let (udp, http) = start().await;
+    send_udp(udp, &["session-zeroed"]).await;
+    let feed = feed_with(http, 1).await;
+    let feed = feed["feed"].as_str().unwrap();
+
+    let (status, _) = get(http, &format!("/api/session?feed={feed}")).await;
+    assert_eq!(status, 200);
+
+    let (status, error) = get(http, "/api/session?feed=127.0.0.1:1/5").await;
+    assert_eq!(status, 404);
+    assert_eq!(error["error"], "no feed 127.0.0.1:1/5");
+}
+
+#[tokio::test]
+async fn missing_data_and_bad_cars_are_errors() {
+    let (udp, http) = start().await;
+
+    // nothing received yet
+    let (status, error) = get(http, "/api/session").await;
+    assert_eq!(status, 404);
+    assert_eq!(error["error"], "no session packet received yet");
+    let (status, events) = get(http, "/api/events").await;
+    assert_eq!(status, 200);
+    assert_eq!(events, Value::Array(Vec::new()));
+
+    send_udp(udp, &["lap-zeroed"]).await;
+    feed_with(http, 1).await;
+    let (status, error) = get(http, "/api/cars/0/damage").await;
+    assert_eq!(status, 404);
+    assert_eq!(error["error"], "no car damage packet received yet");
+    let (status, error) = get(http, "/api/final-classification").await;
+    assert_eq!(status, 404);
+    assert_eq!(
+        error["error"],
+        "no final classification packet received yet"
+    );
+
+    let (status, error) = get(http, "/api/cars/22/status").await;
+    assert_eq!(status, 400);
+    assert_eq!(error["error"], "car index 22 is out of bounds");
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use server::{BindOptions, Server, SessionState};
+use tokio::net::UdpSocket;
+
+use common::corpus;
+
+fn addr(addr: &str) -> BindOptions {
+    BindOptions::new(addr.parse().unwrap())
+}
+
+#[tokio::test]
+async fn receives_on_ipv4_and_ipv6_at_once() {
+    let mut server = Server::with_options(&[addr("127.0.0.1:0"), addr("[::1]:0")]).unwrap();
+    let state = Arc::new(SessionState::new());
+    server.add_sink(state.clone());
+    let addrs = server.local_addrs().unwrap();
+    tokio::spawn(async move { server.listen().await });
+
+    for (bind, to) in [("127.0.0.1:0", addrs[0]), ("[::1]:0", addrs[1])] {
+        let socket = UdpSocket::bind(bind).await.unwrap();
+        socket.send_to(&corpus("lap-zeroed"), to).await.unwrap();
+    }
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while state.feeds().len() < 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("timed out waiting for both feeds");
+    let senders: Vec<SocketAddr> = state.feeds().iter().map(|stats| stats.feed.src).collect();
+    assert!(senders.iter().any(SocketAddr::is_ipv4));
+    assert!(senders.iter().any(SocketAddr::is_ipv6));
+}
+
+#[tokio::test]
+async fn broadcast_receivers_share_a_port() {
+    let first = Server::with_options(&[addr("0.0.0.0:0").broadcast()]).unwrap();
+    let port = first.local_addrs().unwrap()[0].port();
+    let shared = format!("0.0.0.0:{port}");
+    assert!(Server::with_options(&[addr(&shared).broadcast()]).is_ok());
+}
+
+#[test]
+fn rejects_options_that_cannot_work() {
+    assert!(addr("[::]:20777").broadcast().check().is_err());
+    assert!(addr("0.0.0.0:20777")
+        .join("10.0.0.1".parse().unwrap())
+        .check()
+        .is_err());
+    assert!(addr("[::]:20777")
+        .join("239.255.0.1".parse().unwrap())
+        .check()
+        .is_err());
+    assert!(addr("[::]:20777")
+        .join("ff02::1234".parse().unwrap())
+        .check()
+        .is_ok());
+}
@@ -0,0 +1,147 @@
+mod common;
+
+use std::fs::File;
+use std::io::{BufReader, Cursor};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+use server::capture::{
+    CaptureHeader, CaptureReader, CaptureRecord, CaptureSummary, CaptureWriter, CAPTURE_VERSION,
+};
+use server::Recorder;
+
+use common::{corpus, empty_dir, with_uid};
+
+fn records() -> Vec<CaptureRecord> {
+    let v4: SocketAddr = "192.168.1.20:52344".parse().unwrap();
+    let v6: SocketAddr = "[fe80::1]:20777".parse().unwrap();
+    vec![
+        CaptureRecord {
+            // whole microseconds, which is all the format keeps
+            received_at: UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456),
+            src: v4,
+            data: corpus("lap-zeroed"),
+        },
+        CaptureRecord {
+            received_at: UNIX_EPOCH + Duration::from_micros(1_700_000_000_140_000),
+            src: v6,
+            data: vec![1, 2, 3],
+        },
+    ]
+}
+
+fn header() -> CaptureHeader {
+    CaptureHeader {
+        created: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        ..CaptureHeader::new(2023, 42)
+    }
+}
+
+fn read_file(path: &Path) -> CaptureReader<BufReader<File>> {
+    CaptureReader::new(BufReader::new(File::open(path).unwrap())).unwrap()
+}
+
+#[test]
+fn captures_round_trip() {
+    let mut writer = CaptureWriter::new(Vec::new(), &header()).unwrap();
+    for record in records() {
+        writer.write_record(&record).unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+
+    let mut reader = CaptureReader::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(reader.header(), &header());
+    assert_eq!(reader.header().capture_version, CAPTURE_VERSION);
+    assert!(reader.footer().is_none());
+    let read: Vec<CaptureRecord> = reader.by_ref().map(Result::unwrap).collect();
+    assert_eq!(read, records());
+    assert_eq!(reader.footer().unwrap().records, 2);
+    // nothing after the footer
+    assert!(reader.read_record().unwrap().is_none());
+}
+
+#[test]
+fn files_cut_short_keep_every_complete_record() {
+    let mut writer = CaptureWriter::new(Vec::new(), &header()).unwrap();
+    for record in records() {
+        writer.write_record(&record).unwrap();
+    }
+    let mut bytes = writer.into_inner();
+
+    // never finished: every record, then the end of the file without a footer
+    let mut reader = CaptureReader::new(Cursor::new(bytes.clone())).unwrap();
+    assert_eq!(reader.by_ref().count(), 2);
+    assert!(reader.footer().is_none());
+
+    // cut off partway through the last record
+    bytes.truncate(bytes.len() - 2);
+    let summary = CaptureSummary::read(CaptureReader::new(Cursor::new(bytes)).unwrap());
+    assert_eq!(summary.records, 1);
+    assert_eq!(summary.packets["lap"], 1);
+    assert!(summary.read_error.is_some());
+    assert!(summary.footer.is_none());
+}
+
+#[test]
+fn other_files_are_rejected() {
+    assert!(CaptureReader::new(Cursor::new(b"not a capture file".to_vec())).is_err());
+
+    let mut newer = CaptureWriter::new(Vec::new(), &header())
+        .unwrap()
+        .into_inner();
+    newer[8..10].copy_from_slice(&(CAPTURE_VERSION + 1).to_le_bytes());
+    assert!(CaptureReader::new(Cursor::new(newer)).is_err());
+}
+
+#[tokio::test]
+async fn recorder_starts_a_new_file_for_a_new_session() {
+    let dir = empty_dir("capture-recorder");
+    let recorder = Recorder::new(&dir).unwrap();
+    let src: SocketAddr = "127.0.0.1:52344".parse().unwrap();
+    let at = |secs| UNIX_EPOCH + Duration::from_secs(1_700_000_000 + secs);
+
+    for (secs, raw) in [
+        with_uid(corpus("lap-zeroed"), 1),
+        with_uid(corpus("motion-zeroed"), 1),
+        // no header to go by, so it's kept with the sender's current session
+        vec![1, 2, 3],
+        with_uid(corpus("lap-zeroed"), 2),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        recorder.record(src, &raw, at(secs as u64));
+    }
+    recorder.close().await.unwrap();
+    assert!(recorder.current_paths().is_empty());
+    assert_eq!(recorder.dropped(), 0);
+
+    let mut files: Vec<CaptureReader<BufReader<File>>> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| read_file(&entry.unwrap().path()))
+        .collect();
+    assert_eq!(files.len(), 2);
+    files.sort_by_key(|file| file.header().session_uid);
+    let [first, second] = &mut files[..] else {
+        unreachable!()
+    };
+    assert_eq!(first.header().session_uid, 1);
+    assert_eq!(second.header().session_uid, 2);
+
+    // the first session's file was finished when the second started
+    let first_records: Vec<CaptureRecord> = first.by_ref().map(Result::unwrap).collect();
+    assert_eq!(first_records.len(), 3);
+    assert!(first_records.iter().all(|record| record.src == src));
+    // stamped when received, not when written
+    let times: Vec<_> = first_records
+        .iter()
+        .map(|record| record.received_at)
+        .collect();
+    assert_eq!(times, [at(0), at(1), at(2)]);
+    assert_eq!(first_records[2].data, [1, 2, 3]);
+    assert_eq!(first.footer().unwrap().records, 3);
+    assert_eq!(second.by_ref().count(), 1);
+    assert_eq!(second.footer().unwrap().records, 1);
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,274 @@
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use clap::error::ErrorKind;
+use clap::Parser;
+use server::cli::{parse_command, Cli, Command, ListenArgs, Logging, ReplayArgs};
+use server::config::{Config, ConfigError};
+use server::logging::{LogFormat, Logger};
+use server::replay::{ReplayCommand, SeekTarget, Speed};
+use tracing::level_filters::LevelFilter;
+
+fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+    Cli::try_parse_from(["server"].iter().chain(args))
+}
+
+fn listen(args: &[&str]) -> ListenArgs {
+    match parse(&[&["listen"][..], args].concat()).unwrap().command {
+        Command::Listen(args) => *args,
+        command => panic!("expected listen, got {command:?}"),
+    }
+}
+
+fn replay(args: &[&str]) -> ReplayArgs {
+    match parse(&[&["replay", "127.0.0.1:20777", "a.f1cap"][..], args].concat())
+        .unwrap()
+        .command
+    {
+        Command::Replay(args) => args,
+        command => panic!("expected replay, got {command:?}"),
+    }
+}
+
+fn rejected(args: &[&str]) -> ErrorKind {
+    parse(args).unwrap_err().kind()
+}
+
+#[test]
+fn listen_options_make_the_same_config_as_a_file() {
+    let args = listen(&[
+        "-b",
+        "0.0.0.0:20777",
+        "--broadcast",
+        "--format",
+        "compact",
+        "-p",
+        "lap,event",
+        "-c",
+        "0,1",
+        "--sources",
+        "192.168.1.30:52344",
+        "--record",
+        "captures",
+        "--relay",
+        "192.168.1.20:20777/car_telemetry@10",
+        "--relay",
+        "192.168.1.21:20777",
+        "--ws",
+        "0.0.0.0:8080",
+        "--http",
+        "0.0.0.0:8081",
+        "--grpc",
+        "0.0.0.0:50051",
+        "--mqtt",
+        "broker:1884/car_status,event@5",
+        "--mqtt-qos",
+        "1",
+        "--mqtt-retain",
+        "--mqtt-sender-topics",
+    ]);
+    let file: Config = r#"
+        bind = { addr = "0.0.0.0:20777", broadcast = true }
+
+        [output]
+        format = "compact"
+        packets = ["lap", "event"]
+        cars = [0, 1]
+        sources = ["192.168.1.30:52344"]
+
+        [recorder]
+        dir = "captures"
+
+        [[relay]]
+        addr = "192.168.1.20:20777"
+        packets = ["car_telemetry"]
+        max_rate = 10
+
+        [[relay]]
+        addr = "192.168.1.21:20777"
+
+        [websocket]
+        bind = "0.0.0.0:8080"
+
+        [http]
+        bind = "0.0.0.0:8081"
+
+        [grpc]
+        bind = "0.0.0.0:50051"
+
+        [mqtt]
+        host = "broker"
+        port = 1884
+        sender_topics = true
+        qos = 1
+        retain = true
+        packets = ["car_status", "event"]
+        max_rate = 5
+    "#
+    .parse()
+    .unwrap();
+    assert_eq!(args.into_config().unwrap(), file);
+}
+
+#[test]
+fn listening_without_options_uses_the_defaults() {
+    assert_eq!(listen(&[]).into_config().unwrap(), Config::default());
+
+    let config = listen(&["--quiet", "--mqtt", "localhost"])
+        .into_config()
+        .unwrap();
+    assert!(!config.output.enabled);
+    let mqtt = config.mqtt.unwrap();
+    assert_eq!((mqtt.host.as_str(), mqtt.port), ("localhost", 1883));
+    assert_eq!(mqtt.prefix, "f1");
+    assert!(!mqtt.sender_topics);
+}
+
+#[test]
+fn bad_options_are_usage_errors() {
+    for args in [
+        &["listen", "-c", "22"][..],
+        &["listen", "-p", "pit_wall"],
+        &["listen", "--relay", "nowhere"],
+        &["listen", "--relay", "127.0.0.1:1/lap@fast"],
+        &["listen", "--mqtt", "localhost:port"],
+        &["listen", "--mqtt", "localhost", "--mqtt-qos", "3"],
+        &["listen", "--log-level", "loud"],
+        &["replay", "127.0.0.1:20777", "a.f1cap", "--speed", "50"],
+    ] {
+        assert_eq!(rejected(args), ErrorKind::ValueValidation, "{args:?}");
+    }
+    assert_eq!(
+        rejected(&["listen", "--config", "f1.toml", "--ws", "0.0.0.0:8080"]),
+        ErrorKind::ArgumentConflict
+    );
+    assert_eq!(
+        rejected(&["listen", "--mqtt-retain"]),
+        ErrorKind::MissingRequiredArgument
+    );
+    assert_eq!(
+        rejected(&["replay", "127.0.0.1:20777"]),
+        ErrorKind::MissingRequiredArgument
+    );
+    assert_eq!(rejected(&["inspect"]), ErrorKind::MissingRequiredArgument);
+}
+
+#[test]
+fn multicast_groups_need_an_address_to_join_on() {
+    let args = listen(&["-b", "[::1]:20777", "--multicast", "239.255.0.1"]);
+    match args.into_config() {
+        Err(ConfigError::Invalid(e)) => {
+            assert_eq!(
+                e,
+                "no address of the same IP version to join 239.255.0.1 on"
+            )
+        }
+        other => panic!("expected an invalid config, got {other:?}"),
+    }
+
+    let config = listen(&[
+        "-b",
+        "0.0.0.0:20777",
+        "-b",
+        "[::]:20777",
+        "--multicast",
+        "239.255.0.1,ff02::1",
+    ])
+    .into_config()
+    .unwrap();
+    let group = |addr: &str| addr.parse::<IpAddr>().unwrap();
+    assert_eq!(config.bind[0].multicast, [group("239.255.0.1")]);
+    assert_eq!(config.bind[1].multicast, [group("ff02::1")]);
+}
+
+#[test]
+fn replay_options() {
+    let args = replay(&["--speed", "2", "--from-lap", "3", "-p", "lap"]);
+    assert_eq!(args.speed, Speed::Multiplier(2.0));
+    assert_eq!(args.from_lap, Some(3));
+    assert_eq!(args.packets.packet_ids(), Some(vec![2]));
+    assert_eq!(replay(&[]).packets.packet_ids(), None);
+    assert!(replay(&["--max", "--loop"]).looping);
+
+    assert_eq!(
+        rejected(&[
+            "replay",
+            "127.0.0.1:20777",
+            "a.f1cap",
+            "--max",
+            "--speed",
+            "2"
+        ]),
+        ErrorKind::ArgumentConflict
+    );
+    assert_eq!(
+        rejected(&[
+            "replay",
+            "127.0.0.1:20777",
+            "a.f1cap",
+            "--from-time",
+            "10",
+            "--from-lap",
+            "2"
+        ]),
+        ErrorKind::ArgumentConflict
+    );
+}
+
+#[test]
+fn replay_commands() {
+    assert_eq!(parse_command("p"), Some(ReplayCommand::Pause));
+    assert_eq!(parse_command(" r "), Some(ReplayCommand::Resume));
+    assert_eq!(parse_command("q"), Some(ReplayCommand::Stop));
+    assert_eq!(
+        parse_command("t 12.5"),
+        Some(ReplayCommand::Seek(SeekTarget::SessionTime(12.5)))
+    );
+    assert_eq!(
+        parse_command("l 3"),
+        Some(ReplayCommand::Seek(SeekTarget::Lap(3)))
+    );
+    assert_eq!(
+        parse_command("s max"),
+        Some(ReplayCommand::SetSpeed(Speed::Max))
+    );
+    assert_eq!(
+        parse_command("s 0.5"),
+        Some(ReplayCommand::SetSpeed(Speed::Multiplier(0.5)))
+    );
+    for line in ["", "x", "p now", "t", "l first", "s 50"] {
+        assert_eq!(parse_command(line), None, "{line:?}");
+    }
+}
+
+#[test]
+fn log_options_override_the_config() {
+    let config = Config {
+        log_level: LevelFilter::WARN,
+        log_format: LogFormat::Json,
+        ..Config::default()
+    };
+
+    let logging = Logging {
+        logger: Arc::new(Logger::with_writer(
+            LevelFilter::DEBUG,
+            LogFormat::Text,
+            std::io::sink(),
+        )),
+        level: Some(LevelFilter::DEBUG),
+        format: None,
+    };
+    logging.apply(&config);
+    assert_eq!(logging.logger.level(), LevelFilter::DEBUG);
+    assert_eq!(logging.logger.format(), LogFormat::Json);
+
+    let logging = Logging {
+        level: None,
+        format: Some(LogFormat::Text),
+        ..logging
+    };
+    logging.logger.set_format(LogFormat::Text);
+    logging.apply(&config);
+    assert_eq!(logging.logger.level(), LevelFilter::WARN);
+    assert_eq!(logging.logger.format(), LogFormat::Text);
+}
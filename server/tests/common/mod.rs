@@ -0,0 +1,47 @@
+//! Helpers shared by the integration tests. Each test binary uses only some of them
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use server::Server;
+
+pub fn corpus_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../telemetry/fuzz/corpus/packet")
+}
+
+pub fn corpus(name: &str) -> Vec<u8> {
+    std::fs::read(corpus_dir().join(name)).unwrap()
+}
+
+/// A zeroed corpus packet, e.g. `lap`, sent on `frame`
+pub fn packet(name: &str, frame: u32) -> Vec<u8> {
+    let mut raw = corpus(&format!("{name}-zeroed"));
+    raw[19..23].copy_from_slice(&frame.to_le_bytes());
+    raw[23..27].copy_from_slice(&frame.to_le_bytes());
+    raw
+}
+
+pub fn with_uid(mut raw: Vec<u8>, session_uid: u64) -> Vec<u8> {
+    raw[7..15].copy_from_slice(&session_uid.to_le_bytes());
+    raw
+}
+
+/// A directory of the temp dir named for the test, removed if an earlier run left it behind
+pub fn empty_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("f1-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Waits until the server has received `count` datagrams
+pub async fn received(server: &Server, count: u64) {
+    let line = format!("f1_datagrams_received_total {count}\n");
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !server.metrics().render(None).contains(&line) {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("timed out waiting for {count} datagrams"));
+}
@@ -0,0 +1,97 @@
+use server::config::{Config, ConfigError};
+use server::output::Format;
+use server::QoS;
+use telemetry::PacketID;
+
+fn invalid(toml: &str) -> String {
+    match toml.parse::<Config>() {
+        Err(ConfigError::Invalid(e)) => e,
+        other => panic!("expected an invalid config, got {other:?}"),
+    }
+}
+
+#[test]
+fn documented_example_parses() {
+    let docs = include_str!("../src/config.rs");
+    let example: String = docs
+        .lines()
+        .skip_while(|line| *line != "//! ```toml")
+        .skip(1)
+        .take_while(|line| *line != "//! ```")
+        .map(|line| format!("{}\n", line.trim_start_matches("//!").trim_start()))
+        .collect();
+
+    let config: Config = example.parse().unwrap();
+    assert_eq!(config.bind.len(), 3);
+    assert!(config.bind[2].broadcast);
+    assert_eq!(config.output.format, Format::Compact);
+    assert_eq!(config.relay[0].max_rate, Some(10.0));
+    let mqtt = config.mqtt.unwrap();
+    assert_eq!(mqtt.qos, QoS::AtLeastOnce);
+    assert!(mqtt.sender_topics);
+    assert_eq!(
+        mqtt.fields,
+        vec![(PacketID::CarStatus, vec!["vehicle_fia_flags".to_string()])]
+    );
+}
+
+#[test]
+fn empty_config_uses_defaults() {
+    let config: Config = "".parse().unwrap();
+    assert_eq!(config, Config::default());
+}
+
+#[test]
+fn rejects_inconsistent_configs() {
+    assert!(invalid("bind = []").contains("at least one"));
+    assert!(invalid(
+        r#"
+        bind = "127.0.0.1:20777"
+        [[relay]]
+        addr = "127.0.0.1:20777"
+        "#
+    )
+    .contains("back to this server"));
+    assert!(invalid(
+        r#"
+        [http]
+        bind = "127.0.0.1:8080"
+        [grpc]
+        bind = "127.0.0.1:8080"
+        "#
+    )
+    .contains("more than one"));
+    assert!(invalid(
+        r#"
+        [mqtt]
+        host = "localhost"
+        prefix = "f1/#"
+        "#
+    )
+    .contains("wildcards"));
+}
+
+#[test]
+fn rejects_unknown_names() {
+    for toml in [
+        "bnd = \"127.0.0.1:20777\"",
+        "[output]\npackets = [\"tyres\"]",
+        "[output]\ncars = [22]",
+        "[mqtt]\nhost = \"localhost\"\nqos = 3",
+    ] {
+        assert!(
+            matches!(toml.parse::<Config>(), Err(ConfigError::Parse(_))),
+            "{toml}"
+        );
+    }
+}
+
+#[test]
+fn reports_sections_that_need_a_restart() {
+    let old: Config = "[output]\nformat = \"json\"".parse().unwrap();
+    let new: Config = "bind = \"127.0.0.1:20778\"\n[http]\nbind = \"127.0.0.1:8080\""
+        .parse()
+        .unwrap();
+    assert_eq!(old.needs_restart(&new), ["bind", "http"]);
+    assert!(old.needs_restart(&old).is_empty());
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use server::{FeedId, FlashbackDetector, Server, SessionState};
+use telemetry::{FromBytes, Packet};
+use tokio::net::UdpSocket;
+
+use common::corpus;
+
+/// A corpus packet sent on `frame`, `overall` frames into the session
+fn at(name: &str, frame: u32, overall: u32) -> Vec<u8> {
+    let mut raw = corpus(name);
+    raw[15..19].copy_from_slice(&(frame as f32 / 10.0).to_le_bytes());
+    raw[19..23].copy_from_slice(&frame.to_le_bytes());
+    raw[23..27].copy_from_slice(&overall.to_le_bytes());
+    raw
+}
+
+/// A `FLBK` event going back to `target`
+fn flashback(target: u32, frame: u32, overall: u32) -> Vec<u8> {
+    let mut raw = at("event-flbk", frame, overall);
+    raw[33..37].copy_from_slice(&target.to_le_bytes());
+    raw[37..41].copy_from_slice(&(target as f32 / 10.0).to_le_bytes());
+    raw
+}
+
+fn observe(detector: &mut FlashbackDetector, raw: &[u8]) -> Option<(u32, u32)> {
+    let src: SocketAddr = "192.168.1.20:52344".parse().unwrap();
+    let packet = Packet::from_bytes(raw).unwrap();
+    detector
+        .observe(FeedId::new(src, &packet), &packet)
+        .map(|rewind| (rewind.frame_identifier, rewind.from_frame_identifier))
+}
+
+#[test]
+fn rewinds_once_when_frames_go_back() {
+    let mut detector = FlashbackDetector::new();
+    for frame in 1..=10 {
+        assert_eq!(
+            observe(&mut detector, &at("lap-zeroed", frame, frame)),
+            None
+        );
+    }
+    assert_eq!(
+        observe(&mut detector, &at("lap-zeroed", 4, 11)),
+        Some((4, 10))
+    );
+    assert_eq!(observe(&mut detector, &at("lap-zeroed", 5, 12)), None);
+    // the event for the flashback that's already been reported
+    assert_eq!(observe(&mut detector, &flashback(4, 5, 12)), None);
+}
+
+#[test]
+fn rewinds_on_the_event_before_frames_go_back() {
+    let mut detector = FlashbackDetector::new();
+    for frame in 1..=10 {
+        observe(&mut detector, &at("lap-zeroed", frame, frame));
+    }
+    assert_eq!(observe(&mut detector, &flashback(3, 10, 10)), Some((3, 10)));
+    assert_eq!(observe(&mut detector, &at("lap-zeroed", 3, 11)), None);
+}
+
+#[test]
+fn late_datagrams_are_not_flashbacks() {
+    let mut detector = FlashbackDetector::new();
+    for frame in [1, 2, 4, 3, 5] {
+        assert_eq!(
+            observe(&mut detector, &at("lap-zeroed", frame, frame)),
+            None
+        );
+    }
+}
+
+#[tokio::test]
+async fn session_state_drops_events_after_the_flashback() {
+    let mut server = Server::new("127.0.0.1:0").await.unwrap();
+    let state = Arc::new(SessionState::new());
+    server.add_sink(state.clone());
+    let mut subscription = server.subscribe_all();
+    let addr = server.local_addrs().unwrap()[0];
+    tokio::spawn(async move { server.listen().await });
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let datagrams = [
+        at("event-ftlp", 10, 10),
+        at("event-ovtk", 20, 20),
+        at("event-ovtk", 30, 30),
+        at("lap-zeroed", 15, 31),
+    ];
+    for datagram in &datagrams {
+        socket.send_to(datagram, addr).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    let rewind = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if let Some(rewind) = subscription.recv().await.unwrap().rewind {
+                return rewind;
+            }
+        }
+    })
+    .await
+    .expect("timed out waiting for the rewind");
+    assert_eq!((rewind.frame_identifier, rewind.session_time), (15, 1.5));
+    assert_eq!(rewind.from_frame_identifier, 30);
+
+    let codes: Vec<String> = state.read(|s| s.events.iter().map(|e| e.code.clone()).collect());
+    assert_eq!(codes, ["FTLP"]);
+}
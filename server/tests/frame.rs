@@ -0,0 +1,92 @@
+mod common;
+
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use server::{FeedId, Frame, FrameAssembler, Server};
+use telemetry::{FromBytes, Packet};
+use tokio::net::UdpSocket;
+
+use common::packet;
+
+const TIMEOUT: Duration = Duration::from_millis(50);
+
+fn push(assembler: &mut FrameAssembler, name: &str, frame: u32, now: Instant) -> Vec<Frame> {
+    let src: SocketAddr = "192.168.1.20:52344".parse().unwrap();
+    let packet = Packet::from_bytes(&packet(name, frame)).unwrap();
+    assembler.push(FeedId::new(src, &packet), &packet, now)
+}
+
+fn summary(frames: &[Frame]) -> Vec<(u32, bool)> {
+    frames
+        .iter()
+        .map(|frame| (frame.overall_frame_identifier, frame.complete))
+        .collect()
+}
+
+#[test]
+fn frames_complete_once_every_type_arrives() {
+    let mut assembler = FrameAssembler::new(TIMEOUT);
+    let start = Instant::now();
+
+    // nothing is expected of the first frame, so it waits out the timeout
+    for name in ["motion", "lap", "car_telemetry"] {
+        assert!(push(&mut assembler, name, 1, start).is_empty());
+    }
+    assert_eq!(assembler.next_deadline(), Some(start + TIMEOUT));
+    let first = assembler.expire(start + TIMEOUT);
+    assert_eq!(summary(&first), [(1, false)]);
+    assert!(first[0].motion.is_some() && first[0].lap.is_some());
+
+    assert!(push(&mut assembler, "motion", 2, start).is_empty());
+    assert!(push(&mut assembler, "lap", 2, start).is_empty());
+    let second = push(&mut assembler, "car_telemetry", 2, start);
+    assert_eq!(summary(&second), [(2, true)]);
+
+    // frame 3 never gets its lap packet, and is handed out ahead of frame 4
+    push(&mut assembler, "motion", 3, start);
+    push(&mut assembler, "car_telemetry", 3, start);
+    push(&mut assembler, "motion", 4, start);
+    push(&mut assembler, "lap", 4, start);
+    let frames = push(&mut assembler, "car_telemetry", 4, start);
+    assert_eq!(summary(&frames), [(3, false), (4, true)]);
+    assert!(frames[0].lap.is_none());
+
+    assert!(push(&mut assembler, "lap", 3, start).is_empty());
+    assert_eq!(assembler.next_deadline(), None);
+}
+
+#[test]
+fn ignores_packets_not_sent_per_frame() {
+    let mut assembler = FrameAssembler::new(TIMEOUT);
+    let now = Instant::now();
+    assert!(push(&mut assembler, "session", 1, now).is_empty());
+    assert!(assembler.flush().is_empty());
+}
+
+#[tokio::test]
+async fn subscribers_receive_frames() {
+    let server = Server::new("127.0.0.1:0").await.unwrap();
+    let mut frames = server.subscribe_frames(TIMEOUT);
+    let addr = server.local_addrs().unwrap()[0];
+    tokio::spawn(async move { server.listen().await });
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    for frame in 1..=2 {
+        for name in ["motion", "lap", "car_status"] {
+            socket.send_to(&packet(name, frame), addr).await.unwrap();
+        }
+        tokio::time::sleep(TIMEOUT * 2).await;
+    }
+
+    let mut received = Vec::new();
+    for _ in 0..2 {
+        let frame = tokio::time::timeout(Duration::from_secs(5), frames.recv())
+            .await
+            .expect("timed out waiting for a frame")
+            .unwrap();
+        received.push(frame);
+    }
+    assert_eq!(summary(&received), [(1, false), (2, true)]);
+    assert!(received[1].car_status.is_some());
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use prost::Message;
+use server::grpc::proto::telemetry_client::TelemetryClient;
+use server::grpc::proto::{
+    self, GetSessionStateRequest, ListFeedsRequest, PacketType, SubscribeRequest,
+};
+use server::grpc::TelemetryService;
+use server::{Server, SessionState};
+use telemetry::{FromBytes, Packet};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio_stream::wrappers::TcpListenerStream;
+
+use common::{corpus, corpus_dir};
+
+#[test]
+fn packets_roundtrip_through_protobuf() {
+    for entry in std::fs::read_dir(corpus_dir()).unwrap() {
+        let path = entry.unwrap().path();
+        let Ok(packet) = Packet::from_bytes(&std::fs::read(&path).unwrap()) else {
+            continue;
+        };
+
+        let encoded = proto::Packet::from(packet).encode_to_vec();
+        let decoded = proto::Packet::decode(encoded.as_slice()).unwrap();
+        let roundtripped = Packet::try_from(decoded)
+            .unwrap_or_else(|e| panic!("{} failed to convert back: {e}", path.display()));
+        assert_eq!(packet, roundtripped, "{}", path.display());
+    }
+}
+
+#[tokio::test]
+async fn subscribe_streams_filtered_packets() {
+    let server = Server::new("127.0.0.1:0").await.unwrap();
+    let udp = server.local_addrs().unwrap()[0];
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let grpc = listener.local_addr().unwrap();
+
+    let state = Arc::new(SessionState::new());
+    let service = TelemetryService::new(server.subscriber(), state.clone());
+    let mut server = server;
+    server.add_sink(state);
+    tokio::spawn(
+        tonic::transport::Server::builder()
+            .add_service(service.into_server())
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+    tokio::spawn(async move { server.listen().await });
+
+    let mut client = TelemetryClient::connect(format!("http://{grpc}"))
+        .await
+        .unwrap();
+    let mut stream = client
+        .subscribe(SubscribeRequest {
+            packet_types: vec![PacketType::Event as i32],
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    for name in ["lap-zeroed", "event-ftlp"] {
+        socket.send_to(&corpus(name), udp).await.unwrap();
+    }
+
+    let received = tokio::time::timeout(Duration::from_secs(5), stream.message())
+        .await
+        .expect("timed out waiting for a packet")
+        .unwrap()
+        .unwrap();
+    let Some(proto::packet::Packet::Event(event)) = received.packet.unwrap().packet else {
+        panic!("expected an event packet");
+    };
+    assert_eq!(event.event_string_code, "FTLP");
+    let feed = received.feed;
+    assert!(feed.starts_with(&format!("{}/", socket.local_addr().unwrap())));
+
+    let state = client
+        .get_session_state(GetSessionStateRequest::default())
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(state.feed, feed);
+    assert_eq!(state.events.len(), 1);
+
+    let feeds = client
+        .list_feeds(ListFeedsRequest {})
+        .await
+        .unwrap()
+        .into_inner()
+        .feeds;
+    assert_eq!(feeds.len(), 1);
+    assert_eq!(feeds[0].feed, feed);
+    assert_eq!(feeds[0].packets_by_type["event"], 1);
+
+    let unknown = client
+        .get_session_state(GetSessionStateRequest {
+            feed: "127.0.0.1:1/1".to_string(),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(unknown.code(), tonic::Code::NotFound);
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use std::net::SocketAddr;
+use std::time::SystemTime;
+
+use server::{HealthMonitor, Sink};
+use telemetry::{FromBytes, Packet};
+
+use common::packet;
+
+const TYPES: [&str; 3] = ["motion", "lap", "car_telemetry"];
+
+async fn send(monitor: &HealthMonitor, raw: &[u8]) {
+    let src: SocketAddr = "192.168.1.20:52344".parse().unwrap();
+    let packet = Packet::from_bytes(raw).unwrap();
+    monitor
+        .handle(src, SystemTime::now(), raw, &packet)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn counts_lost_duplicated_and_reordered_packets() {
+    let monitor = HealthMonitor::new();
+    for frame in 1..=40 {
+        // the whole of frame 20 is lost
+        if frame == 20 {
+            continue;
+        }
+        for name in TYPES {
+            // one lap packet is lost, and frame 30's car telemetry arrives after frame 31
+            if (frame, name) == (10, "lap") || (frame, name) == (30, "car_telemetry") {
+                continue;
+            }
+            send(&monitor, &packet(name, frame)).await;
+        }
+        if frame == 5 {
+            send(&monitor, &packet("motion", frame)).await;
+        }
+        if frame == 31 {
+            send(&monitor, &packet("car_telemetry", 30)).await;
+        }
+    }
+
+    let report = monitor.report();
+    assert_eq!(report.len(), 1);
+    let feed = &report[0];
+    assert_eq!(feed.missing_frames, 1);
+    assert_eq!(feed.duplicates, 1);
+    assert_eq!(feed.out_of_order, 1);
+    assert_eq!(feed.missing, 4);
+
+    let lap = &feed.packets["lap"];
+    assert_eq!((lap.received, lap.missing), (38, 2));
+    assert_eq!(lap.loss_percent, 5.0);
+    assert_eq!(feed.packets["motion"].duplicates, 1);
+    assert_eq!(feed.packets["motion"].missing, 1);
+    assert_eq!(feed.packets["car_telemetry"].out_of_order, 1);
+    assert_eq!(feed.packets["car_telemetry"].missing, 1);
+}
+
+#[tokio::test]
+async fn late_packets_are_not_counted_as_lost() {
+    let monitor = HealthMonitor::new();
+    for frame in 1..=30 {
+        if frame != 5 {
+            send(&monitor, &packet("lap", frame)).await;
+        }
+        send(&monitor, &packet("motion", frame)).await;
+    }
+    // long after its frame stopped waiting for it
+    send(&monitor, &packet("lap", 5)).await;
+
+    let lap = &monitor.report()[0].packets["lap"];
+    assert_eq!((lap.received, lap.missing, lap.out_of_order), (30, 0, 1));
+}
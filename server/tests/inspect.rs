@@ -0,0 +1,98 @@
+mod common;
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use serde_json::Value;
+use server::capture::{CaptureHeader, CaptureRecord, CaptureWriter};
+use server::inspect;
+use server::output::{Format, PacketFilter};
+use telemetry::PacketID;
+
+use common::{corpus, empty_dir, with_uid};
+
+/// A finished capture of a lap, a session and a datagram that doesn't decode, from one sender
+fn capture(path: &Path) {
+    let mut writer =
+        CaptureWriter::new(File::create(path).unwrap(), &CaptureHeader::new(2023, 7)).unwrap();
+    let src = "192.168.1.20:52344".parse().unwrap();
+    for (i, data) in [
+        with_uid(corpus("lap-zeroed"), 7),
+        with_uid(corpus("session-zeroed"), 7),
+        vec![1, 2, 3],
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let record = CaptureRecord {
+            received_at: UNIX_EPOCH + Duration::from_secs(1_700_000_000 + i as u64),
+            src,
+            data,
+        };
+        writer.write_record(&record).unwrap();
+    }
+    writer.finish().unwrap();
+}
+
+fn summary(path: &Path, as_json: bool) -> String {
+    let mut out = Vec::new();
+    inspect::write_summary(&mut out, path, inspect::open(path).unwrap(), as_json).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn summaries_count_packets_sessions_and_errors() {
+    let dir = empty_dir("inspect-summary");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("one.f1cap");
+    capture(&path);
+
+    let text = summary(&path, false);
+    assert!(text.contains("  3 datagrams over 2.0s\n"), "{text}");
+    assert!(
+        text.contains("    0000000000000007  from 192.168.1.20:52344"),
+        "{text}"
+    );
+    assert!(text.contains("  decode errors: 1\n"), "{text}");
+    assert!(!text.contains("never finished"), "{text}");
+
+    let json: Value = serde_json::from_str(&summary(&path, true)).unwrap();
+    assert_eq!(json["records"], 3);
+    assert_eq!(json["duration"], 2.0);
+    assert_eq!(json["packets"]["lap"], 1);
+    assert_eq!(json["sessions"][0]["session_uid"], 7);
+    assert!(json["finished_at"].is_u64());
+
+    let Err(error) = inspect::open(&dir.join("missing.f1cap")) else {
+        panic!("opened a file that isn't there");
+    };
+    assert!(error.to_string().contains("missing.f1cap"), "{error}");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn exports_filtered_packets_and_counts_the_rest() {
+    let dir = empty_dir("inspect-export");
+    std::fs::create_dir_all(&dir).unwrap();
+    let paths: Vec<PathBuf> = ["one.f1cap", "two.f1cap"]
+        .into_iter()
+        .map(|name| dir.join(name))
+        .collect();
+    for path in &paths {
+        capture(path);
+    }
+
+    let mut out = Vec::new();
+    let filter = PacketFilter::new([PacketID::Lap], []);
+    let skipped = inspect::export(&mut out, &paths, Format::Json, &filter).unwrap();
+    assert_eq!(skipped, 2);
+    let lines: Vec<Value> = String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines.iter().all(|line| line["type"] == "lap"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
+
+use server::{EndReason, LifecycleEvent, LifecycleSubscription, SessionInfo, SessionManager, Sink};
+use telemetry::{FromBytes, Packet, ToBytes};
+
+use common::{corpus, with_uid};
+
+/// A session packet for a session of `weekend` in `season`
+fn session(session_uid: u64, weekend: u32, season: u32) -> Vec<u8> {
+    let Ok(Packet::Session(mut data)) = Packet::from_bytes(&corpus("session-zeroed")) else {
+        panic!("corpus session packet doesn't decode");
+    };
+    data.weekend_link_identifier = weekend;
+    data.season_link_identifier = season;
+    data.session_type = 10;
+    with_uid(Packet::Session(data).to_bytes().unwrap(), session_uid)
+}
+
+async fn send(manager: &SessionManager, src: &str, raw: &[u8]) {
+    let src: SocketAddr = src.parse().unwrap();
+    let packet = Packet::from_bytes(raw).unwrap();
+    manager
+        .handle(src, SystemTime::now(), raw, &packet)
+        .await
+        .unwrap();
+}
+
+/// What happened, as `(kind, session uid, end reason)`, until nothing more arrives
+async fn drain(
+    lifecycle: &mut LifecycleSubscription,
+) -> Vec<(&'static str, u64, Option<EndReason>)> {
+    let mut events = Vec::new();
+    while let Ok(event) = tokio::time::timeout(Duration::from_millis(50), lifecycle.recv()).await {
+        let (kind, session) = match event.unwrap() {
+            LifecycleEvent::Started(session) => ("started", session),
+            LifecycleEvent::Identified(session) => ("identified", session),
+            LifecycleEvent::Ended(session) => ("ended", session),
+        };
+        events.push((kind, session.feed.session_uid, session.end_reason));
+    }
+    events
+}
+
+const RIG: &str = "192.168.1.20:52344";
+
+#[tokio::test]
+async fn sessions_start_and_end() {
+    let manager = SessionManager::new();
+    let mut lifecycle = manager.subscribe();
+
+    send(&manager, RIG, &with_uid(corpus("lap-zeroed"), 1)).await;
+    send(&manager, RIG, &session(1, 7, 3)).await;
+    send(&manager, RIG, &with_uid(corpus("event-send"), 1)).await;
+    // stragglers after the end don't start the session again
+    send(&manager, RIG, &with_uid(corpus("lap-zeroed"), 1)).await;
+    assert_eq!(
+        drain(&mut lifecycle).await,
+        [
+            ("started", 1, None),
+            ("identified", 1, None),
+            ("ended", 1, Some(EndReason::SessionEnded)),
+        ]
+    );
+
+    send(&manager, RIG, &with_uid(corpus("event-ssta"), 1)).await;
+    send(&manager, RIG, &with_uid(corpus("lap-zeroed"), 2)).await;
+    send(
+        &manager,
+        RIG,
+        &with_uid(corpus("final_classification-zeroed"), 2),
+    )
+    .await;
+    assert_eq!(
+        drain(&mut lifecycle).await,
+        [
+            ("started", 1, None),
+            ("ended", 1, Some(EndReason::Replaced)),
+            ("started", 2, None),
+            ("ended", 2, Some(EndReason::FinalClassification)),
+        ]
+    );
+    assert!(manager.active().is_empty());
+    assert_eq!(manager.sessions().len(), 3);
+}
+
+#[tokio::test]
+async fn quiet_sessions_time_out() {
+    let manager = SessionManager::new().timeout(Duration::from_millis(10));
+    send(&manager, RIG, &with_uid(corpus("lap-zeroed"), 1)).await;
+    assert!(manager.expire_idle().is_empty());
+
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    let ended: Vec<SessionInfo> = manager.expire_idle();
+    assert_eq!(ended.len(), 1);
+    assert_eq!(ended[0].end_reason, Some(EndReason::TimedOut));
+
+    // coming back starts a new session
+    send(&manager, RIG, &with_uid(corpus("lap-zeroed"), 1)).await;
+    assert_eq!(manager.active().len(), 1);
+}
+
+#[tokio::test]
+async fn sessions_are_grouped_into_weekends_and_seasons() {
+    let manager = SessionManager::new();
+    send(&manager, RIG, &session(1, 7, 3)).await;
+    send(&manager, RIG, &session(2, 7, 3)).await;
+    send(&manager, "192.168.1.21:52344", &session(3, 8, 3)).await;
+    send(&manager, "192.168.1.22:52344", &session(4, 9, 4)).await;
+    // no session packet yet, so no weekend
+    send(
+        &manager,
+        "192.168.1.23:52344",
+        &with_uid(corpus("lap-zeroed"), 5),
+    )
+    .await;
+
+    let weekends = manager.weekends();
+    let ids: Vec<(u32, usize)> = weekends
+        .iter()
+        .map(|weekend| (weekend.weekend_link_identifier, weekend.sessions.len()))
+        .collect();
+    assert_eq!(ids, [(7, 2), (8, 1), (9, 1)]);
+
+    let seasons = manager.seasons();
+    let ids: Vec<(u32, usize)> = seasons
+        .iter()
+        .map(|season| (season.season_link_identifier, season.weekends.len()))
+        .collect();
+    assert_eq!(ids, [(3, 2), (4, 1)]);
+    assert_eq!(seasons[0].weekends[0].sessions[0].session_type, Some(10));
+}
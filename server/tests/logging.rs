@@ -0,0 +1,181 @@
+mod common;
+
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use serde_json::Value;
+use server::logging::{LogFormat, Logger};
+use server::{Destination, Relay, Server, Sink, SinkError};
+use telemetry::Packet;
+use tokio::net::UdpSocket;
+use tracing::level_filters::LevelFilter;
+
+use common::{corpus, received};
+
+/// Log output kept in memory
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Buffer {
+    fn lines(&self) -> Vec<String> {
+        let buf = self.0.lock().unwrap();
+        String::from_utf8_lossy(&buf)
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn json(&self) -> Vec<Value> {
+        self.lines()
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+}
+
+// the tests run the server on their own thread, so a thread's default logger sees everything
+
+#[tokio::test]
+async fn decode_errors_are_rate_limited() {
+    let buffer = Buffer::default();
+    let logger = Logger::with_writer(LevelFilter::INFO, LogFormat::Json, buffer.clone());
+    let _guard = tracing::subscriber::set_default(logger);
+
+    let server = Arc::new(Server::new("127.0.0.1:0").await.unwrap());
+    let addr = server.local_addrs().unwrap()[0];
+    let listening = server.clone();
+    tokio::spawn(async move { listening.listen().await });
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    for _ in 0..5 {
+        socket.send_to(&[0; 3], addr).await.unwrap();
+    }
+    received(&server, 5).await;
+
+    let lines = buffer.json();
+    assert_eq!(lines.len(), 1, "{lines:?}");
+    let line = &lines[0];
+    assert_eq!(line["level"], "WARN");
+    assert_eq!(line["message"], "undecodable datagram");
+    assert_eq!(
+        line["fields"]["src"],
+        socket.local_addr().unwrap().to_string()
+    );
+    assert_eq!(line["fields"]["len"], 3);
+    assert_eq!(line["fields"]["suppressed"], 0);
+    assert_eq!(line["spans"][0]["name"], "listen");
+    assert_eq!(line["spans"][0]["addr"], addr.to_string());
+}
+
+struct Failing;
+
+#[async_trait]
+impl Sink for Failing {
+    fn name(&self) -> &str {
+        "failing"
+    }
+
+    async fn handle(
+        &self,
+        _: SocketAddr,
+        _: SystemTime,
+        _: &[u8],
+        _: &Packet,
+    ) -> Result<(), SinkError> {
+        Err("out of disk".into())
+    }
+}
+
+#[test]
+fn relay_failures_are_rate_limited() {
+    let buffer = Buffer::default();
+    let logger = Logger::with_writer(LevelFilter::INFO, LogFormat::Json, buffer.clone());
+    let _guard = tracing::subscriber::set_default(logger);
+
+    // broadcasting isn't allowed on the relay's socket, so every send fails
+    let destination: SocketAddr = "255.255.255.255:20777".parse().unwrap();
+    let relay = Relay::new(vec![Destination::new(destination)]).unwrap();
+    let src: SocketAddr = "127.0.0.1:52344".parse().unwrap();
+    for _ in 0..5 {
+        relay.forward(src, &corpus("lap-zeroed"));
+    }
+    assert_eq!(relay.destinations()[0].skipped(), 5);
+
+    let lines = buffer.json();
+    assert_eq!(lines.len(), 1, "{lines:?}");
+    assert_eq!(lines[0]["message"], "relay failed");
+    assert_eq!(lines[0]["fields"]["destination"], destination.to_string());
+    assert_eq!(lines[0]["fields"]["suppressed"], 0);
+}
+
+#[tokio::test]
+async fn sink_errors_carry_the_packet() {
+    let buffer = Buffer::default();
+    let logger = Logger::with_writer(LevelFilter::INFO, LogFormat::Json, buffer.clone());
+    let _guard = tracing::subscriber::set_default(logger);
+
+    let mut server = Server::new("127.0.0.1:0").await.unwrap();
+    server.add_sink(Failing);
+    let server = Arc::new(server);
+    let addr = server.local_addrs().unwrap()[0];
+    let listening = server.clone();
+    tokio::spawn(async move { listening.listen().await });
+
+    let mut raw = corpus("lap-zeroed");
+    raw[7..15].copy_from_slice(&42u64.to_le_bytes());
+    raw[19..23].copy_from_slice(&7u32.to_le_bytes());
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.send_to(&raw, addr).await.unwrap();
+    received(&server, 1).await;
+    // the sink runs after the packet is counted
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let lines = buffer.json();
+    let line = lines
+        .iter()
+        .find(|line| line["message"] == "sink failed")
+        .expect("sink failure wasn't logged");
+    assert_eq!(line["level"], "ERROR");
+    assert_eq!(line["fields"]["sink"], "failing");
+    assert_eq!(line["fields"]["error"], "out of disk");
+    let packet = &line["spans"][0];
+    assert_eq!(packet["name"], "packet");
+    assert_eq!(packet["src"], socket.local_addr().unwrap().to_string());
+    assert_eq!(packet["packet_id"], 2);
+    assert_eq!(packet["frame"], 7);
+    assert_eq!(packet["session_uid"], 42);
+    assert_eq!(line["spans"][1]["name"], "listen");
+}
+
+#[test]
+fn text_lines_have_the_level_message_and_fields() {
+    let buffer = Buffer::default();
+    let logger = Logger::with_writer(LevelFilter::WARN, LogFormat::Text, buffer.clone());
+    tracing::subscriber::with_default(logger, || {
+        tracing::info!("left out");
+        let span = tracing::info_span!("listen", addr = "127.0.0.1:20777");
+        let _entered = span.enter();
+        tracing::warn!(src = "192.168.1.20:52344", len = 3, "undecodable datagram");
+        tracing::error!(error = "out of disk", "sink failed");
+    });
+    assert_eq!(
+        buffer.lines(),
+        [
+            "WARN  undecodable datagram src=192.168.1.20:52344 len=3 addr=127.0.0.1:20777",
+            "ERROR sink failed error=\"out of disk\" addr=127.0.0.1:20777",
+        ]
+    );
+}
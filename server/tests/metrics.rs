@@ -0,0 +1,62 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use server::{HealthMonitor, Server, SessionState};
+use tokio::net::UdpSocket;
+
+use common::corpus;
+
+#[tokio::test]
+async fn counts_packets_errors_and_sink_latency() {
+    let mut server = Server::new("127.0.0.1:0").await.unwrap();
+    let state = Arc::new(SessionState::new());
+    let health = Arc::new(HealthMonitor::new());
+    server.add_sink(state.clone());
+    server.add_sink(health.clone());
+    let metrics = server.metrics();
+    let addr = server.local_addrs().unwrap()[0];
+    tokio::spawn(async move { server.listen().await });
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let src = socket.local_addr().unwrap();
+    let lap = corpus("lap-zeroed");
+    for datagram in [&lap, &lap, &corpus("crash-unknown-packet-id")] {
+        socket.send_to(datagram, addr).await.unwrap();
+    }
+
+    let rendered = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let rendered = metrics.render(Some(&health));
+            // the sinks handle packets on their own tasks, so can be behind the counts
+            let handled = ["session state", "health monitor"].iter().all(|sink| {
+                rendered.contains(&format!(
+                    "f1_sink_duration_seconds_count{{sink=\"{sink}\"}} 2"
+                ))
+            });
+            if rendered.contains("f1_datagrams_received_total 3") && handled {
+                return rendered;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("timed out waiting for the datagrams to be counted");
+
+    let lines: Vec<&str> = rendered.lines().collect();
+    for expected in [
+        "f1_packets_decoded_total{type=\"lap\"} 2".to_string(),
+        "f1_decode_errors_total{error=\"invalid_packet_id\"} 1".to_string(),
+        format!("f1_source_packets_total{{src=\"{src}\"}} 3"),
+        "f1_sink_duration_seconds_count{sink=\"session state\"} 2".to_string(),
+        "f1_sink_duration_seconds_bucket{sink=\"health monitor\",le=\"+Inf\"} 2".to_string(),
+        "# TYPE f1_sink_duration_seconds histogram".to_string(),
+    ] {
+        assert!(
+            lines.contains(&expected.as_str()),
+            "missing {expected} in\n{rendered}"
+        );
+    }
+    assert!(rendered.contains("f1_packets_duplicated_total{feed="));
+}
@@ -0,0 +1,213 @@
+mod common;
+
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
+
+use bytes::BytesMut;
+use rumqttc::mqttbytes::v4::{read, ConnAck, ConnectReturnCode, Packet as MqttPacket, PubAck};
+use rumqttc::Publish;
+use serde_json::Value;
+use server::{FeedId, MqttOptions, MqttPublisher, QoS, Rewind, Sink};
+use telemetry::{FromBytes, Packet, PacketID, MAX_CARS};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+use common::corpus;
+
+/// Accepts a single client and passes on everything it publishes, standing in for a local broker
+async fn broker() -> (SocketAddr, mpsc::UnboundedReceiver<Publish>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buf = BytesMut::new();
+        loop {
+            let packet = match read(&mut buf, 1 << 20) {
+                Ok(packet) => packet,
+                Err(_) => {
+                    if stream.read_buf(&mut buf).await.unwrap_or(0) == 0 {
+                        return;
+                    }
+                    continue;
+                }
+            };
+            let mut reply = BytesMut::new();
+            match packet {
+                MqttPacket::Connect(_) => {
+                    ConnAck::new(ConnectReturnCode::Success, false)
+                        .write(&mut reply)
+                        .unwrap();
+                }
+                MqttPacket::Publish(publish) => {
+                    if publish.qos == QoS::AtLeastOnce {
+                        PubAck::new(publish.pkid).write(&mut reply).unwrap();
+                    }
+                    let _ = tx.send(publish);
+                }
+                _ => {}
+            }
+            write(&mut stream, &reply).await;
+        }
+    });
+    (addr, rx)
+}
+
+async fn write(stream: &mut TcpStream, bytes: &[u8]) {
+    if !bytes.is_empty() {
+        stream.write_all(bytes).await.unwrap();
+    }
+}
+
+fn publisher(broker: SocketAddr) -> MqttPublisher {
+    let options = MqttOptions::new("test", broker.ip().to_string(), broker.port());
+    MqttPublisher::new(options)
+}
+
+/// Publishes the corpus packets `names`, returning the feed of the last one
+async fn publish(publisher: &MqttPublisher, names: &[&str]) -> FeedId {
+    let src: SocketAddr = "127.0.0.1:20777".parse().unwrap();
+    let mut feed = None;
+    for name in names {
+        let raw = corpus(name);
+        let packet = Packet::from_bytes(&raw).unwrap();
+        feed = Some(FeedId::new(src, &packet));
+        publisher
+            .handle(src, SystemTime::now(), &raw, &packet)
+            .await
+            .unwrap();
+    }
+    feed.unwrap()
+}
+
+async fn next(rx: &mut mpsc::UnboundedReceiver<Publish>) -> Publish {
+    tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("timed out waiting for a publish")
+        .unwrap()
+}
+
+#[tokio::test]
+async fn publishes_per_car_and_event_topics() {
+    let (addr, mut rx) = broker().await;
+    let publisher = publisher(addr);
+
+    let feed = publish(&publisher, &["car_telemetry-zeroed"]).await;
+    let event_feed = publish(&publisher, &["event-ftlp"]).await;
+
+    let mut topics = Vec::new();
+    // every car, the rest of the car telemetry packet, then the event
+    for _ in 0..MAX_CARS + 2 {
+        let publish = next(&mut rx).await;
+        assert_eq!(publish.qos, QoS::AtMostOnce);
+        assert!(!publish.retain);
+        topics.push((publish.topic, publish.payload));
+    }
+    assert_eq!(
+        topics[3].0,
+        format!("f1/{}/car/3/telemetry", feed.session_uid)
+    );
+    let telemetry: Value = serde_json::from_slice(&topics[3].1).unwrap();
+    assert!(telemetry.get("speed").is_some());
+    assert!(topics
+        .iter()
+        .any(|(topic, _)| *topic == format!("f1/{}/telemetry", feed.session_uid)));
+
+    let (topic, payload) = topics.last().unwrap();
+    assert_eq!(*topic, format!("f1/{}/event/FTLP", event_feed.session_uid));
+    let event: Value = serde_json::from_slice(payload).unwrap();
+    assert!(event["details"].get("FastestLap").is_some());
+    assert_eq!(publisher.published(), MAX_CARS as u64 + 2);
+}
+
+#[tokio::test]
+async fn publishes_only_selected_cars_and_fields() {
+    let (addr, mut rx) = broker().await;
+    let publisher = publisher(addr)
+        .prefix("garage")
+        .qos(QoS::AtLeastOnce)
+        .retain(true)
+        .only([PacketID::CarStatus])
+        .cars([0])
+        .fields(PacketID::CarStatus, ["vehicle_fia_flags"]);
+
+    let feed = publish(&publisher, &["car_telemetry-zeroed", "car_status-zeroed"]).await;
+
+    let publish = next(&mut rx).await;
+    assert_eq!(
+        publish.topic,
+        format!("garage/{}/car/0/status", feed.session_uid)
+    );
+    assert_eq!(publish.qos, QoS::AtLeastOnce);
+    assert!(publish.retain);
+    let status: Value = serde_json::from_slice(&publish.payload).unwrap();
+    assert_eq!(status, serde_json::json!({ "vehicle_fia_flags": 0 }));
+
+    assert_eq!(publisher.published(), 1);
+    assert!(tokio::time::timeout(Duration::from_millis(200), rx.recv())
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn sender_topics_keep_senders_apart() {
+    let (addr, mut rx) = broker().await;
+    let publisher = publisher(addr).sender_topics(true);
+    let feed = publish(&publisher, &["event-ftlp"]).await;
+    let rewind = Rewind {
+        feed,
+        frame_identifier: 10,
+        session_time: 1.0,
+        from_frame_identifier: 20,
+        from_session_time: 2.0,
+        overall_frame_identifier: 21,
+    };
+    publisher.rewind(&rewind).await.unwrap();
+
+    let session = format!("f1/127.0.0.1:20777/{}", feed.session_uid);
+    assert_eq!(next(&mut rx).await.topic, format!("{session}/event/FTLP"));
+    let publish = next(&mut rx).await;
+    assert_eq!(publish.topic, format!("{session}/rewind"));
+    let payload: Value = serde_json::from_slice(&publish.payload).unwrap();
+    assert_eq!(payload["frame_identifier"], 10);
+}
+
+#[tokio::test]
+async fn closing_sends_what_is_queued_then_disconnects() {
+    let (addr, mut rx) = broker().await;
+    let publisher = publisher(addr);
+    let feed = publish(&publisher, &["event-ftlp"]).await;
+
+    tokio::time::timeout(Duration::from_secs(5), publisher.close())
+        .await
+        .expect("timed out closing")
+        .unwrap();
+    assert_eq!(
+        next(&mut rx).await.topic,
+        format!("f1/{}/event/FTLP", feed.session_uid)
+    );
+    // the broker stops once the connection is gone
+    let closed = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("still connected");
+    assert!(closed.is_none());
+}
+
+#[tokio::test]
+async fn closing_gives_up_on_an_unreachable_broker() {
+    // nothing listening once the listener is dropped
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let publisher = publisher(addr).close_timeout(Duration::from_millis(100));
+    publish(&publisher, &["event-ftlp"]).await;
+
+    let closed = tokio::time::timeout(Duration::from_secs(5), publisher.close())
+        .await
+        .expect("waited on the broker past the close timeout");
+    assert!(closed.is_err());
+}
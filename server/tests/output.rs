@@ -0,0 +1,47 @@
+mod common;
+
+use server::output::PacketFilter;
+use telemetry::{FromBytes, Packet, MAX_CARS};
+
+use common::corpus;
+
+fn decode(name: &str) -> Packet {
+    Packet::from_bytes(&corpus(name)).unwrap()
+}
+
+#[test]
+fn events_are_kept_for_the_cars_they_involve() {
+    // a penalty for car 3 involving car 9, car 1 overtaking car 2 and a speed trap for car 2
+    let penalty = decode("event-penl");
+    let overtake = decode("event-ovtk");
+    let speed_trap = decode("event-sptp");
+    let session_started = decode("event-ssta");
+
+    let ninth = PacketFilter::new([], [9]);
+    assert!(ninth.to_json(&penalty).is_some());
+    assert!(ninth.to_json(&overtake).is_none());
+    assert!(ninth.to_json(&speed_trap).is_none());
+    // events that aren't about a car are kept whatever the cars
+    assert!(ninth.to_json(&session_started).is_some());
+
+    let second = PacketFilter::new([], [2]);
+    assert!(second.to_json(&penalty).is_none());
+    assert!(second.to_json(&overtake).is_some());
+    let details = &second.to_json(&speed_trap).unwrap()["event_details"]["SpeedTrap"];
+    assert_eq!(details["vehicle_idx"], 2);
+}
+
+#[test]
+fn cars_listed_twice_are_kept_once() {
+    let filter = PacketFilter::new([], [3, 0, 3]);
+    assert_eq!(filter.cars, [3, 0]);
+
+    let lap = filter.to_json(&decode("lap-zeroed")).unwrap();
+    let cars = lap["lap_data"].as_array().unwrap();
+    assert_eq!(cars.len(), 2);
+    assert!(cars.iter().all(|car| car.is_object()), "{cars:?}");
+    assert_eq!(
+        PacketFilter::new([], 0..MAX_CARS).to_json(&decode("lap-zeroed")),
+        PacketFilter::default().to_json(&decode("lap-zeroed"))
+    );
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use std::path::PathBuf;
+
+use server::config::{Config, ListenerConfig, OutputConfig, RecorderConfig, RelayConfig};
+use server::output::Format;
+use server::{Outputs, Server};
+
+use common::{corpus, empty_dir};
+
+fn relaying_to(addr: &str) -> RelayConfig {
+    RelayConfig {
+        addr: addr.parse().unwrap(),
+        packets: Vec::new(),
+        max_rate: None,
+    }
+}
+
+fn recording_to(dir: PathBuf) -> Option<RecorderConfig> {
+    Some(RecorderConfig {
+        dir,
+        packets: Vec::new(),
+    })
+}
+
+#[tokio::test]
+async fn reloading_takes_every_changed_section() {
+    let dir = empty_dir("outputs-reload");
+    let mut server = Server::new("127.0.0.1:0").await.unwrap();
+    let outputs = Outputs::register(&mut server);
+    let mut current = Config {
+        recorder: recording_to(dir.join("first")),
+        relay: vec![relaying_to("127.0.0.1:20779")],
+        ..Config::default()
+    };
+    outputs.start(&server, &current).unwrap();
+    assert!(dir.join("first").is_dir());
+    let first_relay = server.set_relay(None).unwrap();
+    server.set_relay(Some(first_relay.clone()));
+
+    let new = Config {
+        output: OutputConfig {
+            format: Format::Compact,
+            ..OutputConfig::default()
+        },
+        recorder: recording_to(dir.join("second")),
+        relay: vec![relaying_to("127.0.0.1:20778")],
+        // only the outputs are reloaded, everything else needs a restart
+        websocket: Some(ListenerConfig {
+            bind: "127.0.0.1:8080".parse().unwrap(),
+        }),
+        ..Config::default()
+    };
+    outputs.reload(&server, &mut current, &new).await;
+    assert!(dir.join("second").is_dir());
+    assert!(server.set_relay(None).is_some());
+    // the replaced relay was closed rather than left to whoever still holds it
+    first_relay.forward("127.0.0.1:52344".parse().unwrap(), &corpus("lap-zeroed"));
+    assert_eq!(first_relay.destinations()[0].forwarded(), 0);
+    assert_eq!(
+        current,
+        Config {
+            websocket: None,
+            ..new
+        }
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn outputs_that_fail_to_restart_keep_running_as_they_were() {
+    let dir = empty_dir("outputs-failed-reload");
+    std::fs::create_dir_all(&dir).unwrap();
+    // a file where the new recorder's directory should go
+    std::fs::write(dir.join("file"), b"").unwrap();
+    let mut server = Server::new("127.0.0.1:0").await.unwrap();
+    let outputs = Outputs::register(&mut server);
+    let mut current = Config {
+        recorder: recording_to(dir.join("captures")),
+        ..Config::default()
+    };
+    outputs.start(&server, &current).unwrap();
+
+    let new = Config {
+        output: OutputConfig {
+            enabled: false,
+            ..OutputConfig::default()
+        },
+        recorder: recording_to(dir.join("file").join("captures")),
+        ..Config::default()
+    };
+    outputs.reload(&server, &mut current, &new).await;
+    assert!(!current.output.enabled);
+    assert_eq!(current.recorder, recording_to(dir.join("captures")));
+    std::fs::remove_dir_all(dir).unwrap();
+}
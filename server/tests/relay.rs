@@ -0,0 +1,107 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use server::{Destination, Relay, Server};
+use telemetry::PacketID;
+use tokio::net::UdpSocket;
+
+use common::corpus;
+
+/// The next `count` datagrams `socket` receives
+async fn receive(socket: &UdpSocket, count: usize) -> Vec<Vec<u8>> {
+    let mut buf = vec![0; 2048];
+    let mut datagrams = Vec::new();
+    for _ in 0..count {
+        let len = tokio::time::timeout(Duration::from_secs(5), socket.recv(&mut buf))
+            .await
+            .expect("timed out waiting for a datagram")
+            .unwrap();
+        datagrams.push(buf[..len].to_vec());
+    }
+    datagrams
+}
+
+async fn nothing_more(socket: &UdpSocket) {
+    let mut buf = vec![0; 2048];
+    let received = tokio::time::timeout(Duration::from_millis(100), socket.recv(&mut buf)).await;
+    assert!(received.is_err(), "got an unexpected datagram");
+}
+
+#[tokio::test]
+async fn relays_raw_datagrams_through_each_destinations_filter() {
+    let everything = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let laps = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let limited = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let relay = Arc::new(
+        Relay::new(vec![
+            Destination::new(everything.local_addr().unwrap()),
+            Destination::new(laps.local_addr().unwrap()).only([PacketID::Lap]),
+            Destination::new(limited.local_addr().unwrap()).max_rate(0.1),
+        ])
+        .unwrap(),
+    );
+
+    let server = Server::new("127.0.0.1:0").await.unwrap();
+    assert!(server.set_relay(Some(relay.clone())).is_none());
+    let addr = server.local_addrs().unwrap()[0];
+    tokio::spawn(async move { server.listen().await });
+
+    // an unknown packet ID, so it never decodes
+    let mut undecodable = corpus("lap-zeroed");
+    undecodable[6] = 200;
+    let sent = [
+        undecodable,
+        corpus("motion-zeroed"),
+        corpus("lap-zeroed"),
+        corpus("lap-zeroed"),
+    ];
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    for datagram in &sent {
+        socket.send_to(datagram, addr).await.unwrap();
+    }
+
+    assert_eq!(receive(&everything, 4).await, sent);
+    assert_eq!(receive(&laps, 2).await, &sent[2..]);
+    nothing_more(&laps).await;
+    // one of each type at most, with the undecodable datagram a type of its own
+    assert_eq!(receive(&limited, 3).await, &sent[..3]);
+    nothing_more(&limited).await;
+
+    let destinations = relay.destinations();
+    let counts: Vec<(u64, u64)> = destinations
+        .iter()
+        .map(|destination| (destination.forwarded(), destination.skipped()))
+        .collect();
+    assert_eq!(counts, [(4, 0), (2, 2), (3, 1)]);
+}
+
+#[tokio::test]
+async fn relays_can_be_swapped_while_listening() {
+    let first = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let second = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let relay = |socket: &UdpSocket| {
+        Arc::new(Relay::new(vec![Destination::new(socket.local_addr().unwrap())]).unwrap())
+    };
+
+    let server = Arc::new(Server::new("127.0.0.1:0").await.unwrap());
+    server.set_relay(Some(relay(&first)));
+    let addr = server.local_addrs().unwrap()[0];
+    let listening = server.clone();
+    tokio::spawn(async move { listening.listen().await });
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.send_to(&corpus("lap-zeroed"), addr).await.unwrap();
+    receive(&first, 1).await;
+
+    let previous = server.set_relay(Some(relay(&second))).unwrap();
+    assert_eq!(previous.destinations()[0].forwarded(), 1);
+    socket.send_to(&corpus("lap-zeroed"), addr).await.unwrap();
+    receive(&second, 1).await;
+    nothing_more(&first).await;
+
+    server.set_relay(None);
+    socket.send_to(&corpus("lap-zeroed"), addr).await.unwrap();
+    nothing_more(&second).await;
+}
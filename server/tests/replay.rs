@@ -0,0 +1,218 @@
+mod common;
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use server::capture::{CaptureHeader, CaptureRecord, CaptureWriter};
+use server::replay::{ReplayCommand, ReplayOptions, Replayer, SeekTarget, Speed};
+use telemetry::{Attributes, FromBytes, Packet, PacketID, ToBytes};
+use tokio::net::UdpSocket;
+
+use common::{corpus, empty_dir};
+
+/// A lap packet sent `session_time` seconds into the session, with the player on `lap`
+fn lap(session_time: f32, lap: u8) -> Vec<u8> {
+    let Ok(Packet::Lap(mut data)) = Packet::from_bytes(&corpus("lap-zeroed")) else {
+        panic!("corpus lap packet doesn't decode");
+    };
+    data.header.session_time = session_time;
+    data.lap_data[0].current_lap_num = lap;
+    Packet::Lap(data).to_bytes().unwrap()
+}
+
+fn motion(session_time: f32) -> Vec<u8> {
+    let mut raw = corpus("motion-zeroed");
+    raw[15..19].copy_from_slice(&session_time.to_le_bytes());
+    raw
+}
+
+/// Writes a capture of `datagrams`, each received `interval` after the one before
+fn capture(name: &str, datagrams: &[Vec<u8>], interval: Duration) -> PathBuf {
+    let dir = empty_dir(name);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("capture.f1cap");
+    let file = BufWriter::new(File::create(&path).unwrap());
+    let mut writer = CaptureWriter::new(file, &CaptureHeader::new(2023, 0)).unwrap();
+    let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    for (i, data) in datagrams.iter().enumerate() {
+        let record = CaptureRecord {
+            received_at: start + interval * i as u32,
+            src: "192.168.1.20:52344".parse().unwrap(),
+            data: data.clone(),
+        };
+        writer.write_record(&record).unwrap();
+    }
+    writer.finish().unwrap();
+    path
+}
+
+/// Session times of the next `count` datagrams, or `None` for ones that aren't packets
+async fn receive(socket: &UdpSocket, count: usize) -> Vec<Option<f32>> {
+    let mut buf = vec![0; 2048];
+    let mut times = Vec::new();
+    for _ in 0..count {
+        let len = tokio::time::timeout(Duration::from_secs(5), socket.recv(&mut buf))
+            .await
+            .expect("timed out waiting for a datagram")
+            .unwrap();
+        times.push(
+            Packet::from_bytes(&buf[..len])
+                .ok()
+                .map(|p| p.header().session_time),
+        );
+    }
+    times
+}
+
+/// Replays `path`, returning what the target received
+async fn replay(path: &Path, options: ReplayOptions) -> Vec<Option<f32>> {
+    let target = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let addr: SocketAddr = target.local_addr().unwrap();
+    let sent = Replayer::new(vec![path.to_path_buf()], options)
+        .run(addr)
+        .await
+        .unwrap();
+    receive(&target, sent as usize).await
+}
+
+#[tokio::test]
+async fn timing_is_scaled_by_the_speed() {
+    let datagrams = [lap(1.0, 1), lap(2.0, 1), lap(3.0, 1)];
+    let path = capture("replay-timing", &datagrams, Duration::from_millis(250));
+    let target = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let addr = target.local_addr().unwrap();
+
+    let started = Instant::now();
+    let options = ReplayOptions {
+        speed: Speed::multiplier(2.0).unwrap(),
+        ..ReplayOptions::default()
+    };
+    let sent = Replayer::new(vec![path.to_path_buf()], options)
+        .run(addr)
+        .await
+        .unwrap();
+    let elapsed = started.elapsed();
+    assert_eq!(sent, 3);
+    // 500ms of capture at twice the speed
+    assert!(elapsed >= Duration::from_millis(250), "{elapsed:?}");
+    assert!(elapsed < Duration::from_millis(450), "{elapsed:?}");
+    assert_eq!(receive(&target, 3).await, [Some(1.0), Some(2.0), Some(3.0)]);
+
+    let started = Instant::now();
+    let options = ReplayOptions {
+        speed: Speed::Max,
+        ..ReplayOptions::default()
+    };
+    Replayer::new(vec![path], options).run(addr).await.unwrap();
+    assert!(started.elapsed() < Duration::from_millis(250));
+}
+
+#[test]
+fn speeds_out_of_range_are_refused() {
+    assert_eq!(Speed::multiplier(2.0), Some(Speed::Multiplier(2.0)));
+    assert_eq!(Speed::multiplier(0.1), None);
+    assert_eq!(Speed::multiplier(100.0), None);
+}
+
+#[tokio::test]
+async fn replays_start_from_the_seek_target() {
+    let datagrams = [
+        lap(1.0, 1),
+        motion(2.0),
+        lap(3.0, 2),
+        lap(4.0, 2),
+        lap(5.0, 3),
+    ];
+    let path = capture("replay-seek", &datagrams, Duration::ZERO);
+
+    let options = ReplayOptions {
+        start: Some(SeekTarget::SessionTime(2.5)),
+        ..ReplayOptions::default()
+    };
+    assert_eq!(
+        replay(&path, options).await,
+        [Some(3.0), Some(4.0), Some(5.0)]
+    );
+
+    let options = ReplayOptions {
+        start: Some(SeekTarget::Lap(3)),
+        ..ReplayOptions::default()
+    };
+    assert_eq!(replay(&path, options).await, [Some(5.0)]);
+
+    // seeking while playing
+    let target = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let options = ReplayOptions {
+        speed: Speed::Max,
+        ..ReplayOptions::default()
+    };
+    let mut replayer = Replayer::new(vec![path], options);
+    replayer
+        .handle()
+        .send(ReplayCommand::Seek(SeekTarget::Lap(2)));
+    let sent = replayer.run(target.local_addr().unwrap()).await.unwrap();
+    assert_eq!(
+        receive(&target, sent as usize).await,
+        [Some(3.0), Some(4.0), Some(5.0)]
+    );
+}
+
+#[tokio::test]
+async fn looping_starts_again_until_stopped() {
+    let path = capture("replay-loop", &[lap(1.0, 1), lap(2.0, 1)], Duration::ZERO);
+    let target = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let addr = target.local_addr().unwrap();
+    let options = ReplayOptions {
+        looping: true,
+        ..ReplayOptions::default()
+    };
+    let mut replayer = Replayer::new(vec![path], options);
+    let handle = replayer.handle();
+    let replaying = tokio::spawn(async move { replayer.run(addr).await });
+
+    assert_eq!(
+        receive(&target, 6).await,
+        [
+            Some(1.0),
+            Some(2.0),
+            Some(1.0),
+            Some(2.0),
+            Some(1.0),
+            Some(2.0)
+        ]
+    );
+    assert!(handle.send(ReplayCommand::Stop));
+    let sent = tokio::time::timeout(Duration::from_secs(5), replaying)
+        .await
+        .expect("timed out waiting for the replay to stop")
+        .unwrap()
+        .unwrap();
+    assert!(sent >= 6);
+    assert!(!handle.send(ReplayCommand::Resume));
+}
+
+#[tokio::test]
+async fn only_the_chosen_packet_types_are_sent() {
+    let datagrams = [
+        lap(1.0, 1),
+        motion(2.0),
+        b"not a packet".to_vec(),
+        lap(3.0, 1),
+    ];
+    let path = capture("replay-filter", &datagrams, Duration::ZERO);
+
+    let options = ReplayOptions {
+        packet_ids: Some(vec![u8::from(PacketID::Lap)]),
+        ..ReplayOptions::default()
+    };
+    assert_eq!(replay(&path, options).await, [Some(1.0), Some(3.0)]);
+
+    // without a filter even datagrams that don't decode are sent
+    assert_eq!(
+        replay(&path, ReplayOptions::default()).await,
+        [Some(1.0), Some(2.0), None, Some(3.0)]
+    );
+}
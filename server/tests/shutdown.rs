@@ -0,0 +1,181 @@
+mod common;
+
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use server::capture::CaptureReader;
+use server::grpc::proto::telemetry_client::TelemetryClient;
+use server::grpc::proto::SubscribeRequest;
+use server::grpc::TelemetryService;
+use server::{websocket, Recorder, RecvError, Server, SessionState, Sink, SinkError};
+use telemetry::Packet;
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::sync::oneshot;
+use tokio_stream::wrappers::TcpListenerStream;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::Message;
+
+use common::{corpus, empty_dir};
+
+/// Notes being closed, and nothing else
+#[derive(Default)]
+struct Closing(AtomicBool);
+
+#[async_trait]
+impl Sink for Closing {
+    async fn handle(
+        &self,
+        _: SocketAddr,
+        _: SystemTime,
+        _: &[u8],
+        _: &Packet,
+    ) -> Result<(), SinkError> {
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<(), SinkError> {
+        self.0.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Runs `server` until the returned sender is used or dropped
+fn listen(server: Server) -> (oneshot::Sender<()>, tokio::task::JoinHandle<()>) {
+    let (stop, stopped) = oneshot::channel::<()>();
+    let listening = tokio::spawn(async move {
+        let shutdown = async {
+            let _ = stopped.await;
+        };
+        server.listen_until(shutdown).await.unwrap();
+    });
+    (stop, listening)
+}
+
+#[tokio::test]
+async fn shutting_down_drains_sockets_and_finishes_recordings() {
+    let dir = empty_dir("shutdown-recorder");
+    let server = Server::new("127.0.0.1:0").await.unwrap();
+    server.set_recorder(Some(Arc::new(Recorder::new(&dir).unwrap())));
+    let udp = server.local_addrs().unwrap()[0];
+    let mut subscription = server.subscribe_all();
+
+    // sent before the server even starts, so they're only received by draining the socket
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    for name in ["lap-zeroed", "motion-zeroed", "car_telemetry-zeroed"] {
+        socket.send_to(&corpus(name), udp).await.unwrap();
+    }
+    // recorded too, though it never reaches subscribers
+    socket.send_to(&[1, 2, 3], udp).await.unwrap();
+    let (stop, listening) = listen(server);
+    stop.send(()).unwrap();
+    tokio::time::timeout(Duration::from_secs(5), listening)
+        .await
+        .expect("timed out shutting down")
+        .unwrap();
+
+    for _ in 0..3 {
+        subscription.recv().await.unwrap();
+    }
+    assert_eq!(subscription.recv().await.unwrap_err(), RecvError::Closed);
+
+    let files: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(files.len(), 1);
+    let mut reader = CaptureReader::new(BufReader::new(File::open(&files[0]).unwrap())).unwrap();
+    let records: Vec<_> = reader.by_ref().map(Result::unwrap).collect();
+    assert_eq!(records.len(), 4);
+    assert_eq!(records[3].data, [1, 2, 3]);
+    assert_eq!(reader.footer().unwrap().records, 4);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn clients_are_told_the_server_is_going_away() {
+    let server = Server::new("127.0.0.1:0").await.unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let ws = listener.local_addr().unwrap();
+    let (stop_ws, ws_stopped) = oneshot::channel::<()>();
+    let serving_ws = tokio::spawn(websocket::serve(listener, server.subscriber(), async {
+        let _ = ws_stopped.await;
+    }));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let grpc = listener.local_addr().unwrap();
+    let service = TelemetryService::new(server.subscriber(), Arc::new(SessionState::new()));
+    tokio::spawn(
+        tonic::transport::Server::builder()
+            .add_service(service.into_server())
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+
+    let (stop, listening) = listen(server);
+    let (mut ws_client, _) = connect_async(format!("ws://{ws}/ws")).await.unwrap();
+    let mut grpc_client = TelemetryClient::connect(format!("http://{grpc}"))
+        .await
+        .unwrap();
+    let mut stream = grpc_client
+        .subscribe(SubscribeRequest::default())
+        .await
+        .unwrap()
+        .into_inner();
+
+    stop.send(()).unwrap();
+    stop_ws.send(()).unwrap();
+    listening.await.unwrap();
+
+    let message = tokio::time::timeout(Duration::from_secs(5), ws_client.next())
+        .await
+        .expect("timed out waiting for the close frame")
+        .unwrap()
+        .unwrap();
+    let Message::Close(Some(close)) = message else {
+        panic!("expected a close frame, got {message:?}");
+    };
+    assert_eq!(close.code, CloseCode::Away);
+    tokio::time::timeout(Duration::from_secs(5), serving_ws)
+        .await
+        .expect("WebSocket server still waiting on its client")
+        .unwrap()
+        .unwrap();
+
+    let status = tokio::time::timeout(Duration::from_secs(5), stream.message())
+        .await
+        .expect("timed out waiting for the stream to end")
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::Unavailable);
+}
+
+#[tokio::test]
+async fn a_failing_socket_still_closes_sinks_and_subscriptions() {
+    // connected to a port nobody's listening on, so sending from it gets a port unreachable
+    // back, which fails the next receive
+    let gone = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.connect(gone.local_addr().unwrap()).await.unwrap();
+    drop(gone);
+    socket.send(b"anyone there?").await.unwrap();
+
+    let closing = Arc::new(Closing::default());
+    let mut server = Server::with_sockets(vec![socket]);
+    server.add_sink(closing.clone());
+    let mut subscription = server.subscribe_all();
+
+    let error = tokio::time::timeout(Duration::from_secs(5), server.listen())
+        .await
+        .expect("timed out waiting for the socket to fail")
+        .unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::ConnectionRefused);
+    assert!(closing.0.load(Ordering::Relaxed));
+    assert_eq!(subscription.recv().await.unwrap_err(), RecvError::Closed);
+}
@@ -0,0 +1,247 @@
+mod common;
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use server::{ReloadableSink, Server, Sink, SinkError};
+use telemetry::{FromBytes, Packet};
+use tokio::net::UdpSocket;
+use tokio::sync::oneshot;
+
+use common::corpus;
+
+/// Counts the packets it's handed, taking `delay` over each
+#[derive(Default)]
+struct Counting {
+    handled: AtomicUsize,
+    closed: AtomicBool,
+    delay: Duration,
+}
+
+#[async_trait]
+impl Sink for Counting {
+    fn name(&self) -> &str {
+        "counting"
+    }
+
+    async fn handle(
+        &self,
+        _: SocketAddr,
+        _: SystemTime,
+        _: &[u8],
+        _: &Packet,
+    ) -> Result<(), SinkError> {
+        tokio::time::sleep(self.delay).await;
+        self.handled.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<(), SinkError> {
+        self.closed.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+struct Failing;
+
+#[async_trait]
+impl Sink for Failing {
+    async fn handle(
+        &self,
+        _: SocketAddr,
+        _: SystemTime,
+        _: &[u8],
+        _: &Packet,
+    ) -> Result<(), SinkError> {
+        Err("out of disk".into())
+    }
+}
+
+struct Panicking;
+
+#[async_trait]
+impl Sink for Panicking {
+    async fn handle(
+        &self,
+        _: SocketAddr,
+        _: SystemTime,
+        _: &[u8],
+        _: &Packet,
+    ) -> Result<(), SinkError> {
+        panic!("sink bug")
+    }
+
+    async fn close(&self) -> Result<(), SinkError> {
+        panic!("sink bug")
+    }
+}
+
+/// Never gets anywhere with a packet, or with closing
+struct Stuck;
+
+#[async_trait]
+impl Sink for Stuck {
+    async fn handle(
+        &self,
+        _: SocketAddr,
+        _: SystemTime,
+        _: &[u8],
+        _: &Packet,
+    ) -> Result<(), SinkError> {
+        std::future::pending().await
+    }
+
+    async fn close(&self) -> Result<(), SinkError> {
+        std::future::pending().await
+    }
+}
+
+async fn send_laps(to: SocketAddr, count: usize) {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    for _ in 0..count {
+        socket.send_to(&corpus("lap-zeroed"), to).await.unwrap();
+    }
+}
+
+async fn handled(sink: &Counting, count: usize) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while sink.handled.load(Ordering::Relaxed) < count {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("timed out waiting for the sink");
+}
+
+#[tokio::test]
+async fn failing_sinks_dont_affect_the_others() {
+    let counting = Arc::new(Counting::default());
+    let mut server = Server::new("127.0.0.1:0").await.unwrap();
+    server.add_sink(Failing);
+    server.add_sink(Panicking);
+    server.add_sink(counting.clone());
+    let addr = server.local_addrs().unwrap()[0];
+    let (stop, stopped) = oneshot::channel::<()>();
+    let listening = tokio::spawn(async move {
+        server
+            .listen_until(async {
+                let _ = stopped.await;
+            })
+            .await
+    });
+
+    send_laps(addr, 3).await;
+    handled(&counting, 3).await;
+
+    // a sink panicking on close doesn't keep the others from closing
+    stop.send(()).unwrap();
+    listening.await.unwrap().unwrap();
+    assert!(counting.closed.load(Ordering::Relaxed));
+}
+
+#[tokio::test]
+async fn slow_sinks_dont_hold_up_the_others() {
+    let counting = Arc::new(Counting::default());
+    let mut server = Server::new("127.0.0.1:0").await.unwrap();
+    server.add_sink(Stuck);
+    server.add_sink(counting.clone());
+    let metrics = server.metrics();
+    let addr = server.local_addrs().unwrap()[0];
+    tokio::spawn(async move { server.listen().await });
+
+    send_laps(addr, 5).await;
+    handled(&counting, 5).await;
+    assert!(metrics
+        .render(None)
+        .contains("f1_sink_duration_seconds_count{sink=\"counting\"} 5"));
+}
+
+#[tokio::test]
+async fn shutting_down_waits_for_sinks_to_catch_up() {
+    let slow = Arc::new(Counting {
+        delay: Duration::from_millis(20),
+        ..Counting::default()
+    });
+    let mut server = Server::new("127.0.0.1:0").await.unwrap();
+    server.add_sink(slow.clone());
+    let metrics = server.metrics();
+    let addr = server.local_addrs().unwrap()[0];
+    let (stop, stopped) = oneshot::channel::<()>();
+    let listening = tokio::spawn(async move {
+        server
+            .listen_until(async {
+                let _ = stopped.await;
+            })
+            .await
+    });
+
+    send_laps(addr, 5).await;
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !metrics
+            .render(None)
+            .contains("f1_datagrams_received_total 5\n")
+        {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("timed out waiting for the datagrams");
+    stop.send(()).unwrap();
+    listening.await.unwrap().unwrap();
+
+    assert_eq!(slow.handled.load(Ordering::Relaxed), 5);
+    assert!(slow.closed.load(Ordering::Relaxed));
+}
+
+#[tokio::test]
+async fn shutting_down_gives_up_on_sinks_that_never_close() {
+    let counting = Arc::new(Counting::default());
+    let mut server = Server::new("127.0.0.1:0").await.unwrap();
+    server.add_sink(Stuck);
+    server.add_sink(counting.clone());
+    server.sink_close_timeout(Duration::from_millis(100));
+    let (stop, stopped) = oneshot::channel::<()>();
+    let listening = tokio::spawn(async move {
+        server
+            .listen_until(async {
+                let _ = stopped.await;
+            })
+            .await
+    });
+
+    stop.send(()).unwrap();
+    tokio::time::timeout(Duration::from_secs(5), listening)
+        .await
+        .expect("waited on the stuck sink past the close timeout")
+        .unwrap()
+        .unwrap();
+    assert!(counting.closed.load(Ordering::Relaxed));
+}
+
+#[tokio::test]
+async fn reloadable_sinks_hand_back_the_sink_they_replace() {
+    let reloadable = ReloadableSink::new("reloadable");
+    let first = Arc::new(Counting::default());
+    assert!(reloadable.replace(Some(first.clone())).is_none());
+
+    let src: SocketAddr = "192.168.1.20:52344".parse().unwrap();
+    let raw = corpus("lap-zeroed");
+    let packet = Packet::from_bytes(&raw).unwrap();
+    reloadable
+        .handle(src, SystemTime::now(), &raw, &packet)
+        .await
+        .unwrap();
+
+    let replaced = reloadable.replace(None).unwrap();
+    assert!(reloadable.is_empty());
+    replaced.close().await.unwrap();
+    assert!(first.closed.load(Ordering::Relaxed));
+    reloadable
+        .handle(src, SystemTime::now(), &raw, &packet)
+        .await
+        .unwrap();
+    assert_eq!(first.handled.load(Ordering::Relaxed), 1);
+}
@@ -0,0 +1,168 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use server::{RecvError, Server, SUBSCRIPTION_CAPACITY};
+use telemetry::{Attributes, PacketID, PacketLapData};
+use tokio::net::UdpSocket;
+use tokio::sync::oneshot;
+
+use common::{corpus, received};
+
+fn listen(server: &Arc<Server>) -> oneshot::Sender<()> {
+    let (stop, stopped) = oneshot::channel::<()>();
+    let listening = server.clone();
+    tokio::spawn(async move {
+        listening
+            .listen_until(async {
+                let _ = stopped.await;
+            })
+            .await
+    });
+    stop
+}
+
+#[tokio::test]
+async fn typed_subscriptions_skip_other_packet_types() {
+    let server = Arc::new(Server::new("127.0.0.1:0").await.unwrap());
+    let addr = server.local_addrs().unwrap()[0];
+    let mut laps = server.subscribe::<PacketLapData>();
+    let mut everything = server.subscribe_all();
+    let _stop = listen(&server);
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let mut lap = corpus("lap-zeroed");
+    lap[19..23].copy_from_slice(&7u32.to_le_bytes());
+    for datagram in [corpus("motion-zeroed"), corpus("event-ssta"), lap] {
+        socket.send_to(&datagram, addr).await.unwrap();
+    }
+
+    let received = laps.recv().await.unwrap();
+    assert_eq!(received.src, socket.local_addr().unwrap());
+    assert_eq!(received.feed.src, received.src);
+    assert_eq!({ received.packet.header.frame_identifier }, 7);
+
+    let ids: Vec<PacketID> = [
+        everything.recv().await,
+        everything.recv().await,
+        everything.recv().await,
+    ]
+    .into_iter()
+    .map(|received| received.unwrap().packet.packet_id())
+    .collect();
+    assert_eq!(ids, [PacketID::Motion, PacketID::Event, PacketID::Lap]);
+}
+
+#[tokio::test]
+async fn resubscribing_starts_from_the_next_packet() {
+    let server = Arc::new(Server::new("127.0.0.1:0").await.unwrap());
+    let addr = server.local_addrs().unwrap()[0];
+    let mut first = server.subscribe_all();
+    let _stop = listen(&server);
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket
+        .send_to(&corpus("motion-zeroed"), addr)
+        .await
+        .unwrap();
+    received(&server, 1).await;
+    let mut second = first.resubscribe();
+    assert_eq!(server.subscriber_count(), 2);
+    socket.send_to(&corpus("lap-zeroed"), addr).await.unwrap();
+
+    assert_eq!(
+        first.recv().await.unwrap().packet.packet_id(),
+        PacketID::Motion
+    );
+    assert_eq!(
+        first.recv().await.unwrap().packet.packet_id(),
+        PacketID::Lap
+    );
+    assert_eq!(
+        second.recv().await.unwrap().packet.packet_id(),
+        PacketID::Lap
+    );
+
+    drop(second);
+    assert_eq!(server.subscriber_count(), 1);
+}
+
+#[tokio::test]
+async fn subscribers_that_fall_behind_are_told_how_many_they_missed() {
+    let server = Arc::new(Server::new("127.0.0.1:0").await.unwrap());
+    let addr = server.local_addrs().unwrap()[0];
+    let mut behind = server.subscribe_all();
+    let mut laps = server.subscribe::<PacketLapData>();
+    let stop = listen(&server);
+
+    // in batches, so none are lost to a full socket buffer
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let overflow = 10;
+    let total = SUBSCRIPTION_CAPACITY + overflow;
+    let mut sent = 0;
+    while sent < total {
+        for _ in 0..32.min(total - sent) {
+            socket.send_to(&corpus("lap-zeroed"), addr).await.unwrap();
+            sent += 1;
+        }
+        received(&server, sent as u64).await;
+    }
+
+    assert_eq!(
+        behind.recv().await.unwrap_err(),
+        RecvError::Lagged(overflow as u64)
+    );
+    // carries on from the oldest packet still buffered
+    for _ in 0..SUBSCRIPTION_CAPACITY {
+        behind.recv().await.unwrap();
+    }
+    assert_eq!(
+        laps.recv().await.unwrap_err(),
+        RecvError::Lagged(overflow as u64)
+    );
+    assert_eq!(server.lagged_packets(), 2 * overflow as u64);
+    assert!(server.metrics().render(None).contains(&format!(
+        "f1_subscriber_lagged_packets_total {}\n",
+        2 * overflow
+    )));
+
+    stop.send(()).unwrap();
+    let closed = tokio::time::timeout(Duration::from_secs(5), behind.recv())
+        .await
+        .expect("timed out waiting for the subscription to end");
+    assert_eq!(closed.unwrap_err(), RecvError::Closed);
+}
+
+#[tokio::test]
+async fn subscribing_after_shutdown_ends_straight_away() {
+    let server = Arc::new(Server::new("127.0.0.1:0").await.unwrap());
+    let stop = listen(&server);
+    let mut before = server.subscribe_all();
+    stop.send(()).unwrap();
+    assert_eq!(before.recv().await.unwrap_err(), RecvError::Closed);
+
+    let mut after = server.subscribe_all();
+    assert_eq!(after.recv().await.unwrap_err(), RecvError::Closed);
+    assert_eq!(server.subscriber_count(), 0);
+}
+
+#[tokio::test]
+async fn subscriber_handles_only_count_once_they_subscribe() {
+    let server = Arc::new(Server::new("127.0.0.1:0").await.unwrap());
+    let subscriber = server.subscriber();
+    assert_eq!(server.subscriber_count(), 0);
+
+    let subscription = subscriber.clone().subscribe();
+    assert_eq!(server.subscriber_count(), 1);
+    drop(subscription);
+    assert_eq!(server.subscriber_count(), 0);
+
+    // a handle doesn't keep the server's subscriptions open after it shuts down
+    let stop = listen(&server);
+    let mut before = server.subscribe_all();
+    stop.send(()).unwrap();
+    assert_eq!(before.recv().await.unwrap_err(), RecvError::Closed);
+    let mut after = subscriber.subscribe();
+    assert_eq!(after.recv().await.unwrap_err(), RecvError::Closed);
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use serde_json::Value;
+use server::{websocket, Server};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+use common::corpus;
+
+type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Starts a server with a WebSocket endpoint, returning the UDP and WebSocket addresses
+async fn start() -> (SocketAddr, SocketAddr) {
+    let server = Server::new("127.0.0.1:0").await.unwrap();
+    let udp = server.local_addrs().unwrap()[0];
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let ws = listener.local_addr().unwrap();
+
+    let router = websocket::router(server.subscriber());
+    tokio::spawn(async move { axum::serve(listener, router).await });
+    tokio::spawn(async move { server.listen().await });
+    (udp, ws)
+}
+
+async fn next(client: &mut Client) -> Message {
+    tokio::time::timeout(Duration::from_secs(5), client.next())
+        .await
+        .expect("timed out waiting for a message")
+        .unwrap()
+        .unwrap()
+}
+
+async fn next_json(client: &mut Client) -> Value {
+    match next(client).await {
+        Message::Text(text) => serde_json::from_str(&text).unwrap(),
+        message => panic!("expected a text message, got {message:?}"),
+    }
+}
+
+/// Messages are handled in order, so once the error for a bad filter arrives every filter sent
+/// before it is in place
+async fn sync(client: &mut Client) {
+    client.send(Message::Text("{}}".into())).await.unwrap();
+    assert!(next_json(client).await.get("error").is_some());
+}
+
+async fn send_udp(server: SocketAddr, names: &[&str]) {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    for name in names {
+        socket.send_to(&corpus(name), server).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn json_client_gets_filtered_packets() {
+    let (udp, ws) = start().await;
+    let (mut client, _) = connect_async(format!("ws://{ws}/ws")).await.unwrap();
+
+    let filter = r#"{"packets": ["lap"], "cars": [0, 3]}"#;
+    client.send(Message::Text(filter.into())).await.unwrap();
+    sync(&mut client).await;
+    send_udp(udp, &["car_telemetry-zeroed", "lap-zeroed"]).await;
+
+    let message = next_json(&mut client).await;
+    assert_eq!(message["type"], "lap");
+    assert_eq!(message["cars"], serde_json::json!([0, 3]));
+    assert_eq!(message["packet"]["lap_data"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn msgpack_client_gets_binary_frames() {
+    let (udp, ws) = start().await;
+    let (mut client, _) = connect_async(format!("ws://{ws}/ws?format=msgpack"))
+        .await
+        .unwrap();
+
+    sync(&mut client).await;
+    send_udp(udp, &["session-zeroed"]).await;
+
+    let message: Value = match next(&mut client).await {
+        Message::Binary(bytes) => rmp_serde::from_slice(&bytes).unwrap(),
+        message => panic!("expected a binary message, got {message:?}"),
+    };
+    assert_eq!(message["type"], "session");
+}
+
+#[tokio::test]
+async fn unknown_packet_type_is_rejected() {
+    let (_, ws) = start().await;
+    let (mut client, _) = connect_async(format!("ws://{ws}/ws")).await.unwrap();
+
+    client
+        .send(Message::Text(r#"{"packets": ["pit_wall"]}"#.into()))
+        .await
+        .unwrap();
+    let message = next_json(&mut client).await;
+    assert_eq!(message["error"], "unknown packet type pit_wall");
+}
+
+#[tokio::test]
+async fn client_can_follow_one_sender() {
+    let (udp, ws) = start().await;
+    let (mut client, _) = connect_async(format!("ws://{ws}/ws")).await.unwrap();
+
+    let rig = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let src = rig.local_addr().unwrap();
+    let filter = format!(r#"{{"sources": ["{src}"]}}"#);
+    client.send(Message::Text(filter)).await.unwrap();
+    sync(&mut client).await;
+
+    send_udp(udp, &["session-zeroed"]).await;
+    rig.send_to(&corpus("lap-zeroed"), udp).await.unwrap();
+
+    let message = next_json(&mut client).await;
+    assert_eq!(message["type"], "lap");
+    assert_eq!(message["src"], src.to_string());
+    let session_uid = message["packet"]["header"]["session_uid"].as_u64().unwrap();
+    assert_eq!(message["feed"], format!("{src}/{session_uid}"));
+}
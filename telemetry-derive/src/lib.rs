@@ -0,0 +1,161 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Fields, LitInt};
+
+/// Implements the boilerplate shared by every packet struct.
+///
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// use telemetry::{FromBytes, PacketError, PacketHeader, PacketInfo, ToBytes};
+/// use telemetry_derive::Packet;
+///
+/// #[derive(Debug, Clone, Copy, Serialize, Deserialize, Packet)]
+/// #[packet(id = 13, size = 33)]
+/// #[repr(C, packed)]
+/// pub struct PacketRpmData {
+///     pub header: PacketHeader,
+///     pub engine_rpm: u32,
+/// }
+///
+/// let mut buf = vec![0; PacketRpmData::SIZE];
+/// buf[6] = 13;
+/// let packet = PacketRpmData::from_bytes(&buf)?;
+/// assert_eq!(packet.to_bytes()?, buf);
+/// # Ok::<(), PacketError>(())
+/// ```
+///
+/// Generates `FromBytes`, `ToBytes`, `Attributes` and `PacketInfo`, plus a compile time check that
+/// the struct is exactly `size` bytes. The struct must have a `header: PacketHeader` field, and
+/// `from_bytes` fails with `InvalidPacketID` on bytes whose header is for another packet type.
+///
+/// Packets with a non-trivial wire layout (i.e. the event packet) can pass `manual` to skip
+/// `FromBytes`, `ToBytes` and the size check, and implement those by hand.
+#[proc_macro_derive(Packet, attributes(packet))]
+pub fn derive_packet(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+struct PacketArgs {
+    id: LitInt,
+    size: LitInt,
+    manual: bool,
+}
+
+fn parse_args(input: &DeriveInput) -> syn::Result<PacketArgs> {
+    let mut id = None;
+    let mut size = None;
+    let mut manual = false;
+
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("packet")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("id") {
+                id = Some(meta.value()?.parse::<LitInt>()?);
+            } else if meta.path.is_ident("size") {
+                size = Some(meta.value()?.parse::<LitInt>()?);
+            } else if meta.path.is_ident("manual") {
+                manual = true;
+            } else {
+                return Err(meta.error("expected `id`, `size` or `manual`"));
+            }
+            Ok(())
+        })?;
+    }
+
+    let missing = |name| {
+        syn::Error::new(
+            input.ident.span(),
+            format!("missing #[packet({name} = ..)]"),
+        )
+    };
+    Ok(PacketArgs {
+        id: id.ok_or_else(|| missing("id"))?,
+        size: size.ok_or_else(|| missing("size"))?,
+        manual,
+    })
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let args = parse_args(input)?;
+    let name = &input.ident;
+    let name_str = name.to_string();
+    let PacketArgs { id, size, manual } = args;
+
+    let has_header = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields
+                .named
+                .iter()
+                .any(|f| f.ident.as_ref().is_some_and(|i| i == "header")),
+            _ => false,
+        },
+        _ => {
+            return Err(syn::Error::new(
+                input.span(),
+                "Packet can only be derived for structs",
+            ))
+        }
+    };
+    if !has_header {
+        return Err(syn::Error::new(
+            name.span(),
+            "Packet requires a named `header: PacketHeader` field",
+        ));
+    }
+
+    let info = quote! {
+        impl ::telemetry::PacketInfo for #name {
+            const ID: u8 = #id;
+            const SIZE: usize = #size;
+            const NAME: &'static str = #name_str;
+        }
+
+        impl ::telemetry::Attributes for #name {
+            fn header(&self) -> ::telemetry::PacketHeader {
+                self.header
+            }
+
+            fn packet_id(&self) -> ::telemetry::PacketID {
+                self.header.packet_id.into()
+            }
+        }
+    };
+
+    if manual {
+        return Ok(info);
+    }
+
+    Ok(quote! {
+        #info
+
+        const _: () = assert!(
+            ::std::mem::size_of::<#name>() == #size,
+            concat!(stringify!(#name), " does not match its #[packet(size)]")
+        );
+
+        impl ::telemetry::FromBytes for #name {
+            fn from_bytes(buf: &[u8]) -> Result<Self, ::telemetry::PacketError> {
+                if buf.len() < #size {
+                    return Err(::telemetry::PacketError::BufferTooShort {
+                        expected: #size,
+                        actual: buf.len(),
+                    });
+                }
+                let header = ::telemetry::PacketHeader::peek(buf)?;
+                if header.packet_id != #id {
+                    return Err(::telemetry::PacketError::InvalidPacketID(header.packet_id));
+                }
+                Ok(::telemetry::__private::bincode::deserialize::<#name>(&buf[..#size])?)
+            }
+        }
+
+        impl ::telemetry::ToBytes for #name {
+            fn to_bytes(&self) -> Result<Vec<u8>, ::telemetry::PacketError> {
+                Ok(::telemetry::__private::bincode::serialize(self)?)
+            }
+        }
+    })
+}
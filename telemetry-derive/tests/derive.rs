@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+use telemetry::{Attributes, FromBytes, PacketError, PacketHeader, PacketID, PacketInfo, ToBytes};
+use telemetry_derive::Packet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Packet)]
+#[packet(id = 2, size = 35)]
+#[repr(C, packed)]
+struct PacketGapData {
+    header: PacketHeader,
+    gap_in_ms: [u16; 3],
+}
+
+/// Only gets the info traits, so `from_bytes` is up to us
+#[derive(Debug, Clone, Copy, Packet)]
+#[packet(id = 3, size = 4096, manual)]
+#[repr(C, packed)]
+struct PacketByHand {
+    header: PacketHeader,
+}
+
+fn packet(packet_id: u8, len: usize) -> Vec<u8> {
+    let mut buf = vec![0; len];
+    buf[..2].copy_from_slice(&2023u16.to_le_bytes());
+    buf[6] = packet_id;
+    buf[19..23].copy_from_slice(&9u32.to_le_bytes());
+    buf
+}
+
+#[test]
+fn info_comes_from_the_attribute() {
+    assert_eq!(PacketGapData::ID, 2);
+    assert_eq!(PacketGapData::SIZE, 35);
+    assert_eq!(PacketGapData::NAME, "PacketGapData");
+    assert_eq!(PacketGapData::DESCRIPTOR.short_name(), "gap");
+
+    // `manual` skips the size check, or this wouldn't compile
+    assert_eq!(PacketByHand::SIZE, 4096);
+    assert_eq!(PacketByHand::NAME, "PacketByHand");
+}
+
+#[test]
+fn packets_round_trip() {
+    let mut buf = packet(2, PacketGapData::SIZE);
+    buf[29..35].copy_from_slice(&[1, 0, 2, 0, 3, 0]);
+    // anything after the packet is ignored
+    buf.push(0xff);
+
+    let gaps = PacketGapData::from_bytes(&buf).unwrap();
+    assert_eq!({ gaps.gap_in_ms }, [1, 2, 3]);
+    assert_eq!(gaps.packet_id(), PacketID::Lap);
+    assert_eq!({ gaps.header().frame_identifier }, 9);
+    assert_eq!(gaps.to_bytes().unwrap(), buf[..PacketGapData::SIZE]);
+}
+
+#[test]
+fn short_buffers_are_an_error() {
+    let buf = packet(2, PacketGapData::SIZE - 1);
+    assert!(matches!(
+        PacketGapData::from_bytes(&buf),
+        Err(PacketError::BufferTooShort {
+            expected: 35,
+            actual: 34
+        })
+    ));
+}
+
+#[test]
+fn other_packet_types_are_an_error() {
+    let buf = packet(6, PacketGapData::SIZE);
+    assert!(matches!(
+        PacketGapData::from_bytes(&buf),
+        Err(PacketError::InvalidPacketID(6))
+    ));
+    let buf = packet(200, PacketGapData::SIZE);
+    assert!(matches!(
+        PacketGapData::from_bytes(&buf),
+        Err(PacketError::InvalidPacketID(200))
+    ));
+}
@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use telemetry::{Attributes, FromBytes, PacketCarStatusData, ToBytes, MAX_CARS};
+
+fuzz_target!(|data: &[u8]| {
+    for idx in 0..=MAX_CARS {
+        let _ = PacketCarStatusData::decode_car(data, idx);
+    }
+
+    if let Ok(packet) = PacketCarStatusData::from_bytes(data) {
+        let _ = packet.packet_id();
+
+        let bytes = packet.to_bytes().expect("decoded packet should encode");
+        let again = PacketCarStatusData::from_bytes(&bytes).expect("encoded packet should decode");
+        assert_eq!(bytes, again.to_bytes().unwrap());
+    }
+});
@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use telemetry::{Attributes, FromBytes, PacketEventData, ToBytes};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(packet) = PacketEventData::from_bytes(data) {
+        let _ = packet.packet_id();
+
+        let bytes = packet.to_bytes().expect("decoded packet should encode");
+        let again = PacketEventData::from_bytes(&bytes).expect("encoded packet should decode");
+        assert_eq!(bytes, again.to_bytes().unwrap());
+    }
+});
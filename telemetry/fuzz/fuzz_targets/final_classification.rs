@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use telemetry::{Attributes, FromBytes, PacketFinalClassificationData, ToBytes, MAX_CARS};
+
+fuzz_target!(|data: &[u8]| {
+    for idx in 0..=MAX_CARS {
+        let _ = PacketFinalClassificationData::decode_car(data, idx);
+    }
+
+    if let Ok(packet) = PacketFinalClassificationData::from_bytes(data) {
+        let _ = packet.packet_id();
+
+        let bytes = packet.to_bytes().expect("decoded packet should encode");
+        let again = PacketFinalClassificationData::from_bytes(&bytes)
+            .expect("encoded packet should decode");
+        assert_eq!(bytes, again.to_bytes().unwrap());
+    }
+});
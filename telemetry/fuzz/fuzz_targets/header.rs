@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use telemetry::{Attributes, FromBytes, PacketHeader, ToBytes};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(header) = PacketHeader::peek(data) {
+        let _ = header.packet_id();
+
+        // peek must agree with a full decode; compare bytes as session_time may be NaN
+        let decoded = PacketHeader::from_bytes(data).expect("peeked header should decode");
+        assert_eq!(header.to_bytes().unwrap(), decoded.to_bytes().unwrap());
+        assert_eq!(header.to_bytes().unwrap(), data[..PacketHeader::SIZE]);
+    }
+});
@@ -1,3 +1,11 @@
+// lets `#[derive(Packet)]` refer to `::telemetry` from inside this crate too
+extern crate self as telemetry;
+
 mod packet;
 
 pub use packet::*;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use bincode;
+}
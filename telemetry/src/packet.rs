@@ -16,21 +16,25 @@ mod tyre_sets;
 
 use std::fmt::Display;
 
-pub use car_damage::PacketCarDamageData;
-pub use car_setups::PacketCarSetupData;
-pub use car_status::PacketCarStatusData;
-pub use car_telemetry::PacketCarTelemetryData;
-pub use event::PacketEventData;
-pub use final_classification::PacketFinalClassificationData;
+pub use car_damage::{CarDamageData, PacketCarDamageData};
+pub use car_setups::{CarSetupData, PacketCarSetupData};
+pub use car_status::{CarStatusData, PacketCarStatusData};
+pub use car_telemetry::{CarTelemetryData, PacketCarTelemetryData};
+pub use event::{
+    Buttons, DriveThroughPenaltyServed, EventDataDetails, FastestLap, Flashback, InfringementType,
+    Overtake, PacketEventData, Penalty, PenaltySeverity, PenaltyType, RaceWinner, Retirement,
+    SpeedTrap, StartLights, StopGoPenaltyServed, TeamMateInPits,
+};
+pub use final_classification::{FinalClassificationData, PacketFinalClassificationData};
 pub use header::PacketHeader;
-pub use lap::PacketLapData;
-pub use lobby_info::PacketLobbyInfoData;
-pub use motion::PacketMotionData;
-use motion_ex::PacketMotionExData;
-pub use participants::PacketParticipantsData;
-pub use session::PacketSessionData;
-pub use session_history::PacketSessionHistoryData;
-use tyre_sets::PacketTyreSetData;
+pub use lap::{LapData, PacketLapData};
+pub use lobby_info::{LobbyInfoData, PacketLobbyInfoData};
+pub use motion::{CarMotionData, PacketMotionData};
+pub use motion_ex::PacketMotionExData;
+pub use participants::{PacketParticipantsData, ParticipantData};
+pub use session::{MarshalZone, PacketSessionData, WeatherForecastSample};
+pub use session_history::{LapHistoryData, PacketSessionHistoryData, TyreStintHistoryData};
+pub use tyre_sets::{PacketTyreSetData, TyreSetData};
 
 #[derive(Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
 pub enum PacketID {
@@ -48,6 +52,8 @@ pub enum PacketID {
     SessionHistory,
     TyreSets,
     MotionEx,
+    /// An ID outside the spec, e.g. from a newer game build
+    Unknown(u8),
 }
 
 impl From<u8> for PacketID {
@@ -67,9 +73,29 @@ impl From<u8> for PacketID {
             11 => PacketID::SessionHistory,
             12 => PacketID::TyreSets,
             13 => PacketID::MotionEx,
-            // Something catastrophically wrong has happened if this code path executes
-            // (just saying)
-            _ => panic!("Invalid packet ID"),
+            _ => PacketID::Unknown(val),
+        }
+    }
+}
+
+impl From<PacketID> for u8 {
+    fn from(val: PacketID) -> u8 {
+        match val {
+            PacketID::Motion => 0,
+            PacketID::Session => 1,
+            PacketID::Lap => 2,
+            PacketID::Event => 3,
+            PacketID::Participants => 4,
+            PacketID::CarSetups => 5,
+            PacketID::CarTelemetry => 6,
+            PacketID::CarStatus => 7,
+            PacketID::FinalClassification => 8,
+            PacketID::LobbyInfo => 9,
+            PacketID::CarDamage => 10,
+            PacketID::SessionHistory => 11,
+            PacketID::TyreSets => 12,
+            PacketID::MotionEx => 13,
+            PacketID::Unknown(id) => id,
         }
     }
 }
@@ -101,6 +127,8 @@ pub enum PacketError {
     InvalidPacketID(u8),
     EventCodeOutOfBounds(usize),
     EventDecodeError(),
+    BufferTooShort { expected: usize, actual: usize },
+    CarIndexOutOfBounds(usize),
 }
 
 impl Display for PacketError {
@@ -112,6 +140,14 @@ impl Display for PacketError {
                 write!(f, "Event code of length {} is out of bounds", id)
             }
             PacketError::EventDecodeError() => write!(f, "Failed to decode event data"),
+            PacketError::BufferTooShort { expected, actual } => write!(
+                f,
+                "Buffer of {} bytes is too short, expected at least {}",
+                actual, expected
+            ),
+            PacketError::CarIndexOutOfBounds(idx) => {
+                write!(f, "Car index {} is out of bounds", idx)
+            }
         }
     }
 }
@@ -122,11 +158,141 @@ pub trait FromBytes {
         Self: Sized;
 }
 
+pub trait ToBytes {
+    fn to_bytes(&self) -> Result<Vec<u8>, PacketError>;
+}
+
+/// Static information about a packet type, implemented by `#[derive(Packet)]`
+pub trait PacketInfo {
+    const ID: u8;
+    /// Size of the packet on the wire, in bytes
+    const SIZE: usize;
+    const NAME: &'static str;
+    const DESCRIPTOR: PacketDescriptor = PacketDescriptor {
+        id: Self::ID,
+        size: Self::SIZE,
+        name: Self::NAME,
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketDescriptor {
+    pub id: u8,
+    pub size: usize,
+    pub name: &'static str,
+}
+
+/// Every packet type we can decode, indexed by packet ID
+pub const REGISTRY: [PacketDescriptor; 14] = [
+    PacketMotionData::DESCRIPTOR,
+    PacketSessionData::DESCRIPTOR,
+    PacketLapData::DESCRIPTOR,
+    PacketEventData::DESCRIPTOR,
+    PacketParticipantsData::DESCRIPTOR,
+    PacketCarSetupData::DESCRIPTOR,
+    PacketCarTelemetryData::DESCRIPTOR,
+    PacketCarStatusData::DESCRIPTOR,
+    PacketFinalClassificationData::DESCRIPTOR,
+    PacketLobbyInfoData::DESCRIPTOR,
+    PacketCarDamageData::DESCRIPTOR,
+    PacketSessionHistoryData::DESCRIPTOR,
+    PacketTyreSetData::DESCRIPTOR,
+    PacketMotionExData::DESCRIPTOR,
+];
+
+impl PacketDescriptor {
+    /// Looks up the registry entry for a raw packet ID
+    pub fn for_id(id: u8) -> Option<&'static PacketDescriptor> {
+        REGISTRY.get(id as usize)
+    }
+
+    /// Looks up a registry entry by its short name, e.g. `car_telemetry` or `tyre_sets`.
+    /// Matching ignores case, underscores and a trailing `s`.
+    pub fn for_name(name: &str) -> Option<&'static PacketDescriptor> {
+        let wanted = normalise_name(name);
+        REGISTRY
+            .iter()
+            .find(|descriptor| normalise_name(&descriptor.short_name()) == wanted)
+    }
+
+    /// Snake case name without the `Packet`/`Data` affixes, e.g. `car_telemetry`
+    pub fn short_name(&self) -> String {
+        let name = self.name.strip_prefix("Packet").unwrap_or(self.name);
+        let name = name.strip_suffix("Data").unwrap_or(name);
+        let mut short = String::with_capacity(name.len() + 4);
+        for (i, c) in name.chars().enumerate() {
+            if c.is_ascii_uppercase() && i > 0 {
+                short.push('_');
+            }
+            short.push(c.to_ascii_lowercase());
+        }
+        short
+    }
+}
+
+fn normalise_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .filter(|c| *c != '_')
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    name.strip_suffix('s').unwrap_or(&name).to_string()
+}
+
+// catches the registry falling out of order with the packet IDs
+const _: () = {
+    let mut i = 0;
+    while i < REGISTRY.len() {
+        assert!(REGISTRY[i].id as usize == i);
+        i += 1;
+    }
+};
+
 pub trait Attributes {
     fn header(&self) -> PacketHeader;
     fn packet_id(&self) -> PacketID;
 }
 
+/// Maximum number of cars in a session, i.e. the length of every per-car array
+pub const MAX_CARS: usize = 22;
+
+/// Decodes slot `idx` of the per-car array starting `offset` bytes into a raw `packet_id` packet,
+/// leaving the rest of the buffer untouched.
+fn decode_car<T: serde::de::DeserializeOwned>(
+    buf: &[u8],
+    packet_id: PacketID,
+    offset: usize,
+    idx: usize,
+) -> Result<T, PacketError> {
+    let header = PacketHeader::peek(buf)?;
+    if header.packet_id() != packet_id {
+        return Err(PacketError::InvalidPacketID(header.packet_id));
+    }
+    if idx >= MAX_CARS {
+        return Err(PacketError::CarIndexOutOfBounds(idx));
+    }
+
+    let size = std::mem::size_of::<T>();
+    let start = offset + idx * size;
+    let slot = buf
+        .get(start..start + size)
+        .ok_or(PacketError::BufferTooShort {
+            expected: start + size,
+            actual: buf.len(),
+        })?;
+    // the packed struct's size is only the stride on the wire if every field encodes at its own
+    // size, which the slot being used up exactly shows
+    let mut rest = slot;
+    let car = bincode::deserialize_from(&mut rest)?;
+    debug_assert!(
+        rest.is_empty(),
+        "{} is {} bytes on the wire, not {size}",
+        std::any::type_name::<T>(),
+        size - rest.len()
+    );
+    Ok(car)
+}
+
 // allows usage of `?` operator with `PacketError`
 impl From<Box<bincode::ErrorKind>> for PacketError {
     fn from(e: Box<bincode::ErrorKind>) -> Self {
@@ -136,31 +302,108 @@ impl From<Box<bincode::ErrorKind>> for PacketError {
 
 impl FromBytes for Packet {
     fn from_bytes(buf: &[u8]) -> Result<Packet, PacketError> {
-        let header = PacketHeader::from_bytes(buf)?;
-
-        match PacketID::from(header.packet_id) {
-            PacketID::Motion => Ok(Packet::Motion(PacketMotionData::from_bytes(buf)?)),
-            PacketID::Session => Ok(Packet::Session(PacketSessionData::from_bytes(buf)?)),
-            PacketID::Lap => Ok(Packet::Lap(PacketLapData::from_bytes(buf)?)),
-            PacketID::Event => Ok(Packet::Event(PacketEventData::from_bytes(buf)?)),
-            PacketID::Participants => Ok(Packet::Participants(PacketParticipantsData::from_bytes(
-                buf,
-            )?)),
-            PacketID::CarSetups => Ok(Packet::CarSetups(PacketCarSetupData::from_bytes(buf)?)),
-            PacketID::CarTelemetry => Ok(Packet::CarTelemetry(PacketCarTelemetryData::from_bytes(
-                buf,
-            )?)),
-            PacketID::CarStatus => Ok(Packet::CarStatus(PacketCarStatusData::from_bytes(buf)?)),
-            PacketID::FinalClassification => Ok(Packet::FinalClassification(
-                PacketFinalClassificationData::from_bytes(buf)?,
-            )),
-            PacketID::LobbyInfo => Ok(Packet::LobbyInfo(PacketLobbyInfoData::from_bytes(buf)?)),
-            PacketID::CarDamage => Ok(Packet::CarDamage(PacketCarDamageData::from_bytes(buf)?)),
-            PacketID::SessionHistory => Ok(Packet::SessionHistory(
-                PacketSessionHistoryData::from_bytes(buf)?,
-            )),
-            PacketID::TyreSets => Ok(Packet::TyreSets(PacketTyreSetData::from_bytes(buf)?)),
-            PacketID::MotionEx => Ok(Packet::MotionEx(PacketMotionExData::from_bytes(buf)?)),
+        let header = PacketHeader::peek(buf)?;
+        let decoded = decode(&header, buf);
+        if let Err(e) = &decoded {
+            let (session_uid, frame) = (header.session_uid, header.frame_identifier);
+            tracing::trace!(
+                packet_id = header.packet_id,
+                len = buf.len(),
+                session_uid,
+                frame,
+                error = %e,
+                "packet failed to decode"
+            );
+        }
+        decoded
+    }
+}
+
+/// Decodes the packet `header` says `buf` holds
+fn decode(header: &PacketHeader, buf: &[u8]) -> Result<Packet, PacketError> {
+    match header.packet_id() {
+        PacketID::Motion => Ok(Packet::Motion(PacketMotionData::from_bytes(buf)?)),
+        PacketID::Session => Ok(Packet::Session(PacketSessionData::from_bytes(buf)?)),
+        PacketID::Lap => Ok(Packet::Lap(PacketLapData::from_bytes(buf)?)),
+        PacketID::Event => Ok(Packet::Event(PacketEventData::from_bytes(buf)?)),
+        PacketID::Participants => Ok(Packet::Participants(PacketParticipantsData::from_bytes(
+            buf,
+        )?)),
+        PacketID::CarSetups => Ok(Packet::CarSetups(PacketCarSetupData::from_bytes(buf)?)),
+        PacketID::CarTelemetry => Ok(Packet::CarTelemetry(PacketCarTelemetryData::from_bytes(
+            buf,
+        )?)),
+        PacketID::CarStatus => Ok(Packet::CarStatus(PacketCarStatusData::from_bytes(buf)?)),
+        PacketID::FinalClassification => Ok(Packet::FinalClassification(
+            PacketFinalClassificationData::from_bytes(buf)?,
+        )),
+        PacketID::LobbyInfo => Ok(Packet::LobbyInfo(PacketLobbyInfoData::from_bytes(buf)?)),
+        PacketID::CarDamage => Ok(Packet::CarDamage(PacketCarDamageData::from_bytes(buf)?)),
+        PacketID::SessionHistory => Ok(Packet::SessionHistory(
+            PacketSessionHistoryData::from_bytes(buf)?,
+        )),
+        PacketID::TyreSets => Ok(Packet::TyreSets(PacketTyreSetData::from_bytes(buf)?)),
+        PacketID::MotionEx => Ok(Packet::MotionEx(PacketMotionExData::from_bytes(buf)?)),
+        PacketID::Unknown(id) => Err(PacketError::InvalidPacketID(id)),
+    }
+}
+
+/// Borrows one specific packet type out of a [`Packet`]
+pub trait FromPacket {
+    fn from_packet(packet: &Packet) -> Option<&Self>;
+}
+
+macro_rules! impl_from_packet {
+    ($($variant:ident => $ty:ty),* $(,)?) => {
+        $(
+            impl FromPacket for $ty {
+                fn from_packet(packet: &Packet) -> Option<&Self> {
+                    match packet {
+                        Packet::$variant(data) => Some(data),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_from_packet! {
+    Header => PacketHeader,
+    Motion => PacketMotionData,
+    Session => PacketSessionData,
+    Lap => PacketLapData,
+    Event => PacketEventData,
+    Participants => PacketParticipantsData,
+    CarSetups => PacketCarSetupData,
+    CarTelemetry => PacketCarTelemetryData,
+    CarStatus => PacketCarStatusData,
+    FinalClassification => PacketFinalClassificationData,
+    LobbyInfo => PacketLobbyInfoData,
+    CarDamage => PacketCarDamageData,
+    SessionHistory => PacketSessionHistoryData,
+    TyreSets => PacketTyreSetData,
+    MotionEx => PacketMotionExData,
+}
+
+impl ToBytes for Packet {
+    fn to_bytes(&self) -> Result<Vec<u8>, PacketError> {
+        match self {
+            Packet::Header(header) => header.to_bytes(),
+            Packet::Motion(data) => data.to_bytes(),
+            Packet::Session(data) => data.to_bytes(),
+            Packet::Lap(data) => data.to_bytes(),
+            Packet::Event(data) => data.to_bytes(),
+            Packet::Participants(data) => data.to_bytes(),
+            Packet::CarSetups(data) => data.to_bytes(),
+            Packet::CarTelemetry(data) => data.to_bytes(),
+            Packet::CarStatus(data) => data.to_bytes(),
+            Packet::FinalClassification(data) => data.to_bytes(),
+            Packet::LobbyInfo(data) => data.to_bytes(),
+            Packet::CarDamage(data) => data.to_bytes(),
+            Packet::SessionHistory(data) => data.to_bytes(),
+            Packet::TyreSets(data) => data.to_bytes(),
+            Packet::MotionEx(data) => data.to_bytes(),
         }
     }
 }
@@ -168,7 +411,7 @@ impl FromBytes for Packet {
 impl Attributes for Packet {
     fn header(&self) -> PacketHeader {
         match self {
-            Packet::Header(header) => header.clone(),
+            Packet::Header(header) => *header,
             Packet::Motion(data) => data.header(),
             Packet::Session(data) => data.header(),
             Packet::Lap(data) => data.header(),
@@ -1,4 +1,4 @@
-use super::{Attributes, FromBytes};
+use telemetry_derive::Packet;
 
 /// # Car Damage Packet
 ///
@@ -7,7 +7,8 @@ use super::{Attributes, FromBytes};
 /// Frequency: 10 per second  
 /// Size: 953 bytes  
 /// Version: 1
-#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize, Packet)]
+#[packet(id = 10, size = 953)]
 #[repr(C, packed)]
 pub struct PacketCarDamageData {
     /// Header
@@ -16,23 +17,15 @@ pub struct PacketCarDamageData {
     pub car_damage_data: [CarDamageData; 22],
 }
 
-impl FromBytes for PacketCarDamageData {
-    fn from_bytes(buf: &[u8]) -> Result<Self, super::PacketError> {
-        let cursor = std::io::Cursor::new(buf);
-        match bincode::deserialize_from(cursor) {
-            Ok(packet) => Ok(packet),
-            Err(e) => Err(e.into()),
-        }
-    }
-}
-
-impl Attributes for PacketCarDamageData {
-    fn header(&self) -> super::PacketHeader {
-        self.header.clone()
-    }
-
-    fn packet_id(&self) -> super::PacketID {
-        self.header().packet_id.into()
+impl PacketCarDamageData {
+    /// Decodes the damage of car `idx` straight from a raw packet, skipping the other cars
+    pub fn decode_car(buf: &[u8], idx: usize) -> Result<CarDamageData, super::PacketError> {
+        super::decode_car(
+            buf,
+            super::PacketID::CarDamage,
+            super::PacketHeader::SIZE,
+            idx,
+        )
     }
 }
 
@@ -1,4 +1,4 @@
-use super::FromBytes;
+use telemetry_derive::Packet;
 
 /// # Car Setups packet
 ///
@@ -9,38 +9,31 @@ use super::FromBytes;
 /// Frequency: 2 per second  
 /// Size: 1107 bytes  
 /// Version: 1
-#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize, Packet)]
+#[packet(id = 5, size = 1107)]
 #[repr(C, packed)]
 pub struct PacketCarSetupData {
     /// Header
     pub header: super::PacketHeader,
     /// Data for all cars on track
-    car_setups: [CarSetupData; 22],
-}
-
-impl FromBytes for PacketCarSetupData {
-    fn from_bytes(buf: &[u8]) -> Result<Self, super::PacketError> {
-        let cursor = std::io::Cursor::new(buf);
-        match bincode::deserialize_from::<_, PacketCarSetupData>(cursor) {
-            Ok(packet) => Ok(packet),
-            Err(e) => Err(e.into()),
-        }
-    }
+    pub car_setups: [CarSetupData; 22],
 }
 
-impl super::Attributes for PacketCarSetupData {
-    fn header(&self) -> super::PacketHeader {
-        self.header.clone()
-    }
-
-    fn packet_id(&self) -> super::PacketID {
-        self.header().packet_id.into()
+impl PacketCarSetupData {
+    /// Decodes the setup of car `idx` straight from a raw packet, skipping the other cars
+    pub fn decode_car(buf: &[u8], idx: usize) -> Result<CarSetupData, super::PacketError> {
+        super::decode_car(
+            buf,
+            super::PacketID::CarSetups,
+            super::PacketHeader::SIZE,
+            idx,
+        )
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
 #[repr(C, packed)]
-struct CarSetupData {
+pub struct CarSetupData {
     /// Front wing aero
     pub front_wing: u8,
     /// Rear wing aero
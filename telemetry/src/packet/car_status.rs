@@ -1,4 +1,4 @@
-use super::{Attributes, FromBytes, PacketID};
+use telemetry_derive::Packet;
 
 /// # Car Status Packet
 ///
@@ -7,32 +7,25 @@ use super::{Attributes, FromBytes, PacketID};
 /// Frequency: Rate as specified in menus  
 /// Size: 1239 bytes  
 /// Version: 1  
-#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize, Packet)]
+#[packet(id = 7, size = 1239)]
 #[repr(C, packed)]
 pub struct PacketCarStatusData {
     /// Header
-    header: super::PacketHeader,
+    pub header: super::PacketHeader,
     /// Status data for all cars
-    car_status_data: [CarStatusData; 22],
-}
-
-impl FromBytes for PacketCarStatusData {
-    fn from_bytes(buf: &[u8]) -> Result<Self, super::PacketError> {
-        let cursor = std::io::Cursor::new(buf);
-        match bincode::deserialize_from(cursor) {
-            Ok(packet) => Ok(packet),
-            Err(e) => Err(e.into()),
-        }
-    }
+    pub car_status_data: [CarStatusData; 22],
 }
 
-impl Attributes for PacketCarStatusData {
-    fn header(&self) -> super::PacketHeader {
-        self.header.clone()
-    }
-
-    fn packet_id(&self) -> super::PacketID {
-        PacketID::from(self.header().packet_id)
+impl PacketCarStatusData {
+    /// Decodes the status of car `idx` straight from a raw packet, skipping the other cars
+    pub fn decode_car(buf: &[u8], idx: usize) -> Result<CarStatusData, super::PacketError> {
+        super::decode_car(
+            buf,
+            super::PacketID::CarStatus,
+            super::PacketHeader::SIZE,
+            idx,
+        )
     }
 }
 
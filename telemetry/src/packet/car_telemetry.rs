@@ -1,4 +1,7 @@
-#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+use telemetry_derive::Packet;
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize, Packet)]
+#[packet(id = 6, size = 1352)]
 #[repr(C, packed)]
 pub struct PacketCarTelemetryData {
     /// Header
@@ -20,23 +23,15 @@ pub struct PacketCarTelemetryData {
     pub suggested_gear: i8,
 }
 
-impl super::FromBytes for PacketCarTelemetryData {
-    fn from_bytes(buf: &[u8]) -> Result<Self, super::PacketError> {
-        let cursor = std::io::Cursor::new(buf);
-        match bincode::deserialize_from::<_, PacketCarTelemetryData>(cursor) {
-            Ok(packet) => Ok(packet),
-            Err(e) => Err(e.into()),
-        }
-    }
-}
-
-impl super::Attributes for PacketCarTelemetryData {
-    fn header(&self) -> super::PacketHeader {
-        self.header.clone()
-    }
-
-    fn packet_id(&self) -> super::PacketID {
-        self.header.packet_id.into()
+impl PacketCarTelemetryData {
+    /// Decodes the telemetry of car `idx` straight from a raw packet, skipping the other cars
+    pub fn decode_car(buf: &[u8], idx: usize) -> Result<CarTelemetryData, super::PacketError> {
+        super::decode_car(
+            buf,
+            super::PacketID::CarTelemetry,
+            super::PacketHeader::SIZE,
+            idx,
+        )
     }
 }
 
@@ -1,6 +1,7 @@
-use bincode::deserialize_from;
+use bincode::{deserialize_from, serialize};
+use telemetry_derive::Packet;
 
-use super::{Attributes, FromBytes, PacketError, PacketHeader};
+use super::{FromBytes, PacketError, PacketHeader, PacketInfo, ToBytes};
 
 /// Event Packet
 ///
@@ -9,7 +10,8 @@ use super::{Attributes, FromBytes, PacketError, PacketHeader};
 /// Frequency: When the event occurs  
 /// Size: 45 bytes  
 /// Version: 1
-#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize, Packet)]
+#[packet(id = 3, size = 45, manual)]
 #[repr(C, packed)]
 pub struct PacketEventData {
     pub header: PacketHeader,
@@ -17,27 +19,29 @@ pub struct PacketEventData {
     pub event_details: EventDataDetails,
 }
 
-impl Attributes for PacketEventData {
-    fn header(&self) -> crate::packet::PacketHeader {
-        self.header.clone()
-    }
-
-    fn packet_id(&self) -> crate::packet::PacketID {
-        self.header().packet_id.into()
-    }
-}
-
 impl FromBytes for PacketEventData {
     fn from_bytes(buf: &[u8]) -> Result<Self, crate::packet::PacketError> {
+        // the details are a fixed-size union, so anything shorter is a truncated datagram
+        if buf.len() < Self::SIZE {
+            return Err(PacketError::BufferTooShort {
+                expected: Self::SIZE,
+                actual: buf.len(),
+            });
+        }
+        let packet_id = PacketHeader::peek(buf)?.packet_id;
+        if packet_id != Self::ID {
+            return Err(PacketError::InvalidPacketID(packet_id));
+        }
         let mut cursor = std::io::Cursor::new(buf);
 
         let header = bincode::deserialize_from::<_, PacketHeader>(&mut cursor)?;
         let header_size = std::mem::size_of::<PacketHeader>();
 
-        let string_code_buf = &buf[header_size..header_size + 4];
-        let event_string_code: [u8; 4] = string_code_buf
-            .try_into()
-            .map_err(|_| PacketError::EventDecodeError())?;
+        let event_string_code: [u8; 4] = buf
+            .get(header_size..header_size + 4)
+            .and_then(|code| code.try_into().ok())
+            .ok_or(PacketError::EventDecodeError())?;
+        cursor.set_position((header_size + 4) as u64);
 
         let string_code =
             std::str::from_utf8(&event_string_code).map_err(|_| PacketError::EventDecodeError())?;
@@ -83,8 +87,46 @@ impl FromBytes for PacketEventData {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
-#[repr(u8)]
+impl ToBytes for PacketEventData {
+    fn to_bytes(&self) -> Result<Vec<u8>, PacketError> {
+        let mut buf = serialize(&self.header)?;
+        buf.extend_from_slice(&self.event_string_code);
+
+        let details = match self.event_details {
+            EventDataDetails::FastestLap(details) => serialize(&details)?,
+            EventDataDetails::Retirement(details) => serialize(&details)?,
+            EventDataDetails::TeamMateInPits(details) => serialize(&details)?,
+            EventDataDetails::RaceWinner(details) => serialize(&details)?,
+            EventDataDetails::Penalty(details) => serialize(&details)?,
+            EventDataDetails::SpeedTrap(details) => serialize(&details)?,
+            EventDataDetails::StartLights(details) => serialize(&details)?,
+            EventDataDetails::DriveThroughPenaltyServed(details) => serialize(&details)?,
+            EventDataDetails::StopGoPenaltyServed(details) => serialize(&details)?,
+            EventDataDetails::Flashback(details) => serialize(&details)?,
+            EventDataDetails::Buttons(details) => serialize(&details)?,
+            EventDataDetails::Overtake(details) => serialize(&details)?,
+            EventDataDetails::SessionStarted
+            | EventDataDetails::SessionEnded
+            | EventDataDetails::DRSEnabled
+            | EventDataDetails::DRSDisabled
+            | EventDataDetails::ChequeredFlag
+            | EventDataDetails::LightsOut
+            | EventDataDetails::RedFlag => Vec::new(),
+        };
+        buf.extend(details);
+
+        // the details are a union on the wire, so always pad out to the full packet size
+        buf.resize(Self::SIZE, 0);
+        Ok(buf)
+    }
+}
+
+/// Penalty type – see Appendices
+///
+/// Values the game sends that aren't listed here (e.g. from a newer game build)
+/// decode to [`PenaltyType::Unknown`] rather than failing the whole event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(from = "u8", into = "u8")]
 pub enum PenaltyType {
     DriveThrough,
     StopGo,
@@ -98,14 +140,149 @@ pub enum PenaltyType {
     TyreRegulation,
     ThisLapInvalidated,
     ThisAndNextLapInvalidated,
+    ThisLapInvalidatedWithoutReason,
+    ThisAndNextLapInvalidatedWithoutReason,
     ThisAndPreviousLapInvalidated,
     ThisAndPreviousLapInvalidatedWithoutReason,
     Retired,
     BlackFlagTimer,
+    Unknown(u8),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
-#[repr(u8)]
+impl PenaltyType {
+    /// Human readable description, as listed in the appendix
+    pub fn description(&self) -> &'static str {
+        match self {
+            PenaltyType::DriveThrough => "Drive through",
+            PenaltyType::StopGo => "Stop Go",
+            PenaltyType::GridPenalty => "Grid penalty",
+            PenaltyType::PenaltyReminder => "Penalty reminder",
+            PenaltyType::TimePenalty => "Time penalty",
+            PenaltyType::Warning => "Warning",
+            PenaltyType::Disqualified => "Disqualified",
+            PenaltyType::RemovedFromFormationLap => "Removed from formation lap",
+            PenaltyType::ParkedTooLongTimer => "Parked too long timer",
+            PenaltyType::TyreRegulation => "Tyre regulations",
+            PenaltyType::ThisLapInvalidated => "This lap invalidated",
+            PenaltyType::ThisAndNextLapInvalidated => "This and next lap invalidated",
+            PenaltyType::ThisLapInvalidatedWithoutReason => "This lap invalidated without reason",
+            PenaltyType::ThisAndNextLapInvalidatedWithoutReason => {
+                "This and next lap invalidated without reason"
+            }
+            PenaltyType::ThisAndPreviousLapInvalidated => "This and previous lap invalidated",
+            PenaltyType::ThisAndPreviousLapInvalidatedWithoutReason => {
+                "This and previous lap invalidated without reason"
+            }
+            PenaltyType::Retired => "Retired",
+            PenaltyType::BlackFlagTimer => "Black flag timer",
+            PenaltyType::Unknown(_) => "Unknown penalty",
+        }
+    }
+
+    /// How serious the penalty is for the driver it was applied to
+    pub fn severity(&self) -> PenaltySeverity {
+        match self {
+            PenaltyType::DriveThrough | PenaltyType::StopGo | PenaltyType::TimePenalty => {
+                PenaltySeverity::TimePenalty
+            }
+            PenaltyType::GridPenalty | PenaltyType::RemovedFromFormationLap => {
+                PenaltySeverity::Grid
+            }
+            PenaltyType::PenaltyReminder
+            | PenaltyType::ParkedTooLongTimer
+            | PenaltyType::Retired
+            | PenaltyType::BlackFlagTimer => PenaltySeverity::Informational,
+            PenaltyType::Warning | PenaltyType::TyreRegulation => PenaltySeverity::Warning,
+            PenaltyType::Disqualified => PenaltySeverity::Disqualification,
+            PenaltyType::ThisLapInvalidated
+            | PenaltyType::ThisAndNextLapInvalidated
+            | PenaltyType::ThisLapInvalidatedWithoutReason
+            | PenaltyType::ThisAndNextLapInvalidatedWithoutReason
+            | PenaltyType::ThisAndPreviousLapInvalidated
+            | PenaltyType::ThisAndPreviousLapInvalidatedWithoutReason => {
+                PenaltySeverity::LapInvalidated
+            }
+            PenaltyType::Unknown(_) => PenaltySeverity::Unknown,
+        }
+    }
+}
+
+impl From<u8> for PenaltyType {
+    fn from(val: u8) -> PenaltyType {
+        match val {
+            0 => PenaltyType::DriveThrough,
+            1 => PenaltyType::StopGo,
+            2 => PenaltyType::GridPenalty,
+            3 => PenaltyType::PenaltyReminder,
+            4 => PenaltyType::TimePenalty,
+            5 => PenaltyType::Warning,
+            6 => PenaltyType::Disqualified,
+            7 => PenaltyType::RemovedFromFormationLap,
+            8 => PenaltyType::ParkedTooLongTimer,
+            9 => PenaltyType::TyreRegulation,
+            10 => PenaltyType::ThisLapInvalidated,
+            11 => PenaltyType::ThisAndNextLapInvalidated,
+            12 => PenaltyType::ThisLapInvalidatedWithoutReason,
+            13 => PenaltyType::ThisAndNextLapInvalidatedWithoutReason,
+            14 => PenaltyType::ThisAndPreviousLapInvalidated,
+            15 => PenaltyType::ThisAndPreviousLapInvalidatedWithoutReason,
+            16 => PenaltyType::Retired,
+            17 => PenaltyType::BlackFlagTimer,
+            _ => PenaltyType::Unknown(val),
+        }
+    }
+}
+
+impl From<PenaltyType> for u8 {
+    fn from(val: PenaltyType) -> u8 {
+        match val {
+            PenaltyType::DriveThrough => 0,
+            PenaltyType::StopGo => 1,
+            PenaltyType::GridPenalty => 2,
+            PenaltyType::PenaltyReminder => 3,
+            PenaltyType::TimePenalty => 4,
+            PenaltyType::Warning => 5,
+            PenaltyType::Disqualified => 6,
+            PenaltyType::RemovedFromFormationLap => 7,
+            PenaltyType::ParkedTooLongTimer => 8,
+            PenaltyType::TyreRegulation => 9,
+            PenaltyType::ThisLapInvalidated => 10,
+            PenaltyType::ThisAndNextLapInvalidated => 11,
+            PenaltyType::ThisLapInvalidatedWithoutReason => 12,
+            PenaltyType::ThisAndNextLapInvalidatedWithoutReason => 13,
+            PenaltyType::ThisAndPreviousLapInvalidated => 14,
+            PenaltyType::ThisAndPreviousLapInvalidatedWithoutReason => 15,
+            PenaltyType::Retired => 16,
+            PenaltyType::BlackFlagTimer => 17,
+            PenaltyType::Unknown(id) => id,
+        }
+    }
+}
+
+/// Broad classification of a [`PenaltyType`]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize, serde::Serialize,
+)]
+pub enum PenaltySeverity {
+    /// Reminders, timers and retirements - nothing is applied to the driver
+    Informational,
+    /// One or more laps have been invalidated
+    LapInvalidated,
+    Warning,
+    /// Drive through, stop go or time added to the race time
+    TimePenalty,
+    /// Grid places lost, or removed from the formation lap
+    Grid,
+    Disqualification,
+    /// Penalty type not known to this version of the library
+    Unknown,
+}
+
+/// Infringement type – see Appendices
+///
+/// Like [`PenaltyType`], unlisted values decode to [`InfringementType::Unknown`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(from = "u8", into = "u8")]
 pub enum InfringementType {
     BlockingBySlowDriving,
     BlockingByWrongWayDriving,
@@ -162,6 +339,213 @@ pub enum InfringementType {
     IllegalTimeGain,
     MandatoryPistop,
     AttributeAssigned,
+    Unknown(u8),
+}
+
+impl InfringementType {
+    /// Human readable description, as listed in the appendix
+    pub fn description(&self) -> &'static str {
+        match self {
+            InfringementType::BlockingBySlowDriving => "Blocking by slow driving",
+            InfringementType::BlockingByWrongWayDriving => "Blocking by wrong way driving",
+            InfringementType::ReversingOffTheStartLine => "Reversing off the start line",
+            InfringementType::BigCollision => "Big Collision",
+            InfringementType::SmallCollision => "Small Collision",
+            InfringementType::CollisionFailedToHandBackPositionSingle => {
+                "Collision failed to hand back position single"
+            }
+            InfringementType::CollisionFailedToHandBackPositionMultiple => {
+                "Collision failed to hand back position multiple"
+            }
+            InfringementType::CornerCuttingGainedTime => "Corner cutting gained time",
+            InfringementType::CornerCuttingOvertakeSingle => "Corner cutting overtake single",
+            InfringementType::CornerCuttingOvertakeMultiple => "Corner cutting overtake multiple",
+            InfringementType::CrossedPitExitLane => "Crossed pit exit lane",
+            InfringementType::IgnoringBlueFlags => "Ignoring blue flags",
+            InfringementType::IgnoringYellowFlags => "Ignoring yellow flags",
+            InfringementType::IgnoringDriveThrough => "Ignoring drive through",
+            InfringementType::TooManyDriveThroughs => "Too many drive throughs",
+            InfringementType::DriveThroughReminderServeWithinNLaps => {
+                "Drive through reminder serve within n laps"
+            }
+            InfringementType::DriveThroughReminderServeThisLap => {
+                "Drive through reminder serve this lap"
+            }
+            InfringementType::PitLaneSpeeding => "Pit lane speeding",
+            InfringementType::ParkedForTooLong => "Parked for too long",
+            InfringementType::IgnoringTyreRegulations => "Ignoring tyre regulations",
+            InfringementType::TooManyPenalties => "Too many penalties",
+            InfringementType::MultipleWarnings => "Multiple warnings",
+            InfringementType::ApproachingDisqualification => "Approaching disqualification",
+            InfringementType::TyreRegulationsSelectSingle => "Tyre regulations select single",
+            InfringementType::TyreRegulationsSelectMultiple => "Tyre regulations select multiple",
+            InfringementType::LapInvalidatedCornerCutting => "Lap invalidated corner cutting",
+            InfringementType::LapInvalidatedRunningWide => "Lap invalidated running wide",
+            InfringementType::CornerCuttingRanWideGaintedTimeMinor => {
+                "Corner cutting ran wide gained time minor"
+            }
+            InfringementType::CornerCuttingRanWideGaintedTimeSignificant => {
+                "Corner cutting ran wide gained time significant"
+            }
+            InfringementType::CornerCuttingRanWideGaintedTimeExtreme => {
+                "Corner cutting ran wide gained time extreme"
+            }
+            InfringementType::LapInvalidatedWallRiding => "Lap invalidated wall riding",
+            InfringementType::LapInvalidatedFlashbackUsed => "Lap invalidated flashback used",
+            InfringementType::LapInvalidatedResetToTrack => "Lap invalidated reset to track",
+            InfringementType::BlockingThePitlane => "Blocking the pitlane",
+            InfringementType::JumpStart => "Jump start",
+            InfringementType::SafetyCarToCarCollision => "Safety car to car collision",
+            InfringementType::SafetyCarIllegalOvertake => "Safety car illegal overtake",
+            InfringementType::SafetyCarExceedingAllowedPace => "Safety car exceeding allowed pace",
+            InfringementType::VirtualSafetyCarExceedingAllowedPace => {
+                "Virtual safety car exceeding allowed pace"
+            }
+            InfringementType::FormationLapBelowAllowedSpeed => "Formation lap below allowed speed",
+            InfringementType::FormationLapParking => "Formation lap parking",
+            InfringementType::RetiredMechanicalFailure => "Retired mechanical failure",
+            InfringementType::RetiredTerminallyDamaged => "Retired terminally damaged",
+            InfringementType::SafetyCarFallingTooFarBack => "Safety car falling too far back",
+            InfringementType::BlackFlagTimer => "Black flag timer",
+            InfringementType::UnservedStopGoPenalty => "Unserved stop go penalty",
+            InfringementType::UnservedDriveThroughPenalty => "Unserved drive through penalty",
+            InfringementType::EngineComponentChange => "Engine component change",
+            InfringementType::GearboxChange => "Gearbox change",
+            InfringementType::ParcFermeChange => "Parc ferme change",
+            InfringementType::LeagueGridPenalty => "League grid penalty",
+            InfringementType::RetryPenalty => "Retry penalty",
+            InfringementType::IllegalTimeGain => "Illegal time gain",
+            InfringementType::MandatoryPistop => "Mandatory pitstop",
+            InfringementType::AttributeAssigned => "Attribute assigned",
+            InfringementType::Unknown(_) => "Unknown infringement",
+        }
+    }
+}
+
+impl From<u8> for InfringementType {
+    fn from(val: u8) -> InfringementType {
+        match val {
+            0 => InfringementType::BlockingBySlowDriving,
+            1 => InfringementType::BlockingByWrongWayDriving,
+            2 => InfringementType::ReversingOffTheStartLine,
+            3 => InfringementType::BigCollision,
+            4 => InfringementType::SmallCollision,
+            5 => InfringementType::CollisionFailedToHandBackPositionSingle,
+            6 => InfringementType::CollisionFailedToHandBackPositionMultiple,
+            7 => InfringementType::CornerCuttingGainedTime,
+            8 => InfringementType::CornerCuttingOvertakeSingle,
+            9 => InfringementType::CornerCuttingOvertakeMultiple,
+            10 => InfringementType::CrossedPitExitLane,
+            11 => InfringementType::IgnoringBlueFlags,
+            12 => InfringementType::IgnoringYellowFlags,
+            13 => InfringementType::IgnoringDriveThrough,
+            14 => InfringementType::TooManyDriveThroughs,
+            15 => InfringementType::DriveThroughReminderServeWithinNLaps,
+            16 => InfringementType::DriveThroughReminderServeThisLap,
+            17 => InfringementType::PitLaneSpeeding,
+            18 => InfringementType::ParkedForTooLong,
+            19 => InfringementType::IgnoringTyreRegulations,
+            20 => InfringementType::TooManyPenalties,
+            21 => InfringementType::MultipleWarnings,
+            22 => InfringementType::ApproachingDisqualification,
+            23 => InfringementType::TyreRegulationsSelectSingle,
+            24 => InfringementType::TyreRegulationsSelectMultiple,
+            25 => InfringementType::LapInvalidatedCornerCutting,
+            26 => InfringementType::LapInvalidatedRunningWide,
+            27 => InfringementType::CornerCuttingRanWideGaintedTimeMinor,
+            28 => InfringementType::CornerCuttingRanWideGaintedTimeSignificant,
+            29 => InfringementType::CornerCuttingRanWideGaintedTimeExtreme,
+            30 => InfringementType::LapInvalidatedWallRiding,
+            31 => InfringementType::LapInvalidatedFlashbackUsed,
+            32 => InfringementType::LapInvalidatedResetToTrack,
+            33 => InfringementType::BlockingThePitlane,
+            34 => InfringementType::JumpStart,
+            35 => InfringementType::SafetyCarToCarCollision,
+            36 => InfringementType::SafetyCarIllegalOvertake,
+            37 => InfringementType::SafetyCarExceedingAllowedPace,
+            38 => InfringementType::VirtualSafetyCarExceedingAllowedPace,
+            39 => InfringementType::FormationLapBelowAllowedSpeed,
+            40 => InfringementType::FormationLapParking,
+            41 => InfringementType::RetiredMechanicalFailure,
+            42 => InfringementType::RetiredTerminallyDamaged,
+            43 => InfringementType::SafetyCarFallingTooFarBack,
+            44 => InfringementType::BlackFlagTimer,
+            45 => InfringementType::UnservedStopGoPenalty,
+            46 => InfringementType::UnservedDriveThroughPenalty,
+            47 => InfringementType::EngineComponentChange,
+            48 => InfringementType::GearboxChange,
+            49 => InfringementType::ParcFermeChange,
+            50 => InfringementType::LeagueGridPenalty,
+            51 => InfringementType::RetryPenalty,
+            52 => InfringementType::IllegalTimeGain,
+            53 => InfringementType::MandatoryPistop,
+            54 => InfringementType::AttributeAssigned,
+            _ => InfringementType::Unknown(val),
+        }
+    }
+}
+
+impl From<InfringementType> for u8 {
+    fn from(val: InfringementType) -> u8 {
+        match val {
+            InfringementType::BlockingBySlowDriving => 0,
+            InfringementType::BlockingByWrongWayDriving => 1,
+            InfringementType::ReversingOffTheStartLine => 2,
+            InfringementType::BigCollision => 3,
+            InfringementType::SmallCollision => 4,
+            InfringementType::CollisionFailedToHandBackPositionSingle => 5,
+            InfringementType::CollisionFailedToHandBackPositionMultiple => 6,
+            InfringementType::CornerCuttingGainedTime => 7,
+            InfringementType::CornerCuttingOvertakeSingle => 8,
+            InfringementType::CornerCuttingOvertakeMultiple => 9,
+            InfringementType::CrossedPitExitLane => 10,
+            InfringementType::IgnoringBlueFlags => 11,
+            InfringementType::IgnoringYellowFlags => 12,
+            InfringementType::IgnoringDriveThrough => 13,
+            InfringementType::TooManyDriveThroughs => 14,
+            InfringementType::DriveThroughReminderServeWithinNLaps => 15,
+            InfringementType::DriveThroughReminderServeThisLap => 16,
+            InfringementType::PitLaneSpeeding => 17,
+            InfringementType::ParkedForTooLong => 18,
+            InfringementType::IgnoringTyreRegulations => 19,
+            InfringementType::TooManyPenalties => 20,
+            InfringementType::MultipleWarnings => 21,
+            InfringementType::ApproachingDisqualification => 22,
+            InfringementType::TyreRegulationsSelectSingle => 23,
+            InfringementType::TyreRegulationsSelectMultiple => 24,
+            InfringementType::LapInvalidatedCornerCutting => 25,
+            InfringementType::LapInvalidatedRunningWide => 26,
+            InfringementType::CornerCuttingRanWideGaintedTimeMinor => 27,
+            InfringementType::CornerCuttingRanWideGaintedTimeSignificant => 28,
+            InfringementType::CornerCuttingRanWideGaintedTimeExtreme => 29,
+            InfringementType::LapInvalidatedWallRiding => 30,
+            InfringementType::LapInvalidatedFlashbackUsed => 31,
+            InfringementType::LapInvalidatedResetToTrack => 32,
+            InfringementType::BlockingThePitlane => 33,
+            InfringementType::JumpStart => 34,
+            InfringementType::SafetyCarToCarCollision => 35,
+            InfringementType::SafetyCarIllegalOvertake => 36,
+            InfringementType::SafetyCarExceedingAllowedPace => 37,
+            InfringementType::VirtualSafetyCarExceedingAllowedPace => 38,
+            InfringementType::FormationLapBelowAllowedSpeed => 39,
+            InfringementType::FormationLapParking => 40,
+            InfringementType::RetiredMechanicalFailure => 41,
+            InfringementType::RetiredTerminallyDamaged => 42,
+            InfringementType::SafetyCarFallingTooFarBack => 43,
+            InfringementType::BlackFlagTimer => 44,
+            InfringementType::UnservedStopGoPenalty => 45,
+            InfringementType::UnservedDriveThroughPenalty => 46,
+            InfringementType::EngineComponentChange => 47,
+            InfringementType::GearboxChange => 48,
+            InfringementType::ParcFermeChange => 49,
+            InfringementType::LeagueGridPenalty => 50,
+            InfringementType::RetryPenalty => 51,
+            InfringementType::IllegalTimeGain => 52,
+            InfringementType::MandatoryPistop => 53,
+            InfringementType::AttributeAssigned => 54,
+            InfringementType::Unknown(id) => id,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
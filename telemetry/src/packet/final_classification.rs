@@ -1,4 +1,4 @@
-use super::{Attributes, FromBytes};
+use telemetry_derive::Packet;
 
 /// Final Classification Packet
 ///
@@ -9,7 +9,8 @@ use super::{Attributes, FromBytes};
 /// Frequency: Once at the end of a race  
 /// Size: 1020 bytes  
 /// Version: 1
-#[derive(Debug, Copy, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, serde::Serialize, serde::Deserialize, Packet)]
+#[packet(id = 8, size = 1020)]
 #[repr(C, packed)]
 pub struct PacketFinalClassificationData {
     /// Header
@@ -20,23 +21,18 @@ pub struct PacketFinalClassificationData {
     pub classification_data: [FinalClassificationData; 22],
 }
 
-impl FromBytes for PacketFinalClassificationData {
-    fn from_bytes(buf: &[u8]) -> Result<Self, super::PacketError> {
-        let cursor = std::io::Cursor::new(buf);
-        match bincode::deserialize_from(cursor) {
-            Ok(packet) => Ok(packet),
-            Err(e) => Err(e.into()),
-        }
-    }
-}
-
-impl Attributes for PacketFinalClassificationData {
-    fn header(&self) -> super::PacketHeader {
-        self.header.clone()
-    }
-
-    fn packet_id(&self) -> super::PacketID {
-        self.header().packet_id.into()
+impl PacketFinalClassificationData {
+    /// Decodes the final classification of car `idx` straight from a raw packet, skipping the other cars
+    pub fn decode_car(
+        buf: &[u8],
+        idx: usize,
+    ) -> Result<FinalClassificationData, super::PacketError> {
+        super::decode_car(
+            buf,
+            super::PacketID::FinalClassification,
+            super::PacketHeader::SIZE + 1,
+            idx,
+        )
     }
 }
 
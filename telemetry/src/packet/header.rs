@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use super::{Attributes, FromBytes, PacketID};
+use super::{Attributes, FromBytes, PacketError, PacketID, ToBytes};
 
 #[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
 #[repr(C, packed)]
@@ -31,6 +31,47 @@ pub struct PacketHeader {
     pub secondary_player_car_index: u8,
 }
 
+impl PacketHeader {
+    /// Size of the header on the wire, in bytes
+    pub const SIZE: usize = std::mem::size_of::<PacketHeader>();
+
+    /// Reads just the header from the front of a raw packet, without touching the payload.
+    ///
+    /// Cheap enough to route datagrams on; fails if the buffer is shorter than the header
+    /// or the packet ID is not one we know how to decode.
+    pub fn peek(buf: &[u8]) -> Result<PacketHeader, PacketError> {
+        let buf: &[u8; Self::SIZE] = buf
+            .get(..Self::SIZE)
+            .and_then(|b| b.try_into().ok())
+            .ok_or(PacketError::BufferTooShort {
+                expected: Self::SIZE,
+                actual: buf.len(),
+            })?;
+
+        let header = PacketHeader {
+            packet_format: u16::from_le_bytes([buf[0], buf[1]]),
+            game_year: buf[2],
+            game_major_version: buf[3],
+            game_minor_version: buf[4],
+            packet_version: buf[5],
+            packet_id: buf[6],
+            session_uid: u64::from_le_bytes([
+                buf[7], buf[8], buf[9], buf[10], buf[11], buf[12], buf[13], buf[14],
+            ]),
+            session_time: f32::from_le_bytes([buf[15], buf[16], buf[17], buf[18]]),
+            frame_identifier: u32::from_le_bytes([buf[19], buf[20], buf[21], buf[22]]),
+            overall_frame_identifier: u32::from_le_bytes([buf[23], buf[24], buf[25], buf[26]]),
+            player_car_index: buf[27],
+            secondary_player_car_index: buf[28],
+        };
+
+        if let PacketID::Unknown(id) = header.packet_id() {
+            return Err(PacketError::InvalidPacketID(id));
+        }
+        Ok(header)
+    }
+}
+
 impl FromBytes for PacketHeader {
     fn from_bytes(buf: &[u8]) -> Result<PacketHeader, super::PacketError> {
         let cursor = std::io::Cursor::new(buf);
@@ -41,9 +82,15 @@ impl FromBytes for PacketHeader {
     }
 }
 
+impl ToBytes for PacketHeader {
+    fn to_bytes(&self) -> Result<Vec<u8>, PacketError> {
+        Ok(bincode::serialize(self)?)
+    }
+}
+
 impl Attributes for PacketHeader {
     fn header(&self) -> PacketHeader {
-        self.clone()
+        *self
     }
 
     fn packet_id(&self) -> PacketID {
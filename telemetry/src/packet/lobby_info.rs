@@ -1,6 +1,5 @@
 use serde_big_array::BigArray;
-
-use super::{Attributes, FromBytes};
+use telemetry_derive::Packet;
 
 /// # Lobby Info Packet
 ///
@@ -10,7 +9,8 @@ use super::{Attributes, FromBytes};
 /// Frequency: Two every second when in the lobby  
 /// Size: 1218 bytes  
 /// Version: 1
-#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize, Packet)]
+#[packet(id = 9, size = 1218)]
 #[repr(C, packed)]
 pub struct PacketLobbyInfoData {
     /// Header
@@ -21,43 +21,23 @@ pub struct PacketLobbyInfoData {
     pub lobby_players: [LobbyInfoData; 22],
 }
 
-impl FromBytes for PacketLobbyInfoData {
-    fn from_bytes(buf: &[u8]) -> Result<Self, super::PacketError> {
-        let cursor = std::io::Cursor::new(buf);
-        match bincode::deserialize_from(cursor) {
-            Ok(packet) => Ok(packet),
-            Err(e) => Err(e.into()),
-        }
-    }
-}
-
-impl Attributes for PacketLobbyInfoData {
-    fn header(&self) -> super::PacketHeader {
-        self.header.clone()
-    }
-
-    fn packet_id(&self) -> super::PacketID {
-        self.header().packet_id.into()
-    }
-}
-
 #[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
 #[repr(C, packed)]
 pub struct LobbyInfoData {
     /// whether the vehicle is AI (1) or Human (0) controlled
-    ai_controlled: u8,
+    pub ai_controlled: u8,
     /// Team ID - see appendix (255 if no team selected)
-    team_id: u8,
+    pub team_id: u8,
     /// Nationality of the driver
-    nationality: u8,
+    pub nationality: u8,
     /// Platform (1 = Steam, 3 = Playstation, 4 = Xbox, 6 = Origin, 255 = unknown)
-    platform: u8,
+    pub platform: u8,
     /// Name of participant in UTF-8 format - null terminated;
     /// will be truncated with ... (U+2026) if too long
     #[serde(with = "BigArray")]
-    name: [u8; 48],
+    pub name: [u8; 48],
     /// Car number of the player
-    car_number: u8,
+    pub car_number: u8,
     /// 0 = not ready, 1 = ready, 2 = spectating
-    ready_status: u8,
+    pub ready_status: u8,
 }
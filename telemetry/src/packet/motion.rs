@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
+use telemetry_derive::Packet;
 
-use super::{Attributes, FromBytes, PacketError, PacketID};
+use super::{PacketError, PacketID};
 
 /// # Motion Packet
 ///
@@ -12,30 +13,18 @@ use super::{Attributes, FromBytes, PacketError, PacketID};
 /// Frequency: Rate as specified in menus  
 /// Size: 1349 bytes  
 /// Version: 1
-#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, Packet)]
+#[packet(id = 0, size = 1349)]
 #[repr(C, packed)]
 pub struct PacketMotionData {
     pub header: super::header::PacketHeader,
     pub car_motion_data: [CarMotionData; 22],
 }
 
-impl FromBytes for PacketMotionData {
-    fn from_bytes(buf: &[u8]) -> Result<PacketMotionData, PacketError> {
-        let cursor = std::io::Cursor::new(buf);
-        match bincode::deserialize_from::<_, PacketMotionData>(cursor) {
-            Ok(packet) => Ok(packet),
-            Err(e) => Err(e.into()),
-        }
-    }
-}
-
-impl Attributes for PacketMotionData {
-    fn header(&self) -> super::header::PacketHeader {
-        self.header.clone()
-    }
-
-    fn packet_id(&self) -> PacketID {
-        self.header.packet_id.into()
+impl PacketMotionData {
+    /// Decodes the motion data of car `idx` straight from a raw packet, skipping the other cars
+    pub fn decode_car(buf: &[u8], idx: usize) -> Result<CarMotionData, PacketError> {
+        super::decode_car(buf, PacketID::Motion, super::PacketHeader::SIZE, idx)
     }
 }
 
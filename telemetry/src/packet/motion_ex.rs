@@ -1,4 +1,4 @@
-use super::{Attributes, FromBytes};
+use telemetry_derive::Packet;
 
 /// # Motion Ex Packet
 ///
@@ -8,7 +8,8 @@ use super::{Attributes, FromBytes};
 /// Frequency: Rate as specified in menus  
 /// Size: 217 bytes  
 /// Version: 1
-#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize, Packet)]
+#[packet(id = 13, size = 217)]
 #[repr(C, packed)]
 pub struct PacketMotionExData {
     /// Header
@@ -54,23 +55,3 @@ pub struct PacketMotionExData {
     /// Vertical force for each wheel
     pub wheel_vert_force: [f32; 4],
 }
-
-impl FromBytes for PacketMotionExData {
-    fn from_bytes(buf: &[u8]) -> Result<Self, super::PacketError> {
-        let cursor = std::io::Cursor::new(buf);
-        match bincode::deserialize_from(cursor) {
-            Ok(packet) => Ok(packet),
-            Err(e) => Err(e.into()),
-        }
-    }
-}
-
-impl Attributes for PacketMotionExData {
-    fn header(&self) -> super::PacketHeader {
-        self.header.clone()
-    }
-
-    fn packet_id(&self) -> super::PacketID {
-        self.header().packet_id.into()
-    }
-}
@@ -1,4 +1,5 @@
 use serde_big_array::BigArray;
+use telemetry_derive::Packet;
 
 /// # Participants Packet
 ///
@@ -10,7 +11,8 @@ use serde_big_array::BigArray;
 /// Frequency: Every 5 seconds  
 /// Size: 1306 bytes  
 /// Version: 1
-#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize, Packet)]
+#[packet(id = 4, size = 1306)]
 #[repr(C, packed)]
 pub struct PacketParticipantsData {
     /// Header
@@ -20,23 +22,15 @@ pub struct PacketParticipantsData {
     pub participants: [ParticipantData; 22],
 }
 
-impl super::FromBytes for PacketParticipantsData {
-    fn from_bytes(buf: &[u8]) -> Result<Self, super::PacketError> {
-        let cursor = std::io::Cursor::new(buf);
-        match bincode::deserialize_from::<_, PacketParticipantsData>(cursor) {
-            Ok(packet) => Ok(packet),
-            Err(e) => Err(e.into()),
-        }
-    }
-}
-
-impl super::Attributes for PacketParticipantsData {
-    fn header(&self) -> super::PacketHeader {
-        self.header.clone()
-    }
-
-    fn packet_id(&self) -> super::PacketID {
-        self.header.packet_id.into()
+impl PacketParticipantsData {
+    /// Decodes the participant data of car `idx` straight from a raw packet, skipping the other cars
+    pub fn decode_car(buf: &[u8], idx: usize) -> Result<ParticipantData, super::PacketError> {
+        super::decode_car(
+            buf,
+            super::PacketID::Participants,
+            super::PacketHeader::SIZE + 1,
+            idx,
+        )
     }
 }
 
@@ -68,3 +62,12 @@ pub struct ParticipantData {
     /// 1 = Steam, 3 = PlayStation, 4 = Xbox, 6 = Origin, 255 = unknown
     pub platform: u8,
 }
+
+impl ParticipantData {
+    /// The participant's name up to the null terminator
+    pub fn display_name(&self) -> String {
+        let name = self.name;
+        let len = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+        String::from_utf8_lossy(&name[..len]).into_owned()
+    }
+}
@@ -1,5 +1,7 @@
-use super::{Attributes, FromBytes, PacketHeader, PacketID};
 use serde_big_array::BigArray;
+use telemetry_derive::Packet;
+
+use super::PacketHeader;
 
 /// # Session Packet
 ///
@@ -8,7 +10,8 @@ use serde_big_array::BigArray;
 /// Frequency: 2 per second  
 /// Size: 644 bytes  
 /// Version: 1  
-#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize, Packet)]
+#[packet(id = 1, size = 644)]
 #[repr(C, packed)]
 pub struct PacketSessionData {
     /// Packet header information.
@@ -224,26 +227,6 @@ pub struct PacketSessionData {
     pub num_red_flag_periods: u8,
 }
 
-impl FromBytes for PacketSessionData {
-    fn from_bytes(bytes: &[u8]) -> Result<Self, super::PacketError> {
-        let cursor = std::io::Cursor::new(bytes);
-        match bincode::deserialize_from::<_, PacketSessionData>(cursor) {
-            Ok(packet) => Ok(packet),
-            Err(e) => Err(e.into()),
-        }
-    }
-}
-
-impl Attributes for PacketSessionData {
-    fn header(&self) -> PacketHeader {
-        self.header.clone()
-    }
-
-    fn packet_id(&self) -> PacketID {
-        self.header.packet_id.into()
-    }
-}
-
 #[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
 #[repr(C, packed)]
 pub struct MarshalZone {
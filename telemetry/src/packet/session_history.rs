@@ -1,6 +1,5 @@
 use serde_big_array::BigArray;
-
-use super::{Attributes, FromBytes};
+use telemetry_derive::Packet;
 
 /// # Session History Packet
 ///
@@ -12,7 +11,8 @@ use super::{Attributes, FromBytes};
 /// Frequency: 20 per second but cycling through cars  
 /// Size: 1460 bytes  
 /// Version: 1
-#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize, Packet)]
+#[packet(id = 11, size = 1460)]
 #[repr(C, packed)]
 pub struct PacketSessionHistoryData {
     /// Header
@@ -38,26 +38,6 @@ pub struct PacketSessionHistoryData {
     pub tyre_stints_history_data: [TyreStintHistoryData; 8],
 }
 
-impl FromBytes for PacketSessionHistoryData {
-    fn from_bytes(buf: &[u8]) -> Result<Self, super::PacketError> {
-        let cursor = std::io::Cursor::new(buf);
-        match bincode::deserialize_from(cursor) {
-            Ok(packet) => Ok(packet),
-            Err(e) => Err(e.into()),
-        }
-    }
-}
-
-impl Attributes for PacketSessionHistoryData {
-    fn header(&self) -> super::PacketHeader {
-        self.header.clone()
-    }
-
-    fn packet_id(&self) -> super::PacketID {
-        self.header().packet_id.into()
-    }
-}
-
 #[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
 #[repr(C, packed)]
 pub struct LapHistoryData {
@@ -84,9 +64,9 @@ pub struct LapHistoryData {
 #[repr(C, packed)]
 pub struct TyreStintHistoryData {
     /// Lap the tyre usage ends on (255 if current tyre)
-    end_lap: u8,
+    pub end_lap: u8,
     /// Actual tyres used
-    tyre_actual_compound: u8,
+    pub tyre_actual_compound: u8,
     /// Visual tyres used
-    tyre_visual_compound: u8,
+    pub tyre_visual_compound: u8,
 }
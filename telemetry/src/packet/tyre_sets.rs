@@ -1,4 +1,4 @@
-use super::{Attributes, FromBytes};
+use telemetry_derive::Packet;
 
 /// # Tyre Sets Packet
 ///
@@ -7,7 +7,8 @@ use super::{Attributes, FromBytes};
 /// Frequency: 20 per second but cycling through cars  
 /// Size: 231 bytes  
 /// Version: 1
-#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize, Packet)]
+#[packet(id = 12, size = 231)]
 #[repr(C, packed)]
 pub struct PacketTyreSetData {
     /// Header
@@ -20,26 +21,6 @@ pub struct PacketTyreSetData {
     pub fitted_idx: u8,
 }
 
-impl FromBytes for PacketTyreSetData {
-    fn from_bytes(buf: &[u8]) -> Result<Self, super::PacketError> {
-        let cursor = std::io::Cursor::new(buf);
-        match bincode::deserialize_from(cursor) {
-            Ok(packet) => Ok(packet),
-            Err(e) => Err(e.into()),
-        }
-    }
-}
-
-impl Attributes for PacketTyreSetData {
-    fn header(&self) -> super::PacketHeader {
-        self.header.clone()
-    }
-
-    fn packet_id(&self) -> super::PacketID {
-        self.header().packet_id.into()
-    }
-}
-
 #[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
 #[repr(C, packed)]
 pub struct TyreSetData {
@@ -0,0 +1,151 @@
+use std::path::Path;
+
+use telemetry::*;
+
+fn corpus(name: &str) -> Vec<u8> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fuzz/corpus/packet")
+        .join(name);
+    std::fs::read(path).unwrap()
+}
+
+/// Gives every car of a zeroed packet its own value in one field, then checks each `decode_car`
+/// slot against the fully decoded array. A wrong stride reads a neighbour's value or padding
+macro_rules! check_cars {
+    ($packet:ty, $name:literal, $cars:ident, |$car:ident, $idx:ident| $mark:expr) => {{
+        let mut packet = <$packet>::from_bytes(&corpus($name)).unwrap();
+        let mut cars = { packet.$cars };
+        for ($idx, $car) in cars.iter_mut().enumerate() {
+            $mark;
+        }
+        packet.$cars = cars;
+        let buf = packet.to_bytes().unwrap();
+
+        let decoded = <$packet>::from_bytes(&buf).unwrap();
+        let cars = { decoded.$cars };
+        for (idx, car) in cars.iter().enumerate() {
+            assert_eq!(
+                &<$packet>::decode_car(&buf, idx).unwrap(),
+                car,
+                "{} car {idx}",
+                stringify!($packet)
+            );
+        }
+        buf
+    }};
+}
+
+#[test]
+fn decode_car_matches_the_decoded_packet() {
+    check_cars!(
+        PacketMotionData,
+        "motion-zeroed",
+        car_motion_data,
+        |car, idx| car.world_position_x = idx as f32 + 0.5
+    );
+    check_cars!(PacketLapData, "lap-zeroed", lap_data, |car, idx| car
+        .last_lap_time_in_ms =
+        90_000 + idx as u32);
+    check_cars!(
+        PacketParticipantsData,
+        "participants-zeroed",
+        participants,
+        |car, idx| car.race_number = idx as u8 + 1
+    );
+    check_cars!(
+        PacketCarSetupData,
+        "car_setups-zeroed",
+        car_setups,
+        |car, idx| car.front_camber = -(idx as f32)
+    );
+    check_cars!(
+        PacketCarTelemetryData,
+        "car_telemetry-zeroed",
+        car_telemetry_data,
+        |car, idx| car.speed = 200 + idx as u16
+    );
+    check_cars!(
+        PacketCarStatusData,
+        "car_status-zeroed",
+        car_status_data,
+        |car, idx| car.fuel_in_tank = idx as f32 * 2.0
+    );
+    check_cars!(
+        PacketFinalClassificationData,
+        "final_classification-zeroed",
+        classification_data,
+        |car, idx| car.best_lap_time_in_ms = 80_000 + idx as u32
+    );
+    check_cars!(
+        PacketCarDamageData,
+        "car_damage-zeroed",
+        car_damage_data,
+        |car, idx| car.tyres_wear = [idx as f32; 4]
+    );
+}
+
+#[test]
+fn decode_car_rejects_bad_indexes_and_short_buffers() {
+    let buf = check_cars!(PacketLapData, "lap-zeroed", lap_data, |car, idx| car
+        .current_lap_time_in_ms =
+        idx as u32);
+
+    assert!(matches!(
+        PacketLapData::decode_car(&buf, MAX_CARS),
+        Err(PacketError::CarIndexOutOfBounds(22))
+    ));
+
+    // cut off in the middle of the last car
+    let last = PacketLapData::decode_car(&buf, MAX_CARS - 1).unwrap();
+    let slot = std::mem::size_of_val(&last);
+    let end = PacketHeader::SIZE + MAX_CARS * slot;
+    let truncated = &buf[..end - 1];
+    assert!(matches!(
+        PacketLapData::decode_car(truncated, MAX_CARS - 1),
+        Err(PacketError::BufferTooShort { expected, actual }) if expected == end && actual == end - 1
+    ));
+    assert_eq!(
+        PacketLapData::decode_car(truncated, MAX_CARS - 2).unwrap(),
+        { PacketLapData::from_bytes(&buf).unwrap().lap_data }[MAX_CARS - 2]
+    );
+
+    assert!(matches!(
+        PacketLapData::decode_car(&buf[..PacketHeader::SIZE - 1], 0),
+        Err(PacketError::BufferTooShort { .. })
+    ));
+    assert!(matches!(
+        PacketMotionData::decode_car(&buf, 0),
+        Err(PacketError::InvalidPacketID(2))
+    ));
+}
+
+#[test]
+fn peek_reads_only_the_header() {
+    let mut buf = corpus("lap-zeroed");
+    buf[7..15].copy_from_slice(&42u64.to_le_bytes());
+    buf[15..19].copy_from_slice(&12.5f32.to_le_bytes());
+    buf[19..23].copy_from_slice(&7u32.to_le_bytes());
+    buf[23..27].copy_from_slice(&9u32.to_le_bytes());
+    buf[27] = 3;
+
+    // the payload isn't needed
+    let header = PacketHeader::peek(&buf[..PacketHeader::SIZE]).unwrap();
+    assert_eq!(header, PacketHeader::from_bytes(&buf).unwrap());
+    assert_eq!(header.packet_id(), PacketID::Lap);
+    assert_eq!({ header.session_uid }, 42);
+    assert_eq!({ header.session_time }, 12.5);
+    assert_eq!({ header.frame_identifier }, 7);
+    assert_eq!({ header.overall_frame_identifier }, 9);
+    assert_eq!({ header.player_car_index }, 3);
+
+    assert!(matches!(
+        PacketHeader::peek(&buf[..PacketHeader::SIZE - 1]),
+        Err(PacketError::BufferTooShort { expected, actual })
+            if expected == PacketHeader::SIZE && actual == PacketHeader::SIZE - 1
+    ));
+    buf[6] = 200;
+    assert!(matches!(
+        PacketHeader::peek(&buf),
+        Err(PacketError::InvalidPacketID(200))
+    ));
+}
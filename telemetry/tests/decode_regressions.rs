@@ -0,0 +1,76 @@
+//! Inputs that used to panic the decoder. Anything `cargo fuzz` finds gets minimised, copied into
+//! `fuzz/corpus/<target>/` as `crash-<description>` and replayed by
+//! `fuzz_corpus_decodes_without_panicking`.
+
+use std::path::Path;
+
+use telemetry::*;
+
+fn header(packet_id: u8) -> Vec<u8> {
+    let mut buf = vec![0; PacketHeader::SIZE];
+    buf[..2].copy_from_slice(&2023u16.to_le_bytes());
+    buf[6] = packet_id;
+    buf
+}
+
+#[test]
+fn truncated_event_is_an_error() {
+    // header plus half of the event string code
+    let mut buf = header(3);
+    buf.extend_from_slice(b"FT");
+
+    assert!(matches!(
+        PacketEventData::from_bytes(&buf),
+        Err(PacketError::BufferTooShort { expected: 45, .. })
+    ));
+    assert!(Packet::from_bytes(&buf).is_err());
+}
+
+#[test]
+fn unknown_packet_id_is_an_error() {
+    let mut buf = header(200);
+    buf.resize(PacketLapData::SIZE, 0);
+
+    assert!(matches!(
+        Packet::from_bytes(&buf),
+        Err(PacketError::InvalidPacketID(200))
+    ));
+    assert!(matches!(
+        PacketLapData::from_bytes(&buf),
+        Err(PacketError::InvalidPacketID(200))
+    ));
+}
+
+#[test]
+fn fuzz_corpus_decodes_without_panicking() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus");
+    let mut inputs = 0;
+
+    for target in std::fs::read_dir(corpus).unwrap() {
+        for input in std::fs::read_dir(target.unwrap().path()).unwrap() {
+            let buf = std::fs::read(input.unwrap().path()).unwrap();
+            inputs += 1;
+
+            let _ = PacketHeader::peek(&buf);
+            if let Ok(packet) = Packet::from_bytes(&buf) {
+                let bytes = packet.to_bytes().unwrap();
+                assert_eq!(
+                    Packet::from_bytes(&bytes).unwrap().to_bytes().unwrap(),
+                    bytes
+                );
+            }
+            for idx in 0..=MAX_CARS {
+                let _ = PacketMotionData::decode_car(&buf, idx);
+                let _ = PacketLapData::decode_car(&buf, idx);
+                let _ = PacketParticipantsData::decode_car(&buf, idx);
+                let _ = PacketCarSetupData::decode_car(&buf, idx);
+                let _ = PacketCarTelemetryData::decode_car(&buf, idx);
+                let _ = PacketCarStatusData::decode_car(&buf, idx);
+                let _ = PacketFinalClassificationData::decode_car(&buf, idx);
+                let _ = PacketCarDamageData::decode_car(&buf, idx);
+            }
+        }
+    }
+
+    assert!(inputs > 0, "fuzz corpus is empty");
+}
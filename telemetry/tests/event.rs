@@ -0,0 +1,90 @@
+use telemetry::*;
+
+fn penalty_event(penalty_type: u8, infringement_type: u8) -> Vec<u8> {
+    let mut buf = vec![0; PacketHeader::SIZE];
+    buf[..2].copy_from_slice(&2023u16.to_le_bytes());
+    buf[6] = 3;
+    buf.extend_from_slice(b"PENL");
+    buf.extend_from_slice(&[penalty_type, infringement_type, 4, 255, 0, 12, 0]);
+    buf.resize(PacketEventData::SIZE, 0);
+    buf
+}
+
+#[test]
+fn penalty_types_match_the_appendix() {
+    let appendix = [
+        (0, PenaltyType::DriveThrough),
+        (1, PenaltyType::StopGo),
+        (2, PenaltyType::GridPenalty),
+        (3, PenaltyType::PenaltyReminder),
+        (4, PenaltyType::TimePenalty),
+        (5, PenaltyType::Warning),
+        (6, PenaltyType::Disqualified),
+        (7, PenaltyType::RemovedFromFormationLap),
+        (8, PenaltyType::ParkedTooLongTimer),
+        (9, PenaltyType::TyreRegulation),
+        (10, PenaltyType::ThisLapInvalidated),
+        (11, PenaltyType::ThisAndNextLapInvalidated),
+        (12, PenaltyType::ThisLapInvalidatedWithoutReason),
+        (13, PenaltyType::ThisAndNextLapInvalidatedWithoutReason),
+        (14, PenaltyType::ThisAndPreviousLapInvalidated),
+        (15, PenaltyType::ThisAndPreviousLapInvalidatedWithoutReason),
+        (16, PenaltyType::Retired),
+        (17, PenaltyType::BlackFlagTimer),
+    ];
+    for (id, penalty) in appendix {
+        assert_eq!(PenaltyType::from(id), penalty);
+        assert_eq!(u8::from(penalty), id);
+        assert_ne!(penalty.severity(), PenaltySeverity::Unknown, "{penalty:?}");
+    }
+
+    assert_eq!(PenaltyType::from(18), PenaltyType::Unknown(18));
+    assert_eq!(u8::from(PenaltyType::Unknown(18)), 18);
+    assert_eq!(
+        PenaltyType::Unknown(18).severity(),
+        PenaltySeverity::Unknown
+    );
+}
+
+#[test]
+fn infringement_types_round_trip() {
+    for id in 0..=54 {
+        let infringement = InfringementType::from(id);
+        assert!(
+            !matches!(infringement, InfringementType::Unknown(_)),
+            "{id} is in the appendix"
+        );
+        assert_eq!(u8::from(infringement), id);
+    }
+
+    assert_eq!(InfringementType::from(55), InfringementType::Unknown(55));
+    assert_eq!(u8::from(InfringementType::Unknown(55)), 55);
+}
+
+#[test]
+fn penalty_events_decode_the_appendix_numbering() {
+    let event = PacketEventData::from_bytes(&penalty_event(17, 44)).unwrap();
+    let EventDataDetails::Penalty(penalty) = event.event_details else {
+        panic!("expected a penalty, got {:?}", { event.event_details });
+    };
+    assert_eq!({ penalty.penalty_type }, PenaltyType::BlackFlagTimer);
+    assert_eq!(penalty.penalty_type.description(), "Black flag timer");
+    assert_eq!(
+        { penalty.infringement_type },
+        InfringementType::BlackFlagTimer
+    );
+
+    let event = PacketEventData::from_bytes(&penalty_event(200, 200)).unwrap();
+    let EventDataDetails::Penalty(penalty) = event.event_details else {
+        panic!("expected a penalty, got {:?}", { event.event_details });
+    };
+    assert_eq!({ penalty.penalty_type }, PenaltyType::Unknown(200));
+    assert_eq!(
+        { penalty.infringement_type },
+        InfringementType::Unknown(200)
+    );
+    assert_eq!(
+        PacketEventData::from_bytes(&event.to_bytes().unwrap()).unwrap(),
+        event
+    );
+}